@@ -0,0 +1,128 @@
+//! Measures the cost the `StatisticLogger` adds to each request: when
+//! packets are queued, when they are dropped because the queue is
+//! full, and when a `NullLogger` lets requests skip logging entirely.
+//! Run with `cargo bench`.
+
+extern crate hyper;
+extern crate iron;
+extern crate iron_test;
+extern crate playground_middleware;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Instant;
+
+use hyper::header::{Headers, UserAgent};
+use iron::prelude::*;
+use iron::{status, AroundMiddleware, Handler};
+use iron_test::request;
+use playground_middleware::{LogPacket, LogWriter, NullLogger, OverflowPolicy, StatisticLogger};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const REQUESTS: usize = 20_000;
+
+/// Throws packets away like `NullLogger`, but is not known to, so they
+/// are still built and queued; this is what a `NullLogger` cost before
+/// requests skipped logging.
+struct Discard;
+
+impl LogWriter for Discard {
+    type Error = io::Error;
+
+    fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Holds up the logging thread until the sender is dropped, keeping
+/// the queue full.
+struct Stalled(Receiver<()>);
+
+impl LogWriter for Stalled {
+    type Error = io::Error;
+
+    fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+        let _ = self.0.recv();
+        Ok(())
+    }
+}
+
+fn ok_handler(_req: &mut Request) -> IronResult<Response> {
+    Ok(Response::with(status::Ok))
+}
+
+fn headers() -> Headers {
+    let mut headers = Headers::new();
+    headers.set(UserAgent("Mozilla/5.0 (X11; Linux x86_64) bench/1.0".into()));
+    headers
+}
+
+fn measure<H: Handler>(name: &str, handler: &H) -> (f64, f64) {
+    let url = "http://127.0.0.1/some/fairly/long/path/to/a/resource?with=a&query=string";
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..REQUESTS {
+        request::get(url, headers(), handler).expect("Request failed");
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    let nanos = elapsed.as_secs() as f64 * 1e9 + elapsed.subsec_nanos() as f64;
+    let per_request = (nanos / REQUESTS as f64, allocations as f64 / REQUESTS as f64);
+    println!("{:<24} {:>8.0} ns/request {:>6.1} allocations/request", name, per_request.0, per_request.1);
+    per_request
+}
+
+fn main() {
+    let (baseline_ns, baseline_allocs) = measure("no logger", &ok_handler);
+
+    let queued = StatisticLogger::with_capacity(Discard, REQUESTS).around(Box::new(ok_handler));
+    let (queued_ns, queued_allocs) = measure("queued", &queued);
+    drop(queued);
+
+    let null = StatisticLogger::with_capacity(NullLogger::new(), REQUESTS).around(Box::new(ok_handler));
+    let (null_ns, null_allocs) = measure("null logger", &null);
+    drop(null);
+
+    let (release, stalled) = mpsc::channel();
+    let full = StatisticLogger::builder()
+        .capacity(1)
+        .overflow_policy(OverflowPolicy::DropNewest)
+        .build(Stalled(stalled))
+        .around(Box::new(ok_handler));
+    // One packet holds up the logging thread and one fills the queue
+    for _ in 0..2 {
+        request::get("http://127.0.0.1/", Headers::new(), &full).expect("Request failed");
+    }
+    let (dropped_ns, dropped_allocs) = measure("dropped (queue full)", &full);
+    drop(release);
+    drop(full);
+
+    println!();
+    println!("logging overhead when queued:  {:>6.0} ns {:>5.1} allocations",
+             queued_ns - baseline_ns, queued_allocs - baseline_allocs);
+    println!("logging overhead when dropped: {:>6.0} ns {:>5.1} allocations",
+             dropped_ns - baseline_ns, dropped_allocs - baseline_allocs);
+    println!("logging overhead when null:    {:>6.0} ns {:>5.1} allocations",
+             null_ns - baseline_ns, null_allocs - baseline_allocs);
+}
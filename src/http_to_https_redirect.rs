@@ -10,6 +10,8 @@ pub struct HttpToHttpsRedirect {
 }
 
 impl HttpToHttpsRedirect {
+    /// Redirects to `host`, which is not checked until a request is
+    /// redirected. Requests fail with a server error if it is invalid.
     pub fn new(host: &str, port: u16) -> Self {
         HttpToHttpsRedirect {
             permanent: false,
@@ -18,6 +20,12 @@ impl HttpToHttpsRedirect {
         }
     }
 
+    /// Like `new`, but returns an error if `host` is not a valid host.
+    pub fn try_new(host: &str, port: u16) -> Result<Self, url::ParseError> {
+        try!(url::Host::parse(host));
+        Ok(HttpToHttpsRedirect::new(host, port))
+    }
+
     pub fn temporary(self) -> Self {
         HttpToHttpsRedirect { permanent: false, ..self }
     }
@@ -31,8 +39,11 @@ impl iron::Handler for HttpToHttpsRedirect {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
         let mut url: url::Url = req.url.clone().into();
 
+        // HTTP URLs always have a host, so only the new host can fail
         url.set_scheme("https").expect("Unable to rewrite URL scheme");
-        url.set_host(Some(&self.host)).expect("Unable to rewrite URL host");
+        if let Err(e) = url.set_host(Some(&self.host)) {
+            return Err(IronError::new(e, status::InternalServerError));
+        }
         url.set_port(Some(self.port)).expect("Unable to rewrite URL port");
 
         let url = iron::Url::from_generic_url(url).expect("Unable to rewrite HTTP URL to HTTPS");
@@ -41,3 +52,28 @@ impl iron::Handler for HttpToHttpsRedirect {
         Ok(Response::with((status, iron::modifiers::Redirect(url))))
     }
 }
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    #[test]
+    fn invalid_hosts_are_rejected_up_front() {
+        assert!(HttpToHttpsRedirect::try_new("example.com", 443).is_ok());
+        assert!(HttpToHttpsRedirect::try_new("exa mple.com", 443).is_err());
+    }
+
+    #[test]
+    fn invalid_hosts_fail_requests_instead_of_panicking() {
+        let redirect = HttpToHttpsRedirect::new("exa mple.com", 443);
+
+        let err = request::get("http://127.0.0.1/", Headers::new(), &redirect).unwrap_err();
+        assert_eq!(err.response.status, Some(status::InternalServerError));
+    }
+}
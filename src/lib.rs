@@ -1,13 +1,37 @@
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_derive;
 
 extern crate csv;
+extern crate flate2;
+extern crate hyper;
 extern crate iron;
+#[cfg(feature = "testing")]
+extern crate iron_test;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "metrics")]
+extern crate metrics;
 extern crate mime;
 extern crate mime_guess;
-extern crate rustc_serialize;
+#[cfg(feature = "otel")]
+extern crate opentelemetry;
+#[cfg(feature = "postgres")]
+extern crate postgres;
+extern crate regex;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+extern crate sha2;
+extern crate socket2;
 extern crate time;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 extern crate url;
+extern crate uuid;
 
 mod cache;
 mod guess_content_type;
@@ -17,11 +41,49 @@ mod modify_with;
 mod prefix;
 mod rewrite;
 mod staticfile;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use cache::Cache;
 pub use guess_content_type::GuessContentType;
 pub use http_to_https_redirect::HttpToHttpsRedirect;
-pub use logging::{StatisticLogger, FileLogger};
+pub use logging::{StatisticLogger, StatisticLoggerBuilder, ErrorHandler, LogErrors};
+pub use logging::{OverflowPolicy, FlushStrategy, DroppedPackets, ShutdownHandle};
+pub use logging::{Contention, LoggerHandle, LoggingError, QueueStats, QueueStatsHandler};
+pub use logging::{HealthHandler, LastError};
+pub use logging::{SyncStatisticLogger, LogWriter, LogPacket, LogFields, RoutePattern, TlsInfo};
+pub use logging::{BeforeLogger, AfterLogger};
+pub use logging::{PathFilter, PrefixLogger, Sampling, TailSampling, Clock, SystemClock, TimingSource};
+pub use logging::{IpAnonymization, QueryLogging, RedactionPolicy, Column, Encoding, StatusFormat, TimingUnit, TimestampFormat};
+pub use logging::{FileLogger, CsvQuoting, StreamLogger, JsonLogger, RotatingFileLogger, FileRotationPolicy, DailyFileLogger};
+pub use logging::{HourlyFileLogger, SyncPolicy, LogReader, LogRecord};
+pub use logging::{GzFileLogger, CommonLogFormatLogger, MemoryLogger, NullLogger, LogCrateLogger};
+pub use logging::{RingBufferLogger, RingBufferHandler};
+pub use logging::{Formatter, CsvFormatter, JsonFormatter, CombinedLogFormatter, FileFormat};
+pub use logging::{MultiLogger, MultiError, RoutingLogger, OnlyStatus, Inspect, MapLogger, Summarized, Summary, RetryLogger};
+pub use logging::{RateLimitLogger, RateLimitPolicy, BackgroundLogger};
+pub use logging::{BoxedLogWriter, BoxedError};
+pub use logging::{FallbackLogger, FallbackError, DeadLetterLogger};
+pub use logging::{TcpLogger, TcpFormat, NetworkOptions, SyslogLogger, Facility, Severity, WebhookLogger, WebhookError};
+pub use logging::{GraphiteLogger, InfluxLogger};
+pub use logging::EmfLogger;
+pub use logging::{StatsdLogger, PrometheusRecorder, PrometheusHandler, LatencyRecorder, LatencyHandler};
+pub use logging::{RequestRate, RouteAggregator, RouteStats, SlidingWindow, StatusCounter, StatusCounts, StatusCountHandler};
+pub use logging::{StatsRecorder, StatsHandler, ByteCounter, ByteCounts, ByteCountHandler};
+pub use logging::{InFlight, InFlightCounts, InFlightHandler};
+pub use logging::{Aggregator, AggregateLogger, AggregateHandler};
+#[cfg(unix)]
+pub use logging::FifoLogger;
+#[cfg(feature = "metrics")]
+pub use logging::MetricsFacadeLogger;
+#[cfg(feature = "otel")]
+pub use logging::OtelLogger;
+#[cfg(feature = "postgres")]
+pub use logging::PostgresLogger;
+#[cfg(feature = "sqlite")]
+pub use logging::SqliteLogger;
+#[cfg(feature = "tracing")]
+pub use logging::TracingLogger;
 pub use modify_with::ModifyWith;
 pub use prefix::Prefix;
 pub use rewrite::Rewrite;
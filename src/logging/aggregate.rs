@@ -0,0 +1,150 @@
+use std::io;
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::Handler;
+use serde_json::{Map, Value};
+
+use super::{json_response, LogPacket, LogWriter};
+
+/// A rollup of the logged requests, such as a counter or a latency
+/// histogram, kept up to date by an `AggregateLogger`. It is shared
+/// with the handler serving it, so it updates itself through `&self`.
+///
+/// `StatsRecorder`, `LatencyRecorder`, `StatusCounter`, `ByteCounter`,
+/// `RouteAggregator`, `RequestRate` and `SlidingWindow` are
+/// aggregators, as well as writers of their own.
+pub trait Aggregator: Send + Sync {
+    /// Adds one packet to the rollup.
+    fn observe(&self, packet: &LogPacket);
+
+    /// The rollup so far, as served by an `AggregateHandler`.
+    fn snapshot(&self) -> Value;
+}
+
+/// Gives every packet to each of its aggregators, registered by name,
+/// so one writer maintains any set of statistics:
+///
+/// ```
+/// use playground_middleware::{AggregateLogger, RequestRate, StatisticLogger, StatsRecorder};
+///
+/// let aggregates = AggregateLogger::new()
+///     .aggregator("stats", StatsRecorder::new())
+///     .aggregator("rate", RequestRate::new());
+/// let stats = aggregates.handler();
+/// let logger = StatisticLogger::new(aggregates);
+/// ```
+///
+/// Clones share the same aggregators.
+#[derive(Clone, Default)]
+pub struct AggregateLogger {
+    aggregators: Vec<(String, Arc<Aggregator>)>,
+}
+
+impl AggregateLogger {
+    pub fn new() -> AggregateLogger {
+        AggregateLogger::default()
+    }
+
+    /// Adds `aggregator`, whose snapshot is served under `name`.
+    pub fn aggregator<A>(mut self, name: &str, aggregator: A) -> Self
+        where A: Aggregator + 'static
+    {
+        self.aggregators.push((name.into(), Arc::new(aggregator)));
+        self
+    }
+
+    /// Every aggregator's snapshot in a JSON object by name, like
+    /// `{"stats":{"requests":3,...},"rate":{"current":1.0,...}}`. A
+    /// name registered twice shows the last one's.
+    pub fn snapshot(&self) -> Value {
+        let snapshots: Map<String, Value> = self.aggregators.iter()
+            .map(|(name, aggregator)| (name.clone(), aggregator.snapshot()))
+            .collect();
+        Value::Object(snapshots)
+    }
+
+    /// A handler serving the `snapshot` as JSON, typically at
+    /// `/stats`.
+    pub fn handler(&self) -> AggregateHandler {
+        AggregateHandler(self.clone())
+    }
+}
+
+impl LogWriter for AggregateLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        for (_, aggregator) in &self.aggregators {
+            aggregator.observe(packet);
+        }
+        Ok(())
+    }
+}
+
+/// Serves the snapshots of an `AggregateLogger`'s aggregators as JSON.
+pub struct AggregateHandler(AggregateLogger);
+
+impl Handler for AggregateHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&self.0.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{StatusCounter, StatsRecorder};
+
+    /// Counts the requests for one path.
+    struct PathCounter {
+        path: &'static str,
+        count: AtomicU64,
+    }
+
+    impl Aggregator for PathCounter {
+        fn observe(&self, packet: &LogPacket) {
+            if packet.path() == self.path {
+                self.count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fn snapshot(&self) -> Value {
+            json!({"path": self.path, "count": self.count.load(Ordering::Relaxed)})
+        }
+    }
+
+    fn packet(path: &str) -> LogPacket {
+        let mut packet = LogPacket::stub();
+        packet.url = iron::Url::parse(&format!("http://127.0.0.1{}", path)).unwrap();
+        packet
+    }
+
+    #[test]
+    fn every_aggregator_is_served_by_name() {
+        let mut logger = AggregateLogger::new()
+            .aggregator("checkout", PathCounter { path: "/checkout", count: AtomicU64::new(0) })
+            .aggregator("status", StatusCounter::new())
+            .aggregator("stats", StatsRecorder::new());
+        let handler = logger.handler();
+
+        for path in &["/checkout", "/cart", "/checkout", "/"] {
+            logger.log(&packet(path)).unwrap();
+        }
+
+        let res = request::get("http://127.0.0.1/stats", Headers::new(), &handler).unwrap();
+        let json: Value = serde_json::from_str(&response::extract_body_to_string(res)).unwrap();
+        assert_eq!(json["checkout"], json!({"path": "/checkout", "count": 2}));
+        assert_eq!(json["status"]["2xx"], 4);
+        assert_eq!(json["stats"]["requests"], 4);
+    }
+}
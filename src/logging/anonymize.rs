@@ -0,0 +1,115 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use sha2::{Digest, Sha256};
+
+/// How client addresses are anonymized before being logged. The port
+/// is kept in every mode.
+///
+/// IPv4-mapped IPv6 addresses, like `::ffff:192.0.2.1`, are treated as
+/// the IPv4 address they contain. Unspecified addresses, which stand
+/// for a missing client address, are left as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum IpAnonymization {
+    /// Log addresses as they are.
+    #[default]
+    None,
+    /// Zero the last octet of IPv4 addresses (keeping the /24) and the
+    /// 64-bit interface identifier of IPv6 addresses (keeping the /64).
+    MaskLastOctet,
+    /// Zero the last 80 bits of IPv6 addresses (keeping the /48),
+    /// which also hides the subnet. IPv4 addresses have their last
+    /// octet zeroed.
+    MaskLast80BitsV6,
+    /// Replace the address with a pseudonym derived from a SHA-256
+    /// hash of the salt and the address. The same client always gets
+    /// the same pseudonym for a given salt, so requests can still be
+    /// correlated. Pseudonyms are written as IPv6 addresses in the
+    /// unique local range `fd00::/8`.
+    HashSalted(String),
+}
+
+impl IpAnonymization {
+    pub fn apply(&self, addr: SocketAddr) -> SocketAddr {
+        if addr.ip().is_unspecified() {
+            return addr;
+        }
+        let ip = match addr.ip() {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(IpAddr::V6(ip)),
+            ip => ip,
+        };
+
+        let ip = match *self {
+            IpAnonymization::None => return addr,
+            IpAnonymization::MaskLastOctet => mask(ip, 8, 64),
+            IpAnonymization::MaskLast80BitsV6 => mask(ip, 8, 80),
+            IpAnonymization::HashSalted(ref salt) => hash(ip, salt),
+        };
+        SocketAddr::new(ip, addr.port())
+    }
+}
+
+fn mask(ip: IpAddr, v4_bits: u32, v6_bits: u32) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(u32::from(ip) & (!0 << v4_bits))),
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & (!0 << v6_bits))),
+    }
+}
+
+fn hash(ip: IpAddr, salt: &str) -> IpAddr {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    match ip {
+        IpAddr::V4(ip) => hasher.update(ip.octets()),
+        IpAddr::V6(ip) => hasher.update(ip.octets()),
+    }
+    let digest = hasher.finalize();
+
+    let mut octets = [0; 16];
+    octets[0] = 0xfd;
+    octets[1..].copy_from_slice(&digest[..15]);
+    IpAddr::V6(Ipv6Addr::from(octets))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn anonymize(anonymization: IpAnonymization, addr: &str) -> String {
+        anonymization.apply(addr.parse().unwrap()).to_string()
+    }
+
+    #[test]
+    fn addresses_are_unchanged_by_default() {
+        assert_eq!(anonymize(IpAnonymization::None, "192.0.2.77:3000"), "192.0.2.77:3000");
+        assert_eq!(anonymize(IpAnonymization::None, "[2001:db8::77]:3000"), "[2001:db8::77]:3000");
+    }
+
+    #[test]
+    fn the_last_octet_can_be_masked() {
+        let mode = IpAnonymization::MaskLastOctet;
+        assert_eq!(anonymize(mode.clone(), "192.0.2.77:3000"), "192.0.2.0:3000");
+        assert_eq!(anonymize(mode.clone(), "[2001:db8:1:2:3:4:5:6]:3000"), "[2001:db8:1:2::]:3000");
+        assert_eq!(anonymize(mode, "[::ffff:192.0.2.77]:3000"), "192.0.2.0:3000");
+    }
+
+    #[test]
+    fn the_last_80_bits_can_be_masked() {
+        let mode = IpAnonymization::MaskLast80BitsV6;
+        assert_eq!(anonymize(mode.clone(), "[2001:db8:1:2:3:4:5:6]:3000"), "[2001:db8:1::]:3000");
+        assert_eq!(anonymize(mode, "192.0.2.77:3000"), "192.0.2.0:3000");
+    }
+
+    #[test]
+    fn addresses_can_be_hashed_with_a_salt() {
+        let mode = IpAnonymization::HashSalted("pepper".into());
+        let hashed = anonymize(mode.clone(), "192.0.2.77:3000");
+
+        assert!(hashed.starts_with("[fd"), "{}", hashed);
+        assert!(hashed.ends_with("]:3000"), "{}", hashed);
+        assert!(!hashed.contains("192.0.2.77"), "{}", hashed);
+        assert_eq!(anonymize(mode.clone(), "192.0.2.77:3000"), hashed);
+        assert_ne!(anonymize(mode.clone(), "192.0.2.78:3000"), hashed);
+        assert_ne!(anonymize(IpAnonymization::HashSalted("salt".into()), "192.0.2.77:3000"), hashed);
+        assert_ne!(anonymize(mode, "[2001:db8::77]:3000"), hashed);
+    }
+}
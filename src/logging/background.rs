@@ -0,0 +1,208 @@
+use std::error;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use super::{lock, DroppedPackets, LogPacket, LogWriter, Summary};
+
+enum Message<E> {
+    Packet(Box<LogPacket>),
+    Finalize(Summary, Sender<Result<(), E>>),
+}
+
+/// Runs a writer on its own thread, behind its own queue, so a slow
+/// writer does not hold up the others in a `MultiLogger`. Packets are
+/// dropped, and counted, while its queue is full.
+///
+/// The writer is flushed whenever it has caught up with its queue.
+/// Its failures cannot be returned by the call that queued the
+/// packet, so the latest one is returned by the next call instead.
+/// Dropping the logger waits for the writer to finish its queue.
+pub struct BackgroundLogger<E> {
+    tx: Option<SyncSender<Message<E>>>,
+    thread: Option<JoinHandle<()>>,
+    error: Arc<Mutex<Option<E>>>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl<E: error::Error + Send + 'static> BackgroundLogger<E> {
+    /// Starts a thread writing to `logger`, holding up to `capacity`
+    /// packets it has yet to write.
+    pub fn new<L>(logger: L, capacity: usize) -> io::Result<BackgroundLogger<E>>
+        where L: LogWriter<Error = E> + Send + 'static
+    {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let error = Arc::new(Mutex::new(None));
+
+        let thread_error = error.clone();
+        let thread = try!(thread::Builder::new()
+            .name("playground-background-logger".into())
+            .spawn(move || write(logger, rx, thread_error)));
+
+        Ok(BackgroundLogger {
+            tx: Some(tx),
+            thread: Some(thread),
+            error: error,
+            dropped: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Counts the packets discarded while the queue was full. Take it
+    /// before the logger is moved to the logging thread.
+    pub fn dropped(&self) -> DroppedPackets {
+        DroppedPackets(self.dropped.clone())
+    }
+
+    fn last_error(&self) -> Result<(), E> {
+        lock(&self.error).take().map_or(Ok(()), Err)
+    }
+}
+
+fn write<L: LogWriter>(mut logger: L, rx: Receiver<Message<L::Error>>, error: Arc<Mutex<Option<L::Error>>>) {
+    let record = |result| if let Err(e) = result {
+        *lock(&error) = Some(e);
+    };
+
+    let mut unflushed = false;
+    loop {
+        let message = match rx.try_recv() {
+            Ok(message) => message,
+            Err(TryRecvError::Empty) if unflushed => {
+                unflushed = false;
+                record(logger.flush());
+                continue;
+            }
+            Err(TryRecvError::Empty) => match rx.recv() {
+                Ok(message) => message,
+                Err(_) => break,
+            },
+            Err(TryRecvError::Disconnected) => break,
+        };
+
+        match message {
+            Message::Packet(packet) => {
+                unflushed = true;
+                record(logger.log(&packet));
+            }
+            Message::Finalize(summary, done) => {
+                let result = logger.finalize(&summary).and_then(|()| logger.flush());
+                unflushed = false;
+                let _ = done.send(result);
+            }
+        }
+    }
+
+    if unflushed {
+        record(logger.flush());
+    }
+}
+
+impl<E: error::Error + Send + 'static> LogWriter for BackgroundLogger<E> {
+    type Error = E;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let message = Message::Packet(Box::new(packet.clone()));
+        match self.tx.as_ref().map(|tx| tx.try_send(message)) {
+            Some(Ok(())) => {}
+            Some(Err(TrySendError::Full(_))) | Some(Err(TrySendError::Disconnected(_))) | None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.last_error()
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.last_error()
+    }
+
+    /// Waits for the writer to finish its queue and its summary, so
+    /// the summary is its final record.
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        let (done_tx, done_rx) = mpsc::channel();
+        if let Some(ref tx) = self.tx {
+            if tx.send(Message::Finalize(*summary, done_tx)).is_ok() {
+                if let Ok(result) = done_rx.recv() {
+                    try!(result);
+                }
+            }
+        }
+        self.last_error()
+    }
+}
+
+impl<E> Drop for BackgroundLogger<E> {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use super::super::MemoryLogger;
+
+    /// Waits for permission before writing each packet.
+    struct Stalled {
+        memory: MemoryLogger,
+        permits: Receiver<()>,
+    }
+
+    impl LogWriter for Stalled {
+        type Error = io::Error;
+
+        fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+            let _ = self.permits.recv();
+            self.memory.log(packet).map_err(io::Error::other)
+        }
+    }
+
+    struct Failing;
+
+    impl LogWriter for Failing {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    #[test]
+    fn a_stalled_writer_drops_packets_instead_of_blocking() {
+        let memory = MemoryLogger::new();
+        let (permit, permits) = mpsc::channel();
+        let mut logger = BackgroundLogger::new(Stalled { memory: memory.clone(), permits: permits }, 2).unwrap();
+        let dropped = logger.dropped();
+
+        // One packet is being written while two wait in the queue
+        for _ in 0..10 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert!(dropped.count() >= 7, "{}", dropped.count());
+        assert!(memory.packets().is_empty());
+
+        for _ in 0..10 {
+            permit.send(()).unwrap();
+        }
+        drop(logger);
+        assert_eq!(memory.packets().len(), 10 - dropped.count());
+    }
+
+    #[test]
+    fn failures_are_returned_by_the_next_call() {
+        let mut logger = BackgroundLogger::new(Failing, 16).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+
+        let summary = Summary { requests: 1, response_bytes: 0, uptime: Duration::from_secs(1) };
+        let error = logger.finalize(&summary).unwrap_err();
+        assert_eq!(error.to_string(), "disk full");
+        logger.flush().unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+use std::{error, fmt};
+
+use super::{LogPacket, LogWriter, Summary};
+
+/// An error from a writer whose concrete error type has been erased,
+/// allowing writers with different error types to be combined.
+#[derive(Debug)]
+pub struct BoxedError(Box<error::Error + Send>);
+
+impl BoxedError {
+    pub fn new<E>(error: E) -> BoxedError
+        where E: error::Error + Send + 'static
+    {
+        BoxedError(Box::new(error))
+    }
+}
+
+impl fmt::Display for BoxedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl error::Error for BoxedError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Adapts a writer to report `BoxedError`s, so that writers with
+/// different error types can be stored together, for example as
+/// `Box<LogWriter<Error = BoxedError> + Send>`, and chosen at runtime.
+pub struct BoxedLogWriter<L>(L);
+
+impl<L> BoxedLogWriter<L>
+    where L: LogWriter,
+          L::Error: Send + 'static,
+{
+    pub fn new(writer: L) -> BoxedLogWriter<L> {
+        BoxedLogWriter(writer)
+    }
+}
+
+impl<L> LogWriter for BoxedLogWriter<L>
+    where L: LogWriter,
+          L::Error: Send + 'static,
+{
+    type Error = BoxedError;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.0.log(packet).map_err(BoxedError::new)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush().map_err(BoxedError::new)
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.0.finalize(summary).map_err(BoxedError::new)
+    }
+
+    fn discards_everything(&self) -> bool {
+        self.0.discards_everything()
+    }
+}
+
+impl<E: error::Error> LogWriter for Box<LogWriter<Error = E> + Send> {
+    type Error = E;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        (**self).log(packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        (**self).flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        (**self).finalize(summary)
+    }
+
+    fn discards_everything(&self) -> bool {
+        (**self).discards_everything()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io;
+
+    use super::super::{MemoryLogger, StatisticLogger, StreamLogger};
+
+    #[test]
+    fn different_writers_can_be_stored_together() {
+        let memory = MemoryLogger::new();
+        let mut writers: Vec<Box<LogWriter<Error = BoxedError> + Send>> = vec![
+            Box::new(BoxedLogWriter::new(memory.clone())),
+            Box::new(BoxedLogWriter::new(StreamLogger::new(io::sink()))),
+        ];
+
+        for writer in &mut writers {
+            writer.log(&LogPacket::stub()).unwrap();
+            writer.flush().unwrap();
+        }
+
+        assert_eq!(memory.packets().len(), 1);
+
+        // A boxed writer is itself a writer
+        StatisticLogger::new(writers.remove(0));
+    }
+}
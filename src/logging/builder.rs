@@ -0,0 +1,849 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::fmt;
+
+use iron::prelude::*;
+
+use super::{Batch, Capture, IpAnonymization, LogPacket, LogWriter, OverflowPolicy, PathFilter, RequestFilter};
+use super::{Clock, FlushStrategy, QueryLogging, RedactionPolicy, RequestLabel, Sampling, SystemClock, TailSampling};
+use super::TimingSource;
+use super::capture;
+use super::coalesce::Coalesced;
+use super::inline::{self, Inline, SyncStatisticLogger};
+use super::sampling::TailSampled;
+use super::slow::{self, SlowRequestHandler, SlowRequests};
+use super::{LoggingError, Recorder, StatisticLogger, Threads, DEFAULT_CAPACITY,
+    DEFAULT_CONTENTION_THRESHOLD, DEFAULT_THREAD_NAME};
+
+/// Handles the packets a writer fails to record; see
+/// `StatisticLoggerBuilder::on_error`. It is implemented for closures
+/// taking the packet and the writer's own error type.
+pub trait ErrorHandler<E>: Send + 'static {
+    fn handle(&mut self, packet: &LogPacket, error: &E);
+}
+
+impl<E, F> ErrorHandler<E> for F
+    where F: FnMut(&LogPacket, &E) + Send + 'static
+{
+    fn handle(&mut self, packet: &LogPacket, error: &E) {
+        self(packet, error)
+    }
+}
+
+/// Reports every failure with `error!`, unless the builder is given
+/// another handler.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LogErrors;
+
+impl<E: fmt::Display> ErrorHandler<E> for LogErrors {
+    fn handle(&mut self, _packet: &LogPacket, error: &E) {
+        error!("Unable to log request: {}", error);
+    }
+}
+
+/// Configures a `StatisticLogger`. Every option is set here; the
+/// constructors on `StatisticLogger` are shortcuts for the common
+/// ones.
+///
+/// ```
+/// use std::time::Duration;
+/// use playground_middleware::{MemoryLogger, OverflowPolicy, StatisticLoggerBuilder};
+///
+/// let logger = StatisticLoggerBuilder::new()
+///     .capacity(4096)
+///     .overflow_policy(OverflowPolicy::DropOldest)
+///     .batch(100, Duration::from_secs(1))
+///     .filter(|req| req.url.path() != ["health"])
+///     .thread_name("access-log")
+///     .build(MemoryLogger::new());
+///
+/// assert_eq!(logger.capacity(), 4096);
+/// ```
+pub struct StatisticLoggerBuilder<H = LogErrors> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    contention_threshold: Duration,
+    batch: Batch,
+    capture: Capture,
+    filter: Option<RequestFilter>,
+    label: Option<RequestLabel>,
+    sampling: Option<Sampling>,
+    tail_sampling: Option<TailSampling>,
+    coalesce: Option<Duration>,
+    thread_name: String,
+    stack_size: Option<usize>,
+    on_error: H,
+    slow: Option<(Duration, SlowRequestHandler)>,
+    clock: Arc<Clock>,
+    timing: TimingSource,
+    catch_panics: bool,
+    enabled: bool,
+}
+
+impl StatisticLoggerBuilder {
+    pub fn new() -> StatisticLoggerBuilder {
+        StatisticLoggerBuilder {
+            capacity: DEFAULT_CAPACITY,
+            policy: OverflowPolicy::default(),
+            contention_threshold: DEFAULT_CONTENTION_THRESHOLD,
+            batch: Batch::default(),
+            capture: Capture::default(),
+            filter: None,
+            label: None,
+            sampling: None,
+            tail_sampling: None,
+            coalesce: None,
+            thread_name: DEFAULT_THREAD_NAME.into(),
+            stack_size: None,
+            on_error: LogErrors,
+            slow: None,
+            clock: Arc::new(SystemClock),
+            timing: TimingSource::default(),
+            catch_panics: false,
+            enabled: true,
+        }
+    }
+}
+
+impl<H> StatisticLoggerBuilder<H> {
+
+    /// Queues at most `capacity` packets for the logging thread. Once
+    /// the queue is full, the overflow policy applies, so a larger
+    /// capacity absorbs longer bursts at the cost of more memory held
+    /// by unwritten packets. Defaults to 1024.
+    pub fn capacity(self, capacity: usize) -> Self {
+        StatisticLoggerBuilder { capacity: capacity, ..self }
+    }
+
+    /// Chooses what happens when the queue is full. Defaults to
+    /// `OverflowPolicy::Block`.
+    pub fn overflow_policy(self, policy: OverflowPolicy) -> Self {
+        StatisticLoggerBuilder { policy: policy, ..self }
+    }
+
+    /// Counts the requests that wait longer than `threshold` to queue
+    /// their packet in `LoggerHandle::contention`. Defaults to one
+    /// millisecond.
+    pub fn contention_threshold(self, threshold: Duration) -> Self {
+        StatisticLoggerBuilder { contention_threshold: threshold, ..self }
+    }
+
+    /// Flushes the writer after every `size` packets, or once the
+    /// oldest unflushed packet has waited for `interval`, whichever
+    /// comes first. Flushing less often increases throughput, but more
+    /// packets are lost should the process crash. Any partial batch is
+    /// flushed when the logger shuts down. Defaults to flushing after
+    /// every packet.
+    pub fn batch(self, size: usize, interval: Duration) -> Self {
+        let batch = Batch {
+            size: size,
+            interval: Some(interval),
+            quiet: false,
+        };
+        StatisticLoggerBuilder { batch: batch, ..self }
+    }
+
+    /// Chooses when the writer is flushed, from a single setting.
+    /// Defaults to `FlushStrategy::EveryPacket`.
+    pub fn flush_strategy(self, strategy: FlushStrategy) -> Self {
+        StatisticLoggerBuilder { batch: Batch::from(strategy), ..self }
+    }
+
+    /// Only logs the requests for which `filter` returns true, such
+    /// as everything but health checks. Filtered requests are never
+    /// queued.
+    ///
+    /// When several filters are added, including with `path_filter`,
+    /// a request is only logged if every one of them allows it.
+    pub fn filter<F>(self, filter: F) -> Self
+        where F: Fn(&Request) -> bool + Send + Sync + 'static
+    {
+        self.add_filter(Box::new(filter))
+    }
+
+    /// Only logs the requests whose path the filter allows.
+    pub fn path_filter(self, paths: PathFilter) -> Self {
+        self.add_filter(Box::new(move |req: &Request| paths.allows(req.url.as_ref().path())))
+    }
+
+    /// Labels every logged request with what `label` returns for it,
+    /// such as a tenant taken from the subdomain or an experiment
+    /// bucket; see `LogPacket::label`. It runs while the request is
+    /// served, once the handler has returned, so it should be cheap
+    /// and free of side effects.
+    pub fn label<F>(self, label: F) -> Self
+        where F: Fn(&Request) -> Option<String> + Send + Sync + 'static
+    {
+        StatisticLoggerBuilder { label: Some(Box::new(label)), ..self }
+    }
+
+    fn add_filter(mut self, filter: RequestFilter) -> Self {
+        self.filter = Some(match self.filter.take() {
+            Some(previous) => Box::new(move |req: &Request| previous(req) && filter(req)),
+            None => filter,
+        });
+        self
+    }
+
+    /// Only logs a sample of the requests that pass the filters.
+    pub fn sampling(self, sampling: Sampling) -> Self {
+        StatisticLoggerBuilder { sampling: Some(sampling), ..self }
+    }
+
+    /// Only writes a sample of the ordinary requests that were queued,
+    /// but every failed or slow one. Requests are still queued and
+    /// timed, so this helps with log volume rather than overhead.
+    pub fn tail_sampling(self, sampling: TailSampling) -> Self {
+        StatisticLoggerBuilder { tail_sampling: Some(sampling), ..self }
+    }
+
+    /// Collapses requests with the same method, path and status, such
+    /// as health checks, that start within `window` of each other
+    /// into one packet whose `count` is the number of requests. Select
+    /// `Column::Count` to write the count to CSV. Slow requests are
+    /// still reported one by one.
+    pub fn coalesce(self, window: Duration) -> Self {
+        StatisticLoggerBuilder { coalesce: Some(window), ..self }
+    }
+
+    /// Names the logging thread, for profilers and crash dumps.
+    /// Defaults to `playground-logger`.
+    pub fn thread_name(self, name: &str) -> Self {
+        StatisticLoggerBuilder { thread_name: name.into(), ..self }
+    }
+
+    /// Sets the stack size of the logging thread, in bytes. Defaults
+    /// to the standard library's default for spawned threads.
+    pub fn stack_size(self, size: usize) -> Self {
+        StatisticLoggerBuilder { stack_size: Some(size), ..self }
+    }
+
+    /// Calls `on_error` with every packet the writer fails to record
+    /// and the writer's error, instead of reporting it with `error!`.
+    /// The logging thread continues with the next packet afterwards.
+    pub fn on_error<E, F>(self, on_error: F) -> StatisticLoggerBuilder<F>
+        where F: FnMut(&LogPacket, &E) + Send + 'static
+    {
+        StatisticLoggerBuilder {
+            capacity: self.capacity,
+            policy: self.policy,
+            contention_threshold: self.contention_threshold,
+            batch: self.batch,
+            capture: self.capture,
+            filter: self.filter,
+            label: self.label,
+            sampling: self.sampling,
+            tail_sampling: self.tail_sampling,
+            coalesce: self.coalesce,
+            thread_name: self.thread_name,
+            stack_size: self.stack_size,
+            on_error: on_error,
+            slow: self.slow,
+            clock: self.clock,
+            timing: self.timing,
+            catch_panics: self.catch_panics,
+            enabled: self.enabled,
+        }
+    }
+
+    /// Warns through the `log` crate about every request that takes
+    /// longer than `threshold`, in addition to logging it.
+    pub fn slow_threshold(self, threshold: Duration) -> Self {
+        self.on_slow_request(threshold, slow::warn(threshold))
+    }
+
+    /// Calls `on_slow` for every request that takes longer than
+    /// `threshold`, in addition to logging it. Like the writer, it runs
+    /// on the logging thread rather than while serving the request.
+    pub fn on_slow_request<F>(self, threshold: Duration, on_slow: F) -> Self
+        where F: FnMut(&LogPacket) + Send + 'static
+    {
+        StatisticLoggerBuilder { slow: Some((threshold, Box::new(on_slow))), ..self }
+    }
+
+    /// Takes request start times and durations from `clock` instead
+    /// of the system's clocks, and times flush intervals with it on
+    /// the logging thread.
+    pub fn clock<C>(self, clock: C) -> Self
+        where C: Clock + 'static
+    {
+        StatisticLoggerBuilder { clock: Arc::new(clock), ..self }
+    }
+
+    /// Measures the `timing` of requests with the wall-clock time
+    /// instead of the monotonic one; see `TimingSource`.
+    pub fn timing_source(self, source: TimingSource) -> Self {
+        StatisticLoggerBuilder { timing: source, ..self }
+    }
+
+    /// Catches panics in the wrapped handler, logging the request
+    /// with a 500 status and the panic message as its error. The
+    /// panic is turned into an `IronError` with the same status, so
+    /// the client gets a response. Panics unwind through the logger,
+    /// unlogged, by default.
+    pub fn catch_panics(self) -> Self {
+        StatisticLoggerBuilder { catch_panics: true, ..self }
+    }
+
+    /// Turns logging off, or back on, such as from a configuration
+    /// flag. While it is off, requests go straight to the handler,
+    /// without building a packet, as they do when every writer
+    /// discards everything. They are still counted in flight and given
+    /// a request ID.
+    pub fn enabled(self, enabled: bool) -> Self {
+        StatisticLoggerBuilder { enabled: enabled, ..self }
+    }
+
+    /// Records the client address from the `X-Forwarded-For` header
+    /// set by reverse proxies, falling back to the connecting address
+    /// when the header is missing or malformed.
+    ///
+    /// `trusted_proxies` is the number of proxies in front of the
+    /// server. Clients can send their own `X-Forwarded-For`, so only
+    /// the address added by the outermost trusted proxy is used. With
+    /// no trusted proxies, or fewer addresses than proxies, the header
+    /// is ignored.
+    pub fn trust_forwarded_for(mut self, trusted_proxies: usize) -> Self {
+        self.capture.forwarded_for = Some(trusted_proxies);
+        self
+    }
+
+    /// Records the scheme from the `X-Forwarded-Proto` header set by
+    /// TLS-terminating proxies, falling back to the scheme of the
+    /// request when the header is missing. Only enable this behind a
+    /// proxy, as clients can send the header themselves.
+    pub fn trust_forwarded_proto(mut self) -> Self {
+        self.capture.forwarded_proto = true;
+        self
+    }
+
+    /// Anonymizes client addresses before they are queued, so full
+    /// addresses never reach a writer. Defaults to
+    /// `IpAnonymization::None`.
+    pub fn anonymize_ip(mut self, anonymization: IpAnonymization) -> Self {
+        self.capture.anonymize_ip = anonymization;
+        self
+    }
+
+    /// Chooses how query strings are recorded, for example to redact
+    /// access tokens. Defaults to `QueryLogging::Keep`.
+    pub fn query_logging(mut self, logging: QueryLogging) -> Self {
+        self.capture.query = logging;
+        self
+    }
+
+    /// Hides the values of sensitive query parameters and headers.
+    /// Applied after `query_logging`.
+    pub fn redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.capture.redaction = policy;
+        self
+    }
+
+    /// Cuts URLs longer than `len` bytes short, ending them with
+    /// `...` and marking their packets as `truncated`, so enormous
+    /// query strings cannot bloat the log. The scheme and host are
+    /// always kept whole. Applied after `redaction`; by default URLs
+    /// are never truncated.
+    pub fn max_url_len(mut self, len: usize) -> Self {
+        self.capture.max_url_len = Some(len);
+        self
+    }
+
+    /// Records the values of the named request headers, such as
+    /// `Accept` or a tenant header. Other headers are never recorded.
+    /// In CSV, the headers are written to one column as a JSON object.
+    pub fn log_request_headers(mut self, names: &[&str]) -> Self {
+        self.capture.request_headers = names.iter().map(|&name| name.into()).collect();
+        self
+    }
+
+    /// Records the values of the named response headers, such as
+    /// `Cache-Control`, from both successful and failed responses.
+    /// They are written like those from `log_request_headers`.
+    pub fn log_response_headers(mut self, names: &[&str]) -> Self {
+        self.capture.response_headers = names.iter().map(|&name| name.into()).collect();
+        self
+    }
+
+    /// Attaches a static tag to every packet, such as the service or
+    /// region. In CSV, tags are written to one column as a JSON object.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.capture.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Tags every packet with the `hostname` of the machine, looked up
+    /// once, now. Nothing is tagged if it cannot be found.
+    pub fn tag_hostname(mut self) -> Self {
+        if let Some(hostname) = capture::hostname() {
+            self.capture.tags.insert("hostname".into(), hostname);
+        }
+        self
+    }
+
+    /// Starts the logging thread writing to `logger`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logging thread cannot be spawned; see `try_build`.
+    pub fn build<L>(self, logger: L) -> StatisticLogger
+        where L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        self.try_build(logger).expect("Unable to spawn logger thread")
+    }
+
+    /// Like `build`, but returns an error if the logging thread cannot
+    /// be spawned.
+    pub fn try_build<L>(self, logger: L) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        self.spawn(vec![logger])
+    }
+
+    /// Starts `workers` logging threads, each writing to its own
+    /// writer made by calling `factory` with the thread's index, so a
+    /// slow writer such as a network sink can keep up with more
+    /// traffic. The threads are named after `thread_name` followed by
+    /// their index, like `playground-logger-0`.
+    ///
+    /// Each packet is written by whichever thread takes it from the
+    /// queue first, so packets may be written out of order, and each
+    /// writer only sees its share of them. Error and slow request
+    /// handlers are shared by the threads and called one at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a logging thread cannot be spawned; see
+    /// `try_build_pool`.
+    pub fn build_pool<F, L>(self, workers: usize, factory: F) -> StatisticLogger
+        where F: FnMut(usize) -> L,
+              L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        self.try_build_pool(workers, factory).expect("Unable to spawn logger thread")
+    }
+
+    /// Like `build_pool`, but returns an error if a logging thread
+    /// cannot be spawned.
+    pub fn try_build_pool<F, L>(self, workers: usize, factory: F) -> Result<StatisticLogger, LoggingError>
+        where F: FnMut(usize) -> L,
+              L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        self.spawn((0..workers.max(1)).map(factory).collect())
+    }
+
+    /// Logs on the threads serving requests instead, with writers made
+    /// by calling `factory`; see `SyncStatisticLogger`. Only the
+    /// options deciding which requests are logged and what is recorded
+    /// about them apply: those about the queue, the logging thread,
+    /// errors and slow requests are ignored.
+    pub fn build_inline<F, L>(self, factory: F) -> SyncStatisticLogger
+        where F: Fn() -> L + Send + Sync + 'static,
+              L: LogWriter + 'static,
+    {
+        let recorder = Recorder {
+            sink: Arc::new(Inline::new(factory)),
+            capture: self.capture,
+            filter: self.filter,
+            label: self.label,
+            sampling: self.sampling,
+            clock: self.clock,
+            timing: self.timing,
+            seq: AtomicU64::new(0),
+        };
+        inline::logger(recorder, self.catch_panics, self.enabled)
+    }
+
+    fn spawn<L>(mut self, loggers: Vec<L>) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        match self.tail_sampling.take() {
+            Some(sampling) => {
+                let loggers = loggers.into_iter()
+                    .map(|logger| TailSampled::new(logger, sampling.clone()))
+                    .collect();
+                self.spawn_slow(loggers)
+            }
+            None => self.spawn_slow(loggers),
+        }
+    }
+
+    /// Slow requests are reported whether or not they are sampled.
+    fn spawn_slow<L>(mut self, loggers: Vec<L>) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        match self.slow.take() {
+            Some((threshold, on_slow)) => {
+                let on_slow = Arc::new(Mutex::new(on_slow));
+                let loggers = loggers.into_iter()
+                    .map(|logger| SlowRequests::new(logger, threshold, on_slow.clone()))
+                    .collect();
+                self.spawn_coalesced(loggers)
+            }
+            None => self.spawn_coalesced(loggers),
+        }
+    }
+
+    fn spawn_coalesced<L>(mut self, loggers: Vec<L>) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        match self.coalesce.take() {
+            Some(window) => {
+                let loggers = loggers.into_iter()
+                    .map(|logger| Coalesced::new(logger, window, self.clock.clone()))
+                    .collect();
+                self.spawn_writers(loggers)
+            }
+            None => self.spawn_writers(loggers),
+        }
+    }
+
+    fn spawn_writers<L>(self, loggers: Vec<L>) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static,
+              H: ErrorHandler<L::Error>,
+    {
+        let threads = Threads {
+            name: self.thread_name,
+            stack_size: self.stack_size,
+        };
+
+        let mut on_error = self.on_error;
+        let mut logger = try!(StatisticLogger::spawn(loggers, self.capacity, move |packet, e| on_error.handle(packet, e),
+                                                     self.batch, threads, self.clock.clone())
+            .map_err(LoggingError::Spawn));
+
+        logger.worker.policy = self.policy;
+        logger.worker.contention_threshold = self.contention_threshold;
+        logger.capture = self.capture;
+        logger.filter = self.filter;
+        logger.label = self.label;
+        logger.sampling = self.sampling;
+        logger.clock = self.clock;
+        logger.timing = self.timing;
+        logger.catch_panics = self.catch_panics;
+        logger.enabled = self.enabled;
+        Ok(logger)
+    }
+}
+
+impl Default for StatisticLoggerBuilder {
+    fn default() -> StatisticLoggerBuilder {
+        StatisticLoggerBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::MemoryLogger;
+
+    struct Failing;
+
+    impl LogWriter for Failing {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    fn ok_handler(_req: &mut Request) -> IronResult<Response> {
+        Ok(Response::with(status::Ok))
+    }
+
+    #[test]
+    fn requests_are_labeled_with_their_tenant() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new()
+            .label(|req| {
+                let host = req.url.as_ref().host_str()?;
+                let mut labels = host.split('.');
+                let tenant = labels.next()?;
+                if labels.count() < 2 {
+                    return None;
+                }
+                Some(tenant.into())
+            })
+            .build(memory.clone());
+
+        let handler = logger.around(Box::new(ok_handler));
+        request::get("http://acme.example.com/", Headers::new(), &handler).expect("Request failed");
+        request::get("http://example.com/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets[0].label(), Some("acme"));
+        assert_eq!(packets[1].label(), None);
+    }
+
+    #[test]
+    fn every_option_can_be_configured() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new()
+            .capacity(16)
+            .overflow_policy(OverflowPolicy::DropOldest)
+            .batch(10, Duration::from_millis(50))
+            .filter(|req| req.url.path() != ["health"])
+            .thread_name("access-log")
+            .trust_forwarded_for(1)
+            .anonymize_ip(IpAnonymization::MaskLastOctet)
+            .log_request_headers(&["Accept"])
+            .log_response_headers(&["X-Request-Id"])
+            .build(memory.clone());
+        assert_eq!(logger.capacity(), 16);
+
+        let handler = logger.around(Box::new(ok_handler));
+        let mut headers = Headers::new();
+        headers.set_raw("X-Forwarded-For", vec![b"203.0.113.9".to_vec()]);
+        headers.set_raw("Accept", vec![b"text/html".to_vec()]);
+        request::get("http://127.0.0.1/", headers, &handler).expect("Request failed");
+        request::get("http://127.0.0.1/health", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].ip().to_string(), "203.0.113.0:0");
+        assert_eq!(packets[0].request_headers()["Accept"], "text/html");
+        assert_eq!(packets[0].response_headers()["X-Request-Id"], packets[0].request_id());
+    }
+
+    struct ThreadName(Arc<Mutex<Option<String>>>);
+
+    impl LogWriter for ThreadName {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            *self.0.lock().unwrap() = thread::current().name().map(String::from);
+            Ok(())
+        }
+    }
+
+    fn logging_thread_name(builder: StatisticLoggerBuilder) -> Option<String> {
+        let name = Arc::new(Mutex::new(None));
+        let handler = builder.build(ThreadName(name.clone())).around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let name = name.lock().unwrap().clone();
+        name
+    }
+
+    #[test]
+    fn the_logging_thread_is_named() {
+        assert_eq!(logging_thread_name(StatisticLoggerBuilder::new()), Some("playground-logger".into()));
+
+        let builder = StatisticLoggerBuilder::new().thread_name("access-log").stack_size(256 * 1024);
+        assert_eq!(logging_thread_name(builder), Some("access-log".into()));
+    }
+
+    #[test]
+    fn excluded_paths_are_not_logged() {
+        let memory = MemoryLogger::new();
+        let paths = PathFilter::exclude(["^/health$"]).unwrap();
+        let logger = StatisticLoggerBuilder::new()
+            .path_filter(paths)
+            .filter(|req| req.url.path() != ["api", "private"])
+            .build(memory.clone());
+
+        let handler = logger.around(Box::new(ok_handler));
+        for url in &["http://127.0.0.1/health", "http://127.0.0.1/api/x", "http://127.0.0.1/api/private"] {
+            request::get(url, Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        let paths: Vec<_> = memory.packets().iter().map(|p| p.path().to_owned()).collect();
+        assert_eq!(paths, vec!["/api/x"]);
+    }
+
+    #[test]
+    fn disabled_loggers_log_nothing() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new().enabled(false).build(memory.clone());
+        let handler = logger.around(Box::new(ok_handler));
+        let res = request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        assert!(res.headers.get_raw("X-Request-Id").is_some());
+        drop(handler);
+        assert!(memory.packets().is_empty());
+
+        let inline = MemoryLogger::new();
+        let logged = inline.clone();
+        let logger = StatisticLoggerBuilder::new().enabled(false).build_inline(move || logged.clone());
+        let handler = logger.around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        assert!(inline.packets().is_empty());
+    }
+
+    #[test]
+    fn only_sampled_requests_are_logged() {
+        fn failing_handler(_req: &mut Request) -> IronResult<Response> {
+            Ok(Response::with(status::ServiceUnavailable))
+        }
+
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new()
+            .sampling(Sampling::new(0.0).errors(1.0))
+            .build(memory.clone());
+
+        let handler = logger.around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+        assert_eq!(memory.packets().len(), 0);
+
+        let logger = StatisticLoggerBuilder::new()
+            .sampling(Sampling::new(0.0).errors(1.0))
+            .build(memory.clone());
+        let handler = logger.around(Box::new(failing_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+        assert_eq!(memory.packets().len(), 1);
+    }
+
+    #[test]
+    fn errors_can_be_handled() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let logged = errors.clone();
+        let logger = StatisticLoggerBuilder::new()
+            // The writer's own error type reaches the handler
+            .on_error(move |_packet, e: &io::Error| logged.lock().unwrap().push((e.kind(), e.to_string())))
+            .build(Failing);
+
+        let handler = logger.around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        assert_eq!(*errors.lock().unwrap(), vec![(io::ErrorKind::Other, "disk full".to_string())]);
+    }
+
+    #[test]
+    fn slow_requests_are_reported() {
+        fn slow_handler(_req: &mut Request) -> IronResult<Response> {
+            thread::sleep(Duration::from_millis(50));
+            Ok(Response::with(status::Ok))
+        }
+
+        let slow = Arc::new(Mutex::new(Vec::new()));
+        let reported = slow.clone();
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new()
+            .on_slow_request(Duration::from_millis(20), move |packet| {
+                reported.lock().unwrap().push(packet.path().to_owned());
+            })
+            .build(memory.clone());
+
+        let mut chain = Chain::new(|req: &mut Request| {
+            if req.url.path() == ["slow"] {
+                slow_handler(req)
+            } else {
+                ok_handler(req)
+            }
+        });
+        chain.link_around(logger);
+        request::get("http://127.0.0.1/fast", Headers::new(), &chain).expect("Request failed");
+        request::get("http://127.0.0.1/slow", Headers::new(), &chain).expect("Request failed");
+        drop(chain);
+
+        assert_eq!(*slow.lock().unwrap(), vec!["/slow"]);
+        assert_eq!(memory.packets().len(), 2);
+    }
+
+    #[test]
+    fn tail_sampling_drops_ordinary_requests_on_the_logging_thread() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new()
+            .tail_sampling(TailSampling::new(0.0))
+            .build(memory.clone());
+
+        let handler = logger.around(Box::new(|req: &mut Request| -> IronResult<Response> {
+            if req.url.path() == ["missing"] {
+                Ok(Response::with(status::NotFound))
+            } else {
+                Ok(Response::with(status::Ok))
+            }
+        }));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/missing", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].path(), "/missing");
+    }
+
+    #[test]
+    fn identical_requests_are_coalesced() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLoggerBuilder::new()
+            .coalesce(Duration::from_secs(60))
+            .build(memory.clone());
+
+        let handler = logger.around(Box::new(|_req: &mut Request| Ok(Response::with(status::Ok))));
+        for _ in 0..5 {
+            request::get("http://127.0.0.1/health", Headers::new(), &handler).expect("Request failed");
+        }
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        let counts: Vec<_> = packets.iter().map(|p| (p.path(), p.count())).collect();
+        assert_eq!(counts, vec![("/health", 5), ("/", 1)]);
+    }
+
+    struct Tagged {
+        index: usize,
+        written: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl LogWriter for Tagged {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            thread::sleep(Duration::from_millis(5));
+            assert_eq!(thread::current().name(), Some(&format!("playground-logger-{}", self.index)[..]));
+            self.written.lock().unwrap().push(self.index);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_pool_of_threads_shares_the_queue() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let logger = StatisticLoggerBuilder::new().build_pool(3, |index| {
+            Tagged {
+                index: index,
+                written: written.clone(),
+            }
+        });
+
+        let handler = logger.around(Box::new(ok_handler));
+        for _ in 0..30 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        let mut indexes = written.lock().unwrap().clone();
+        assert_eq!(indexes.len(), 30);
+        indexes.sort();
+        indexes.dedup();
+        assert!(indexes.len() > 1, "{:?}", indexes);
+    }
+}
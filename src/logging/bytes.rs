@@ -0,0 +1,122 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::Handler;
+use serde_json::{self, Value};
+
+use super::{json_response, Aggregator, LogPacket, LogWriter};
+
+/// Counts requests and the response bytes served for them, as a cheap
+/// measure of egress. Responses whose size is not known up front do
+/// not add to the total. Clones share the same counters; use `handler`
+/// to expose them as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct ByteCounter(Arc<Totals>);
+
+#[derive(Debug, Default)]
+struct Totals {
+    requests: AtomicU64,
+    response_bytes: AtomicU64,
+}
+
+/// The totals of a `ByteCounter`. Both only increase until the
+/// counter is reset.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ByteCounts {
+    pub requests: u64,
+    pub response_bytes: u64,
+}
+
+impl ByteCounter {
+    pub fn new() -> ByteCounter {
+        ByteCounter::default()
+    }
+
+    /// The totals so far. A snapshot taken while requests are logged
+    /// may include a request without its bytes.
+    pub fn snapshot(&self) -> ByteCounts {
+        ByteCounts {
+            requests: self.0.requests.load(Ordering::Relaxed),
+            response_bytes: self.0.response_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Zeroes the totals, returning those before. As with `snapshot`,
+    /// a request logged meanwhile may have its bytes counted on the
+    /// other side of the reset, but nothing is lost or counted twice.
+    pub fn reset(&self) -> ByteCounts {
+        ByteCounts {
+            requests: self.0.requests.swap(0, Ordering::Relaxed),
+            response_bytes: self.0.response_bytes.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    /// A handler serving the totals as a JSON object like
+    /// `{"requests":3,"response_bytes":1024}`.
+    pub fn handler(&self) -> ByteCountHandler {
+        ByteCountHandler(self.clone())
+    }
+}
+
+impl Aggregator for ByteCounter {
+    fn observe(&self, packet: &LogPacket) {
+        self.0.requests.fetch_add(1, Ordering::Relaxed);
+        if let Some(len) = packet.response_len {
+            self.0.response_bytes.fetch_add(len, Ordering::Relaxed);
+        }
+    }
+
+    /// The totals served by `handler`.
+    fn snapshot(&self) -> Value {
+        serde_json::to_value(ByteCounter::snapshot(self)).expect("Unable to serialize byte counts")
+    }
+}
+
+impl LogWriter for ByteCounter {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+/// Serves the totals of a `ByteCounter` as JSON.
+pub struct ByteCountHandler(ByteCounter);
+
+impl Handler for ByteCountHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&self.0.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    #[test]
+    fn response_bytes_are_totalled() {
+        let mut counter = ByteCounter::new();
+        let mut packet = LogPacket::stub();
+        for &len in &[Some(100), Some(24), None, Some(0), Some(900)] {
+            packet.response_len = len;
+            counter.log(&packet).unwrap();
+        }
+
+        assert_eq!(counter.snapshot(), ByteCounts {
+            requests: 5,
+            response_bytes: 1024,
+        });
+
+        let res = request::get("http://127.0.0.1/bytes", Headers::new(), &counter.handler()).unwrap();
+        assert_eq!(response::extract_body_to_string(res), r#"{"requests":5,"response_bytes":1024}"#);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::str;
+
+use iron;
+use iron::headers::Headers;
+use iron::prelude::*;
+use url::Position;
+
+use super::{IpAnonymization, QueryLogging, RedactionPolicy};
+
+/// Settings controlling how request details are recorded.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+    pub forwarded_for: Option<usize>,
+    pub forwarded_proto: bool,
+    pub anonymize_ip: IpAnonymization,
+    pub request_headers: Vec<String>,
+    pub response_headers: Vec<String>,
+    pub query: QueryLogging,
+    pub redaction: RedactionPolicy,
+    pub tags: BTreeMap<String, String>,
+    pub max_url_len: Option<usize>,
+}
+
+/// Ends truncated URLs. Unlike `…`, dots are not percent-encoded, so
+/// the marker does not push the URL back over the limit.
+const ELLIPSIS: &str = "...";
+
+impl Capture {
+    /// The address of the client, taken from `X-Forwarded-For` when
+    /// that has been enabled and the header is usable.
+    pub fn client_ip(&self, req: &Request) -> SocketAddr {
+        let addr = self.forwarded_for
+            .and_then(|trusted_proxies| forwarded_for(req, trusted_proxies))
+            .unwrap_or(req.remote_addr);
+        self.anonymize_ip.apply(addr)
+    }
+
+    /// The scheme the client used, taken from `X-Forwarded-Proto` when
+    /// that has been enabled and the header is present.
+    pub fn scheme(&self, req: &Request) -> String {
+        let forwarded = if self.forwarded_proto { forwarded_proto(req) } else { None };
+        forwarded.unwrap_or_else(|| req.url.scheme().into())
+    }
+
+    /// Cuts `url` down to the maximum length, if it has one, returning
+    /// whether it had to be truncated.
+    pub fn truncate_url(&self, url: iron::Url) -> (iron::Url, bool) {
+        let max = match self.max_url_len {
+            Some(max) => max,
+            None => return (url, false),
+        };
+        let full = url.to_string();
+        if full.len() <= max {
+            return (url, false);
+        }
+
+        // The URL is ASCII once serialized, so any index is a boundary
+        let origin = url.as_ref()[..Position::BeforePath].len();
+        let mut cut = max.saturating_sub(ELLIPSIS.len()).max(origin + 1);
+        // Never split a percent-encoded byte
+        if let Some(percent) = full[..cut].rfind('%') {
+            if cut - percent < 3 {
+                cut = percent;
+            }
+        }
+        if cut >= full.len() {
+            return (url, false);
+        }
+
+        match iron::Url::parse(&format!("{}{}", &full[..cut], ELLIPSIS)) {
+            Ok(truncated) => (truncated, true),
+            Err(_) => (url, false),
+        }
+    }
+}
+
+/// The name of this machine, from `$HOSTNAME` or, as most shells do
+/// not export that, `/etc/hostname`.
+pub fn hostname() -> Option<String> {
+    let hostname = env::var("HOSTNAME").ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())?;
+    let hostname = hostname.trim();
+    if hostname.is_empty() {
+        return None;
+    }
+    Some(hostname.into())
+}
+
+/// Proxies that append to the header leave the scheme the client used
+/// first.
+fn forwarded_proto(req: &Request) -> Option<String> {
+    let line = req.headers.get_raw("X-Forwarded-Proto")?.first()?;
+    let proto = str::from_utf8(line).ok()?.split(',').next()?.trim();
+    if proto.is_empty() {
+        return None;
+    }
+    Some(proto.to_ascii_lowercase())
+}
+
+/// The values of the named headers that are present, keyed by the
+/// names as given. Headers sent on several lines are joined with
+/// commas.
+pub fn headers(headers: &Headers, names: &[String]) -> BTreeMap<String, String> {
+    names.iter()
+        .filter_map(|name| {
+            let lines = headers.get_raw(name)?;
+            let value = lines.iter()
+                .map(|line| String::from_utf8_lossy(line))
+                .collect::<Vec<_>>()
+                .join(", ");
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+/// Each proxy appends the address it received the request from, so
+/// the entry added by the outermost trusted proxy is the client.
+/// Anything to the left of that may have been forged by the client,
+/// as may the whole header when it has fewer entries than there are
+/// proxies, or when no proxy is trusted.
+fn forwarded_for(req: &Request, trusted_proxies: usize) -> Option<SocketAddr> {
+    if trusted_proxies == 0 {
+        return None;
+    }
+    let lines = req.headers.get_raw("X-Forwarded-For")?;
+
+    let mut entries = Vec::new();
+    for line in lines {
+        let line = str::from_utf8(line).ok()?;
+        entries.extend(line.split(',').map(str::trim));
+    }
+
+    let index = entries.len().checked_sub(trusted_proxies)?;
+    parse_addr(entries[index])
+}
+
+/// Forwarded addresses rarely include the client's port, in which
+/// case it is recorded as 0.
+fn parse_addr(entry: &str) -> Option<SocketAddr> {
+    entry.parse().ok()
+        .or_else(|| entry.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 0)))
+}
@@ -0,0 +1,140 @@
+use std::io::{self, Write};
+
+use super::{LogPacket, LogWriter, TimestampFormat};
+
+/// Records statistics in the Combined Log Format of Apache and nginx,
+/// so existing tools like GoAccess and AWStats can analyse them:
+///
+/// ```text
+/// 127.0.0.1 - - [14/Jul/2017:02:40:00 +0000] "GET /?page=2 HTTP/1.1" 200 13 "-" "iron-test"
+/// ```
+///
+/// Times are in UTC unless an offset is given. Missing values, like the referer of requests that
+/// did not send one, are written as `-`.
+pub struct CommonLogFormatLogger<W: Write> {
+    stream: W,
+    combined: bool,
+    timestamps: TimestampFormat,
+}
+
+impl<W: Write> CommonLogFormatLogger<W> {
+    /// Writes the Combined Log Format.
+    pub fn new(stream: W) -> CommonLogFormatLogger<W> {
+        CommonLogFormatLogger {
+            stream: stream,
+            combined: true,
+            timestamps: TimestampFormat::Apache(0),
+        }
+    }
+
+    /// Writes the Common Log Format, which leaves out the referer and
+    /// user agent.
+    pub fn common(stream: W) -> CommonLogFormatLogger<W> {
+        CommonLogFormatLogger {
+            stream: stream,
+            combined: false,
+            timestamps: TimestampFormat::Apache(0),
+        }
+    }
+
+    /// Writes times at `minutes` east of UTC, like `-300` for `-0500`.
+    pub fn utc_offset(self, minutes: i32) -> Self {
+        CommonLogFormatLogger { timestamps: TimestampFormat::Apache(minutes), ..self }
+    }
+}
+
+impl<W: Write> LogWriter for CommonLogFormatLogger<W> {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.stream.write_all(line(packet, self.combined, &self.timestamps).as_bytes())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.stream.flush()
+    }
+}
+
+/// A packet as a line of the Combined, or else the Common, Log Format,
+/// including the trailing newline.
+pub fn line(packet: &LogPacket, combined: bool, timestamps: &TimestampFormat) -> String {
+    let mut line = format!("{} - - {} \"{} {} {}\" {} {}",
+                           packet.peer_addr().map_or_else(|| "-".into(), |addr| addr.ip().to_string()),
+                           timestamps.format(packet.start),
+                           packet.method,
+                           escape(&request_target(packet)),
+                           packet.http_version,
+                           packet.status.map_or_else(|| "-".into(), |s| s.to_u16().to_string()),
+                           packet.response_len.map_or_else(|| "-".into(), |len| len.to_string()));
+    if combined {
+        line.push_str(&format!(" \"{}\" \"{}\"", quoted(&packet.referer), quoted(&packet.user_agent)));
+    }
+    line.push('\n');
+    line
+}
+
+/// The path and query, as in the request line.
+fn request_target(packet: &LogPacket) -> String {
+    match packet.query {
+        Some(ref query) => format!("{}?{}", packet.path(), query),
+        None => packet.path().into(),
+    }
+}
+
+fn quoted(field: &Option<String>) -> String {
+    field.as_ref().map_or_else(|| "-".into(), |field| escape(field))
+}
+
+/// Escapes quotes, backslashes and control characters the way Apache
+/// does, so every field stays on its line and within its quotes.
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron::method::Method;
+
+    fn logged(logger: fn(Vec<u8>) -> CommonLogFormatLogger<Vec<u8>>, packet: &LogPacket) -> String {
+        let mut logger = logger(Vec::new());
+        logger.log(packet).unwrap();
+        String::from_utf8(logger.stream).unwrap()
+    }
+
+    #[test]
+    fn packets_are_written_in_combined_log_format() {
+        let mut packet = LogPacket::stub();
+        packet.method = Method::Post;
+        packet.query = Some("page=2".into());
+        packet.user_agent = Some("curl \"7.0\"".into());
+
+        assert_eq!(logged(CommonLogFormatLogger::new, &packet),
+                   "127.0.0.1 - - [14/Jul/2017:02:40:00 +0000] \"POST /?page=2 HTTP/1.1\" 200 13 \"-\" \
+                    \"curl \\\"7.0\\\"\"\n");
+
+        packet.referer = Some("https://example.com/".into());
+        let line = logged(CommonLogFormatLogger::new, &packet);
+        assert!(line.ends_with(" 13 \"https://example.com/\" \"curl \\\"7.0\\\"\"\n"), "{}", line);
+
+        let mut logger = CommonLogFormatLogger::common(Vec::new()).utc_offset(-300);
+        logger.log(&packet).unwrap();
+        assert!(String::from_utf8(logger.stream).unwrap().contains(" [13/Jul/2017:21:40:00 -0500] "));
+
+        packet.status = None;
+        packet.response_len = None;
+        packet.http_version = "HTTP/1.0".into();
+        assert_eq!(logged(CommonLogFormatLogger::common, &packet),
+                   "127.0.0.1 - - [14/Jul/2017:02:40:00 +0000] \"POST /?page=2 HTTP/1.0\" - -\n");
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::{Instant, SystemTime};
+
+/// Where the logging middleware gets the time from. Replacing the
+/// clock lets tests check exact start times and durations.
+pub trait Clock: Send + Sync {
+    /// The wall-clock time, recorded as the start of each request.
+    fn now_system(&self) -> SystemTime;
+
+    /// A monotonic time, used to measure durations.
+    fn now_instant(&self) -> Instant;
+}
+
+/// The system's clocks, used unless another clock is given.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_system(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn now_instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Which of the clock's times a request's `timing` is measured with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TimingSource {
+    /// The monotonic time, which only moves forward.
+    #[default]
+    Monotonic,
+    /// The wall-clock time, consistent with the `start` of each
+    /// request. An NTP adjustment while the request is served
+    /// stretches or shrinks its timing, down to zero should the clock
+    /// step backwards.
+    WallClock,
+}
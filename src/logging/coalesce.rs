@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::{Clock, LogPacket, LogWriter, Summary};
+
+/// The requests a coalesced record stands for.
+type Key = (String, String, Option<u16>);
+
+/// Collapses packets with the same method, path and status that start
+/// within `window` of the first into one record, carrying how many
+/// requests it stands for in its `count`. The record is the first
+/// packet of the window. It is written once its window has passed,
+/// when a later packet arrives or the writer is flushed, or when
+/// logging stops.
+pub struct Coalesced<L: LogWriter> {
+    logger: L,
+    window: Duration,
+    clock: Arc<Clock>,
+    pending: Vec<(Key, LogPacket)>,
+}
+
+impl<L: LogWriter> Coalesced<L> {
+    pub fn new(logger: L, window: Duration, clock: Arc<Clock>) -> Coalesced<L> {
+        Coalesced {
+            logger: logger,
+            window: window,
+            clock: clock,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Writes the records whose window has passed by `now`, or all of
+    /// them without a time.
+    fn write_due(&mut self, now: Option<SystemTime>) -> Result<(), L::Error> {
+        let window = self.window;
+        let mut index = 0;
+        while index < self.pending.len() {
+            let due = now.is_none_or(|now| {
+                now.duration_since(self.pending[index].1.start).is_ok_and(|age| age >= window)
+            });
+            if due {
+                let (_, packet) = self.pending.remove(index);
+                try!(self.logger.log(&packet));
+            } else {
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<L: LogWriter> LogWriter for Coalesced<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        try!(self.write_due(Some(packet.start)));
+
+        let key = (packet.method.to_string(), packet.path().to_owned(), packet.status.map(|s| s.to_u16()));
+        match self.pending.iter_mut().find(|pending| pending.0 == key) {
+            Some(pending) => pending.1.count = pending.1.count.saturating_add(packet.count),
+            None => self.pending.push((key, packet.clone())),
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let now = self.clock.now_system();
+        try!(self.write_due(Some(now)));
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        try!(self.write_due(None));
+        self.logger.finalize(summary)
+    }
+
+    fn discards_everything(&self) -> bool {
+        self.logger.discards_everything()
+    }
+}
+
+impl<L: LogWriter> Drop for Coalesced<L> {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_due(None).and_then(|()| self.logger.flush()) {
+            error!("Unable to write coalesced requests: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron::status;
+
+    use super::super::{MemoryLogger, SystemClock};
+
+    #[test]
+    fn identical_requests_in_the_window_are_counted_once() {
+        let memory = MemoryLogger::new();
+        let mut logger = Coalesced::new(memory.clone(), Duration::from_secs(10), Arc::new(SystemClock));
+        let start = LogPacket::stub().start;
+
+        let mut packet = LogPacket::stub();
+        for offset in 0..5 {
+            packet.start = start + Duration::from_secs(offset);
+            logger.log(&packet).unwrap();
+        }
+        // A different status, then a packet after the window
+        packet.status = Some(status::ServiceUnavailable);
+        logger.log(&packet).unwrap();
+        assert!(memory.packets().is_empty());
+        packet.status = Some(status::Ok);
+        packet.start = start + Duration::from_secs(10);
+        logger.log(&packet).unwrap();
+
+        let packets = memory.packets();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].count(), 5);
+        assert_eq!(packets[0].start(), start);
+
+        drop(logger);
+        let counts: Vec<_> = memory.packets().iter().map(|p| (p.status().unwrap().to_u16(), p.count())).collect();
+        assert_eq!(counts, vec![(200, 5), (503, 1), (200, 1)]);
+    }
+}
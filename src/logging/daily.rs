@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use csv;
+use time;
+
+use super::{Encoding, LogPacket, LogWriter};
+
+/// Records statistics to one CSV file per UTC calendar day. A path of
+/// `logs/access.csv` produces files like `logs/access-2024-06-01.csv`.
+/// Each file starts with a header row.
+///
+/// The day is taken from when each request started rather than when
+/// it is written, so requests received just before midnight land in
+/// that day's file even if they are written afterwards.
+pub struct DailyFileLogger {
+    path: PathBuf,
+    current: Option<(String, File)>,
+    encoding: Encoding,
+}
+
+impl DailyFileLogger {
+    pub fn new<P>(path: P) -> DailyFileLogger
+        where P: AsRef<Path>
+    {
+        DailyFileLogger {
+            path: path.as_ref().to_path_buf(),
+            current: None,
+            encoding: Encoding::default(),
+        }
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        DailyFileLogger { encoding: encoding, ..self }
+    }
+
+    fn file_for(&mut self, date: String) -> csv::Result<&mut File> {
+        let is_current = match self.current {
+            Some((ref current, _)) => *current == date,
+            None => false,
+        };
+
+        if !is_current {
+            let path = dated_path(&self.path, &date);
+            let (mut file, is_empty) = try!(super::open_csv(&path));
+            if is_empty {
+                try!(file.write_all(&super::csv_header(super::DEFAULT_DELIMITER, &self.encoding)));
+            }
+            self.current = Some((date, file));
+        }
+
+        Ok(&mut self.current.as_mut().expect("Daily log file was not opened").1)
+    }
+}
+
+/// `path` with `date` added to its file name, before the extension.
+pub fn dated_path(path: &Path, date: &str) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, date, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, date),
+    };
+    path.with_file_name(name)
+}
+
+fn utc_date(packet: &LogPacket) -> String {
+    let since_epoch = super::since_epoch(packet.start);
+    let tm = time::at_utc(time::Timespec::new(since_epoch.as_secs() as i64, 0));
+    time::strftime("%Y-%m-%d", &tm).expect("Unable to format date")
+}
+
+impl LogWriter for DailyFileLogger {
+    type Error = csv::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let row = try!(super::encode_csv(packet, &self.encoding));
+        let file = try!(self.file_for(utc_date(packet)));
+        try!(file.write_all(&row));
+        try!(file.flush());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::io::Read;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use self::tempdir::TempDir;
+
+    fn lines(path: &Path) -> usize {
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents.lines().count()
+    }
+
+    #[test]
+    fn packets_are_split_by_the_day_they_started() {
+        let dir = TempDir::new("daily").unwrap();
+        let mut logger = DailyFileLogger::new(dir.path().join("access.csv"));
+
+        let before_midnight = UNIX_EPOCH + Duration::from_secs(1_500_076_799);
+        let after_midnight = before_midnight + Duration::from_secs(1);
+
+        let mut packet = LogPacket::stub();
+        for &start in &[before_midnight, after_midnight, before_midnight, after_midnight] {
+            packet.start = start;
+            logger.log(&packet).unwrap();
+        }
+
+        assert_eq!(lines(&dir.path().join("access-2017-07-14.csv")), 3);
+        assert_eq!(lines(&dir.path().join("access-2017-07-15.csv")), 3);
+    }
+}
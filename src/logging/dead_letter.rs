@@ -0,0 +1,111 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{Encoding, LogPacket, LogWriter, Summary};
+
+/// Saves every packet that a writer fails to log to a dead-letter CSV
+/// file, so it can be reprocessed later. The file has the usual
+/// columns followed by a `failure` column holding the writer's error.
+///
+/// The writer's error is still returned after the packet is saved,
+/// so it reaches the `StatisticLogger`'s error handler as before.
+pub struct DeadLetterLogger<L> {
+    logger: L,
+    file: File,
+}
+
+impl<L> DeadLetterLogger<L> {
+    pub fn new<P>(logger: L, path: P) -> io::Result<DeadLetterLogger<L>>
+        where P: AsRef<Path>
+    {
+        let mut file = try!(OpenOptions::new().append(true).create(true).open(path));
+        if try!(file.metadata()).len() == 0 {
+            let mut columns = Encoding::default().header();
+            columns.push("failure");
+            try!(file.write_all(&try!(encode_record(&columns))));
+        }
+
+        Ok(DeadLetterLogger {
+            logger: logger,
+            file: file,
+        })
+    }
+
+    fn save(&mut self, packet: &LogPacket, failure: &str) -> io::Result<()> {
+        let mut record = try!(super::encode_csv(packet, &Encoding::default()).map_err(io::Error::other));
+        while record.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+            record.pop();
+        }
+        record.push(b',');
+        record.extend(try!(encode_record(&[failure])));
+
+        self.file.write_all(&record)
+    }
+}
+
+fn encode_record(fields: &[&str]) -> io::Result<Vec<u8>> {
+    let mut record = super::csv_writer(Vec::new(), super::DEFAULT_DELIMITER);
+    try!(record.write_record(fields));
+    record.into_inner().map_err(|e| e.into_error())
+}
+
+impl<L: LogWriter> LogWriter for DeadLetterLogger<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let result = self.logger.log(packet);
+        if let Err(ref e) = result {
+            if let Err(save_error) = self.save(packet, &e.to_string()) {
+                error!("Unable to save request to the dead-letter file: {}", save_error);
+            }
+        }
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::fs;
+
+    use self::tempdir::TempDir;
+
+    struct DiskFull;
+
+    impl LogWriter for DiskFull {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Err(io::Error::other("No space left on device, try later"))
+        }
+    }
+
+    #[test]
+    fn failed_packets_are_saved_with_the_failure() {
+        let dir = TempDir::new("dead-letter").unwrap();
+        let path = dir.path().join("failed.csv");
+
+        let mut logger = DeadLetterLogger::new(DiskFull, &path).unwrap();
+        assert!(logger.log(&LogPacket::stub()).is_err());
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], format!("{},failure", Encoding::default().header().join(",")));
+        assert!(lines[1].starts_with("0,http://127.0.0.1/,GET,"), "{}", lines[1]);
+        assert!(lines[1].ends_with(",\"No space left on device, try later\""), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{self, Write};
+
+use super::{since_epoch, LogPacket, LogWriter};
+
+/// Records statistics as one line of CloudWatch's Embedded Metric
+/// Format per request, so CloudWatch Logs extracts a `latency`
+/// metric, in milliseconds, with the `method` and `status` code as
+/// dimensions, from logs written to stdout on Lambda or ECS:
+///
+/// ```text
+/// {"_aws":{"Timestamp":1500000000000,"CloudWatchMetrics":[{"Namespace":"playground-middleware",
+/// "Dimensions":[["method","status"]],"Metrics":[{"Name":"latency","Unit":"Milliseconds"}]}]},
+/// "method":"GET","status":"200","latency":1.5,"path":"/","request_id":"..."}
+/// ```
+///
+/// The status is `unknown` when the response had none.
+pub struct EmfLogger<W: Write> {
+    stream: W,
+    namespace: String,
+}
+
+impl<W: Write> EmfLogger<W> {
+    pub fn new(stream: W) -> EmfLogger<W> {
+        EmfLogger {
+            stream: stream,
+            namespace: "playground-middleware".into(),
+        }
+    }
+
+    /// The CloudWatch namespace the metric is created in.
+    pub fn namespace(self, namespace: &str) -> Self {
+        EmfLogger { namespace: namespace.into(), ..self }
+    }
+}
+
+impl<W: Write> LogWriter for EmfLogger<W> {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let start = since_epoch(packet.start);
+        let status = packet.status.map_or_else(|| "unknown".into(), |s| s.to_u16().to_string());
+        let line = json!({
+            "_aws": {
+                "Timestamp": start.as_secs() * 1000 + u64::from(start.subsec_millis()),
+                "CloudWatchMetrics": [{
+                    "Namespace": self.namespace,
+                    "Dimensions": [["method", "status"]],
+                    "Metrics": [{"Name": "latency", "Unit": "Milliseconds"}],
+                }],
+            },
+            "method": packet.method.to_string(),
+            "status": status,
+            "latency": packet.timing.as_secs_f64() * 1000.0,
+            "path": packet.path(),
+            "request_id": packet.request_id,
+        });
+        self.stream.write_all(format!("{}\n", line).as_bytes())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::{self, Value};
+
+    #[test]
+    fn packets_declare_their_metrics() {
+        let mut output = Vec::new();
+        {
+            let mut logger = EmfLogger::new(&mut output).namespace("checkout");
+            logger.log(&LogPacket::stub()).unwrap();
+            let mut packet = LogPacket::stub();
+            packet.status = None;
+            logger.log(&packet).unwrap();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+
+        let json = &lines[0];
+        assert_eq!(json["_aws"]["Timestamp"], 1_500_000_000_000u64);
+        assert_eq!(json["_aws"]["CloudWatchMetrics"], json!([{
+            "Namespace": "checkout",
+            "Dimensions": [["method", "status"]],
+            "Metrics": [{"Name": "latency", "Unit": "Milliseconds"}],
+        }]));
+        // Every dimension and metric must be a member of the object
+        assert_eq!(json["method"], "GET");
+        assert_eq!(json["status"], "200");
+        assert_eq!(json["latency"], 1.5);
+        assert_eq!(lines[1]["status"], "unknown");
+    }
+}
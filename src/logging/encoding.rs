@@ -0,0 +1,523 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use iron::status::Status;
+use serde::ser::{Serialize, Serializer, SerializeStruct};
+use serde_json;
+use time;
+
+use super::{format_duration, rfc3339, since_epoch, LogPacket};
+
+/// The unit used for the `timing` column.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TimingUnit {
+    /// Fractional seconds, like `0.001500000`.
+    #[default]
+    Seconds,
+    /// Fractional milliseconds, like `1.500000`.
+    Millis,
+    /// Fractional microseconds, like `1500.000`.
+    Micros,
+    /// Whole nanoseconds, like `1500000`.
+    Nanos,
+}
+
+impl TimingUnit {
+    fn format(&self, duration: Duration) -> String {
+        let nanos = duration.as_secs() as u128 * 1_000_000_000 + duration.subsec_nanos() as u128;
+        match *self {
+            TimingUnit::Seconds => format_duration(&duration),
+            TimingUnit::Millis => format!("{}.{:06}", nanos / 1_000_000, nanos % 1_000_000),
+            TimingUnit::Micros => format!("{}.{:03}", nanos / 1_000, nanos % 1_000),
+            TimingUnit::Nanos => nanos.to_string(),
+        }
+    }
+
+    fn parse(&self, value: &str) -> Option<Duration> {
+        let per_unit: u128 = match *self {
+            TimingUnit::Seconds => 1_000_000_000,
+            TimingUnit::Millis => 1_000_000,
+            TimingUnit::Micros => 1_000,
+            TimingUnit::Nanos => 1,
+        };
+        let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+        let mut nanos = whole.parse::<u128>().ok()?.checked_mul(per_unit)?;
+        if !fraction.is_empty() {
+            let scale = 10u128.checked_pow(fraction.len() as u32)?;
+            nanos += fraction.parse::<u128>().ok()?.checked_mul(per_unit)? / scale;
+        }
+        let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+        Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+    }
+}
+
+/// The format used for the `start` column.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// Fractional seconds since the Unix epoch, like
+    /// `1500000000.000000000`.
+    #[default]
+    Epoch,
+    /// An RFC 3339 UTC timestamp with microseconds, like
+    /// `2017-07-14T02:40:00.000000Z`.
+    Rfc3339,
+    /// The bracketed local time of Apache access logs, like
+    /// `[14/Jul/2017:04:40:00 +0200]`, at the given offset from UTC in
+    /// minutes, such as `120` for the example.
+    Apache(i32),
+}
+
+impl TimestampFormat {
+    pub fn format(&self, time: SystemTime) -> String {
+        match *self {
+            TimestampFormat::Epoch => {
+                format_duration(&since_epoch(time))
+            }
+            TimestampFormat::Rfc3339 => rfc3339(time),
+            TimestampFormat::Apache(offset) => {
+                let local = since_epoch(time).as_secs() as i64 + offset as i64 * 60;
+                let tm = time::at_utc(time::Timespec::new(local, 0));
+                let sign = if offset < 0 { '-' } else { '+' };
+                format!("[{} {}{:02}{:02}]",
+                        time::strftime("%d/%b/%Y:%H:%M:%S", &tm).expect("Unable to format timestamp"),
+                        sign, offset.abs() / 60, offset.abs() % 60)
+            }
+        }
+    }
+
+    /// Reads a time written in this format. Apache timestamps are read
+    /// at the offset they were written with.
+    fn parse(&self, value: &str) -> Option<SystemTime> {
+        match *self {
+            TimestampFormat::Epoch => TimingUnit::Seconds.parse(value).map(|since| UNIX_EPOCH + since),
+            TimestampFormat::Rfc3339 => {
+                let (datetime, micros) = value.strip_suffix('Z')?.split_once('.')?;
+                let micros: u32 = micros.parse().ok().filter(|&micros| micros < 1_000_000)?;
+                let secs = utc_seconds(datetime, "%Y-%m-%dT%H:%M:%S")?;
+                Some(UNIX_EPOCH + Duration::new(u64::try_from(secs).ok()?, micros * 1_000))
+            }
+            TimestampFormat::Apache(_) => {
+                let (local, offset) = value.strip_prefix('[')?.strip_suffix(']')?.split_once(' ')?;
+                let sign = match offset.get(..1)? {
+                    "+" => 1,
+                    "-" => -1,
+                    _ => return None,
+                };
+                let hours: i64 = offset.get(1..3)?.parse().ok()?;
+                let minutes: i64 = offset.get(3..)?.parse().ok()?;
+                let secs = utc_seconds(local, "%d/%b/%Y:%H:%M:%S")? - sign * (hours * 60 + minutes) * 60;
+                Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+            }
+        }
+    }
+}
+
+/// The seconds since the epoch of a UTC time in a `strftime` format.
+fn utc_seconds(value: &str, format: &str) -> Option<i64> {
+    time::strptime(value, format).ok().map(|tm| tm.to_timespec().sec)
+}
+
+/// How the `status` column is written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StatusFormat {
+    /// The numeric code, like `404`.
+    #[default]
+    Code,
+    /// The name of the status, like `NotFound`, as written by earlier
+    /// versions.
+    Name,
+}
+
+impl StatusFormat {
+    pub fn format(&self, status: Status) -> String {
+        match *self {
+            StatusFormat::Code => status.to_u16().to_string(),
+            StatusFormat::Name => format!("{:?}", status),
+        }
+    }
+
+    fn parse(&self, value: &str) -> Option<Status> {
+        match *self {
+            StatusFormat::Code => value.parse().ok().map(Status::from_u16),
+            StatusFormat::Name => (100..600).map(Status::from_u16).find(|status| format!("{:?}", status) == value),
+        }
+    }
+}
+
+/// A field of `LogPacket` that can be written as a CSV column.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Column {
+    Seq,
+    Url,
+    Method,
+    Ip,
+    Status,
+    Start,
+    Timing,
+    ResponseLen,
+    UserAgent,
+    RequestId,
+    Path,
+    Query,
+    Host,
+    ContentType,
+    Error,
+    RequestLen,
+    RequestHeaders,
+    ResponseHeaders,
+    Scheme,
+    Referer,
+    Overhead,
+    Total,
+    Tags,
+    Fields,
+    Count,
+    Route,
+    Ttfb,
+    LogLag,
+    TlsVersion,
+    TlsCipher,
+    Label,
+    SampleRate,
+    HttpVersion,
+    Truncated,
+}
+
+impl Column {
+    /// The columns written by default, in order, led by the sequence
+    /// number that gaps are detected with. This layout is fixed so
+    /// existing consumers keep working; newer columns are only written
+    /// when selected.
+    pub const DEFAULT: &'static [Column] = &[
+        Column::Seq, Column::Url, Column::Method, Column::Ip, Column::Status, Column::Start, Column::Timing,
+        Column::ResponseLen, Column::UserAgent, Column::RequestId, Column::Path, Column::Query,
+        Column::Host, Column::ContentType, Column::Error, Column::RequestLen, Column::RequestHeaders,
+        Column::ResponseHeaders, Column::Scheme,
+    ];
+
+    /// Every column, including those only written when selected.
+    pub const ALL: &'static [Column] = &[
+        Column::Seq, Column::Url, Column::Method, Column::Ip, Column::Status, Column::Start, Column::Timing,
+        Column::ResponseLen, Column::UserAgent, Column::RequestId, Column::Path, Column::Query,
+        Column::Host, Column::ContentType, Column::Error, Column::RequestLen, Column::RequestHeaders,
+        Column::ResponseHeaders, Column::Scheme, Column::Referer, Column::Tags, Column::Fields,
+        Column::Route, Column::Overhead, Column::Total, Column::Ttfb, Column::Count,
+        Column::LogLag, Column::TlsVersion, Column::TlsCipher,
+        Column::Label, Column::SampleRate, Column::HttpVersion,
+        Column::Truncated,
+    ];
+
+    /// The column with the given name in header rows, if any.
+    pub fn from_name(name: &str) -> Option<Column> {
+        Column::ALL.iter().find(|column| column.name() == name).cloned()
+    }
+
+    /// The name of the column in the header row.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Column::Seq => "seq",
+            Column::Url => "url",
+            Column::Method => "method",
+            Column::Ip => "ip",
+            Column::Status => "status",
+            Column::Start => "start",
+            Column::Timing => "timing",
+            Column::ResponseLen => "response_len",
+            Column::UserAgent => "user_agent",
+            Column::RequestId => "request_id",
+            Column::Path => "path",
+            Column::Query => "query",
+            Column::Truncated => "truncated",
+            Column::Host => "host",
+            Column::ContentType => "content_type",
+            Column::Error => "error",
+            Column::RequestLen => "request_len",
+            Column::RequestHeaders => "request_headers",
+            Column::ResponseHeaders => "response_headers",
+            Column::Scheme => "scheme",
+            Column::HttpVersion => "http_version",
+            Column::Referer => "referer",
+            Column::Overhead => "overhead",
+            Column::Total => "total",
+            Column::Tags => "tags",
+            Column::Fields => "fields",
+            Column::Count => "count",
+            Column::Route => "route",
+            Column::Ttfb => "ttfb",
+            Column::LogLag => "log_lag",
+            Column::TlsVersion => "tls_version",
+            Column::TlsCipher => "tls_cipher",
+            Column::Label => "label",
+            Column::SampleRate => "sample_rate",
+        }
+    }
+}
+
+/// How the CSV writers encode each packet. By default the columns in
+/// `Column::DEFAULT` are written, in that order.
+#[derive(Debug, Clone)]
+pub struct Encoding {
+    timing: TimingUnit,
+    timestamps: TimestampFormat,
+    columns: Cow<'static, [Column]>,
+    status: StatusFormat,
+    missing_status: String,
+    missing_ip: String,
+}
+
+impl Default for Encoding {
+    fn default() -> Encoding {
+        Encoding {
+            timing: TimingUnit::default(),
+            timestamps: TimestampFormat::default(),
+            columns: Cow::Borrowed(Column::DEFAULT),
+            status: StatusFormat::default(),
+            missing_status: String::new(),
+            missing_ip: "-".into(),
+        }
+    }
+}
+
+impl Encoding {
+    pub fn new() -> Encoding {
+        Encoding::default()
+    }
+
+    /// Writes only the given columns, in the given order. Writers
+    /// that start files with a header row name the same columns.
+    pub fn columns(self, columns: &[Column]) -> Self {
+        Encoding { columns: Cow::Owned(columns.to_vec()), ..self }
+    }
+
+    /// The names of the selected columns, as written in header rows.
+    pub fn header(&self) -> Vec<&'static str> {
+        self.columns.iter().map(Column::name).collect()
+    }
+
+    pub fn timing(self, unit: TimingUnit) -> Self {
+        Encoding { timing: unit, ..self }
+    }
+
+    pub fn timestamps(self, format: TimestampFormat) -> Self {
+        Encoding { timestamps: format, ..self }
+    }
+
+    pub fn status_format(self, format: StatusFormat) -> Self {
+        Encoding { status: format, ..self }
+    }
+
+    /// Writes `fallback`, such as `-` or `0`, in the `status` column of
+    /// requests whose handler did not set a status. The column is left
+    /// empty by default.
+    pub fn missing_status(self, fallback: &str) -> Self {
+        Encoding { missing_status: fallback.into(), ..self }
+    }
+
+    /// Writes `placeholder` in the `ip` column of requests that came
+    /// in without a peer address, instead of `-`.
+    pub fn missing_ip(self, placeholder: &str) -> Self {
+        Encoding { missing_ip: placeholder.into(), ..self }
+    }
+
+    /// The formatted status, or the fallback.
+    fn status<'a>(&'a self, status: Option<Status>) -> Cow<'a, str> {
+        match status {
+            Some(status) => Cow::Owned(self.status.format(status)),
+            None => Cow::Borrowed(&self.missing_status),
+        }
+    }
+
+    /// The client's address, or the placeholder.
+    fn ip<'a>(&'a self, ip: Option<SocketAddr>) -> Cow<'a, str> {
+        match ip {
+            Some(ip) => Cow::Owned(ip.to_string()),
+            None => Cow::Borrowed(&self.missing_ip),
+        }
+    }
+}
+
+/// The columns `encoding` writes, in order.
+pub fn columns(encoding: &Encoding) -> &[Column] {
+    &encoding.columns
+}
+
+/// Reads a `status` column as `encoding` wrote it.
+pub fn parse_status(encoding: &Encoding, value: &str) -> Option<Status> {
+    if value == encoding.missing_status {
+        return None;
+    }
+    encoding.status.parse(value)
+}
+
+/// Reads a `start` column as `encoding` wrote it.
+pub fn parse_start(encoding: &Encoding, value: &str) -> Option<SystemTime> {
+    encoding.timestamps.parse(value)
+}
+
+/// Reads a duration column, like `timing`, as `encoding` wrote it.
+pub fn parse_duration(encoding: &Encoding, value: &str) -> Option<Duration> {
+    encoding.timing.parse(value)
+}
+
+/// Serializes a packet as a CSV record using the given options.
+pub struct Encoded<'a>(pub &'a LogPacket, pub &'a Encoding);
+
+impl<'a> Serialize for Encoded<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Encoded(packet, encoding) = *self;
+
+        let mut s = try!(serializer.serialize_struct("LogPacket", encoding.columns.len()));
+        for column in encoding.columns.iter() {
+            let name = column.name();
+            try!(match *column {
+                Column::Seq => s.serialize_field(name, &packet.seq),
+                Column::Url => s.serialize_field(name, &packet.url.to_string()),
+                Column::Method => s.serialize_field(name, &packet.method.to_string()),
+                Column::Ip => s.serialize_field(name, &encoding.ip(packet.peer_addr())),
+                Column::Status => s.serialize_field(name, &encoding.status(packet.status)),
+                Column::Start => s.serialize_field(name, &encoding.timestamps.format(packet.start)),
+                Column::Timing => s.serialize_field(name, &encoding.timing.format(packet.timing)),
+                Column::ResponseLen => s.serialize_field(name, &packet.response_len),
+                Column::UserAgent => s.serialize_field(name, &packet.user_agent),
+                Column::RequestId => s.serialize_field(name, &packet.request_id),
+                Column::Path => s.serialize_field(name, packet.path()),
+                Column::Query => s.serialize_field(name, &packet.query),
+                Column::Truncated => s.serialize_field(name, &packet.truncated),
+                Column::Host => s.serialize_field(name, &packet.host),
+                Column::ContentType => s.serialize_field(name, &packet.content_type),
+                Column::Error => s.serialize_field(name, &packet.error),
+                Column::RequestLen => s.serialize_field(name, &packet.request_len),
+                Column::RequestHeaders => s.serialize_field(name, &headers_json(&packet.request_headers)),
+                Column::ResponseHeaders => s.serialize_field(name, &headers_json(&packet.response_headers)),
+                Column::Scheme => s.serialize_field(name, &packet.scheme),
+                Column::HttpVersion => s.serialize_field(name, &packet.http_version),
+                Column::TlsVersion => s.serialize_field(name, &packet.tls_version),
+                Column::TlsCipher => s.serialize_field(name, &packet.tls_cipher),
+                Column::Referer => s.serialize_field(name, &packet.referer),
+                Column::Overhead => s.serialize_field(name, &encoding.timing.format(packet.overhead)),
+                Column::Total => s.serialize_field(name, &encoding.timing.format(packet.total)),
+                Column::Ttfb => s.serialize_field(name, &packet.ttfb.map(|ttfb| encoding.timing.format(ttfb))),
+                Column::LogLag => s.serialize_field(name, &encoding.timing.format(packet.log_lag)),
+                Column::Tags => s.serialize_field(name, &headers_json(&packet.tags)),
+                Column::Fields => s.serialize_field(name, &headers_json(&packet.fields)),
+                Column::Count => s.serialize_field(name, &packet.count),
+                Column::SampleRate => s.serialize_field(name, &packet.sample_rate),
+                Column::Label => s.serialize_field(name, &packet.label),
+                Column::Route => s.serialize_field(name, packet.route().unwrap_or(packet.path())),
+            });
+        }
+        s.end()
+    }
+}
+
+/// Captured headers, tags or fields as a JSON object, or nothing when
+/// there are none.
+fn headers_json(headers: &BTreeMap<String, String>) -> Option<String> {
+    if headers.is_empty() {
+        return None;
+    }
+    Some(serde_json::to_string(headers).expect("Unable to encode headers"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::UNIX_EPOCH;
+
+    use time;
+
+    #[test]
+    fn timing_can_be_written_in_each_unit() {
+        let timing = Duration::new(2, 1_500_042);
+
+        assert_eq!(TimingUnit::Seconds.format(timing), "2.001500042");
+        assert_eq!(TimingUnit::Millis.format(timing), "2001.500042");
+        assert_eq!(TimingUnit::Micros.format(timing), "2001500.042");
+        assert_eq!(TimingUnit::Nanos.format(timing), "2001500042");
+    }
+
+    #[test]
+    fn rfc3339_timestamps_round_trip() {
+        let start = UNIX_EPOCH + Duration::new(1_234_567_890, 987_654_321);
+        let formatted = TimestampFormat::Rfc3339.format(start);
+        assert_eq!(formatted, "2009-02-13T23:31:30.987654Z");
+
+        let tm = time::strptime(&formatted[..19], "%Y-%m-%dT%H:%M:%S").unwrap();
+        let parsed = UNIX_EPOCH + Duration::from_secs(tm.to_timespec().sec as u64);
+        let difference = start.duration_since(parsed).unwrap();
+        assert!(difference < Duration::from_secs(1), "{:?}", difference);
+    }
+
+    #[test]
+    fn apache_timestamps_are_in_local_time() {
+        let start = UNIX_EPOCH + Duration::new(1_500_000_000, 999_999_999);
+
+        assert_eq!(TimestampFormat::Apache(0).format(start), "[14/Jul/2017:02:40:00 +0000]");
+        assert_eq!(TimestampFormat::Apache(120).format(start), "[14/Jul/2017:04:40:00 +0200]");
+        assert_eq!(TimestampFormat::Apache(-210).format(start), "[13/Jul/2017:23:10:00 -0330]");
+    }
+
+    #[test]
+    fn the_timing_column_uses_the_configured_unit() {
+        let encoding = Encoding::new().timing(TimingUnit::Millis);
+        let row = String::from_utf8(super::super::encode_csv(&LogPacket::stub(), &encoding).unwrap()).unwrap();
+
+        assert!(row.contains(",1500000000.000000000,1.500000,"), "{}", row);
+    }
+
+    #[test]
+    fn columns_can_be_selected_and_reordered() {
+        let encoding = Encoding::new().columns(&[Column::Start, Column::Method, Column::Path, Column::Status,
+                                                 Column::Timing]);
+        let row = String::from_utf8(super::super::encode_csv(&LogPacket::stub(), &encoding).unwrap()).unwrap();
+
+        assert_eq!(encoding.header(), vec!["start", "method", "path", "status", "timing"]);
+        assert_eq!(row, "1500000000.000000000,GET,/,200,0.001500000\n");
+    }
+
+    #[test]
+    fn the_default_columns_do_not_change() {
+        assert_eq!(Encoding::default().header(),
+                   vec!["seq", "url", "method", "ip", "status", "start", "timing", "response_len", "user_agent",
+                        "request_id", "path", "query", "host", "content_type", "error", "request_len",
+                        "request_headers", "response_headers", "scheme"]);
+    }
+
+    #[test]
+    fn overhead_is_only_written_when_selected() {
+        assert!(!Encoding::default().header().contains(&"overhead"));
+        assert!(!Encoding::default().header().contains(&"total"));
+
+        let encoding = Encoding::new().columns(&[Column::Timing, Column::Overhead, Column::Total])
+            .timing(TimingUnit::Micros);
+        let row = String::from_utf8(super::super::encode_csv(&LogPacket::stub(), &encoding).unwrap()).unwrap();
+        assert_eq!(row, "1500.000,2.000,1502.000\n");
+    }
+
+    #[test]
+    fn statuses_are_written_as_codes() {
+        let encoding = Encoding::new().columns(&[Column::Status]);
+        let mut packet = LogPacket::stub();
+        packet.status = Some(Status::NotFound);
+        let row = super::super::encode_csv(&packet, &encoding).unwrap();
+        assert_eq!(row, b"404\n");
+
+        packet.status = None;
+        assert_eq!(super::super::encode_csv(&packet, &encoding).unwrap(), b"\"\"\n");
+        let encoding = encoding.missing_status("-");
+        assert_eq!(super::super::encode_csv(&packet, &encoding).unwrap(), b"-\n");
+    }
+
+    #[test]
+    fn statuses_can_be_written_by_name() {
+        assert_eq!(StatusFormat::default().format(Status::NotFound), "404");
+        assert_eq!(StatusFormat::Name.format(Status::NotFound), "NotFound");
+
+        let encoding = Encoding::new().columns(&[Column::Status]).status_format(StatusFormat::Name);
+        assert_eq!(super::super::encode_csv(&LogPacket::stub(), &encoding).unwrap(), b"Ok\n");
+    }
+}
@@ -0,0 +1,103 @@
+use std::{error, fmt};
+
+use super::{LogPacket, LogWriter};
+
+/// Logs to a primary writer and, whenever that fails, to a secondary
+/// writer instead, such as a local file backing up a network sink.
+///
+/// Logging only fails when both writers do. Flushing flushes both.
+pub struct FallbackLogger<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P, S> FallbackLogger<P, S> {
+    pub fn new(primary: P, secondary: S) -> FallbackLogger<P, S> {
+        FallbackLogger {
+            primary: primary,
+            secondary: secondary,
+        }
+    }
+}
+
+impl<P: LogWriter, S: LogWriter> LogWriter for FallbackLogger<P, S> {
+    type Error = FallbackError<P::Error, S::Error>;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let primary = match self.primary.log(packet) {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        self.secondary.log(packet).map_err(|secondary| FallbackError::Both(primary, secondary))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        match (self.primary.flush(), self.secondary.flush()) {
+            (Ok(()), Ok(())) => Ok(()),
+            (Err(p), Ok(())) => Err(FallbackError::Primary(p)),
+            (Ok(()), Err(s)) => Err(FallbackError::Secondary(s)),
+            (Err(p), Err(s)) => Err(FallbackError::Both(p, s)),
+        }
+    }
+}
+
+/// Which of the writers of a `FallbackLogger` failed.
+#[derive(Debug)]
+pub enum FallbackError<P, S> {
+    Primary(P),
+    Secondary(S),
+    Both(P, S),
+}
+
+impl<P: fmt::Display, S: fmt::Display> fmt::Display for FallbackError<P, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FallbackError::Primary(ref p) => write!(f, "Primary writer failed: {}", p),
+            FallbackError::Secondary(ref s) => write!(f, "Secondary writer failed: {}", s),
+            FallbackError::Both(ref p, ref s) => write!(f, "Both writers failed: {}; {}", p, s),
+        }
+    }
+}
+
+impl<P: error::Error, S: error::Error> error::Error for FallbackError<P, S> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io;
+
+    use super::super::MemoryLogger;
+
+    struct Unreachable;
+
+    impl LogWriter for Unreachable {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Err(io::Error::new(io::ErrorKind::ConnectionRefused, "Collector unreachable"))
+        }
+    }
+
+    #[test]
+    fn failed_packets_go_to_the_secondary_writer() {
+        let primary = MemoryLogger::new();
+        let secondary = MemoryLogger::new();
+        let mut logger = FallbackLogger::new(primary.clone(), secondary.clone());
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!((primary.packets().len(), secondary.packets().len()), (1, 0));
+
+        let secondary = MemoryLogger::new();
+        let mut logger = FallbackLogger::new(Unreachable, secondary.clone());
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(secondary.packets().len(), 1);
+    }
+
+    #[test]
+    fn errors_are_reported_when_both_writers_fail() {
+        let mut logger = FallbackLogger::new(Unreachable, Unreachable);
+
+        let error = logger.log(&LogPacket::stub()).unwrap_err();
+        assert_eq!(error.to_string(), "Both writers failed: Collector unreachable; Collector unreachable");
+    }
+}
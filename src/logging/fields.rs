@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use iron::prelude::*;
+use iron::typemap::Key;
+
+/// Extra fields a handler adds to the log record of its request, for
+/// context only the application knows, like the authenticated user or
+/// the tenant. They are written to one column as a JSON object.
+///
+/// ```
+/// extern crate iron;
+/// extern crate playground_middleware;
+///
+/// use iron::prelude::*;
+/// use iron::status;
+/// use playground_middleware::LogFields;
+///
+/// fn handler(req: &mut Request) -> IronResult<Response> {
+///     LogFields::insert(req, "user_id", "42");
+///     Ok(Response::with(status::Ok))
+/// }
+/// # fn main() {}
+/// ```
+pub struct LogFields;
+
+impl Key for LogFields {
+    type Value = BTreeMap<String, String>;
+}
+
+impl LogFields {
+    /// Adds a field, replacing any earlier one with the same name.
+    pub fn insert(req: &mut Request, name: &str, value: &str) {
+        req.extensions.entry::<LogFields>()
+            .or_insert_with(BTreeMap::new)
+            .insert(name.into(), value.into());
+    }
+}
+
+/// The fields added to the request, if any.
+pub fn get(req: &Request) -> BTreeMap<String, String> {
+    req.extensions.get::<LogFields>().cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::{Column, Encoding, MemoryLogger, StatisticLogger};
+
+    #[test]
+    fn fields_added_by_the_handler_are_logged() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(|req: &mut Request| {
+            if req.url.path() == ["account"] {
+                LogFields::insert(req, "user_id", "42");
+                LogFields::insert(req, "tenant", "acme");
+            }
+            Ok(Response::with(status::Ok))
+        }));
+
+        request::get("http://127.0.0.1/account", Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets[0].fields()["user_id"], "42");
+        assert_eq!(packets[0].fields()["tenant"], "acme");
+        assert!(packets[1].fields().is_empty());
+
+        let encoding = Encoding::default().columns(&[Column::Fields]);
+        let row = String::from_utf8(super::super::encode_csv(&packets[0], &encoding).unwrap()).unwrap();
+        assert_eq!(row, "\"{\"\"tenant\"\":\"\"acme\"\",\"\"user_id\"\":\"\"42\"\"}\"\n");
+    }
+}
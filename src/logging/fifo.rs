@@ -0,0 +1,251 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use libc;
+
+use super::{CsvFormatter, DroppedPackets, Encoding, Formatter, LogPacket, LogWriter};
+
+/// Records statistics to a named pipe, such as one read by a sidecar
+/// process. Packets are written as CSV rows, without a header row,
+/// unless another `Formatter` is given.
+///
+/// Writing never blocks: packets are dropped, and counted, while no
+/// reader has the pipe open or while the reader is too far behind to
+/// take more. When the reader goes away the pipe is reopened for the
+/// next packet, so its records continue once a reader comes back.
+/// Like any Rust program by default, the process must ignore
+/// `SIGPIPE`.
+pub struct FifoLogger<F = CsvFormatter> {
+    path: PathBuf,
+    fifo: Option<File>,
+    formatter: F,
+    /// The rest of a record the reader had no room for, written before
+    /// the next one so records are never interleaved.
+    pending: Vec<u8>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl FifoLogger {
+    /// Writes to the pipe at `path`, which must exist. Having no
+    /// reader yet is not an error.
+    pub fn new<P>(path: P) -> io::Result<FifoLogger>
+        where P: AsRef<Path>
+    {
+        FifoLogger::with_formatter(path, CsvFormatter::new())
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        FifoLogger { formatter: self.formatter.encoding(encoding), ..self }
+    }
+}
+
+impl<F: Formatter> FifoLogger<F> {
+    /// Renders packets with `formatter`, such as a `JsonFormatter`.
+    pub fn with_formatter<P>(path: P, formatter: F) -> io::Result<FifoLogger<F>>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref().to_owned();
+        Ok(FifoLogger {
+            fifo: try!(open(&path)),
+            path: path,
+            formatter: formatter,
+            pending: Vec::new(),
+            dropped: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Counts the packets discarded for want of a reader. Take it
+    /// before the logger is moved to the logging thread.
+    pub fn dropped(&self) -> DroppedPackets {
+        DroppedPackets(self.dropped.clone())
+    }
+
+    /// Writes `record` after any pending one, returning whether it was
+    /// started.
+    fn send(&mut self, record: &[u8]) -> io::Result<bool> {
+        let fifo = match self.fifo {
+            Some(ref mut fifo) => fifo,
+            None => return Ok(false),
+        };
+
+        let written = try!(write_available(fifo, &self.pending));
+        self.pending.drain(..written);
+        if !self.pending.is_empty() {
+            return Ok(false);
+        }
+
+        let written = try!(write_available(fifo, record));
+        if written == 0 {
+            return Ok(false);
+        }
+        self.pending.extend_from_slice(&record[written..]);
+        Ok(true)
+    }
+
+    /// Closes the pipe once its reader is gone, so the next packet
+    /// reopens it.
+    fn disconnect_on_broken_pipe<T>(&mut self, result: io::Result<T>) -> io::Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.fifo = None;
+                // A new reader starts with a new record
+                self.pending.clear();
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<F: Formatter> LogWriter for FifoLogger<F> {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let mut record = Vec::new();
+        try!(self.formatter.format(packet, &mut record));
+
+        if self.fifo.is_none() {
+            self.fifo = try!(open(&self.path));
+        }
+        let result = self.send(&record);
+        if try!(self.disconnect_on_broken_pipe(result)) != Some(true) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let result = self.send(&[]);
+        try!(self.disconnect_on_broken_pipe(result));
+        Ok(())
+    }
+}
+
+/// Opens the pipe for writing, or returns `None` when it has no reader.
+fn open(path: &Path) -> io::Result<Option<File>> {
+    let result = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path);
+
+    match result {
+        Ok(fifo) => Ok(Some(fifo)),
+        Err(ref e) if e.raw_os_error() == Some(libc::ENXIO) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes as much of `buf` as the pipe has room for, returning how
+/// much that was.
+fn write_available(fifo: &mut File, buf: &[u8]) -> io::Result<usize> {
+    let mut written = 0;
+    while written < buf.len() {
+        match fifo.write(&buf[written..]) {
+            Ok(0) => break,
+            Ok(n) => written += n,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::ffi::CString;
+    use std::io::Read;
+    use std::os::unix::ffi::OsStrExt;
+
+    use self::tempdir::TempDir;
+
+    use super::super::encode_csv;
+
+    fn mkfifo(path: &Path) {
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let result = unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) };
+        assert_eq!(result, 0, "{}", io::Error::last_os_error());
+    }
+
+    fn attach(path: &Path) -> File {
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .unwrap()
+    }
+
+    /// Reads what has been written so far.
+    fn available(reader: &mut File) -> String {
+        let mut output = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => panic!("Unable to read the pipe: {}", e),
+            }
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn packets_follow_the_reader_as_it_comes_and_goes() {
+        let dir = TempDir::new("fifo").unwrap();
+        let path = dir.path().join("requests");
+        mkfifo(&path);
+        let row = String::from_utf8(encode_csv(&LogPacket::stub(), &Encoding::default()).unwrap()).unwrap();
+
+        let mut logger = FifoLogger::new(&path).unwrap();
+        let dropped = logger.dropped();
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(dropped.count(), 1);
+
+        let mut reader = attach(&path);
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.flush().unwrap();
+        assert_eq!(available(&mut reader), row.repeat(2));
+        assert_eq!(dropped.count(), 1);
+
+        drop(reader);
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(dropped.count(), 2);
+
+        let mut reader = attach(&path);
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(available(&mut reader), row);
+        assert_eq!(dropped.count(), 2);
+    }
+
+    #[test]
+    fn a_full_pipe_drops_whole_records() {
+        let dir = TempDir::new("fifo").unwrap();
+        let path = dir.path().join("requests");
+        mkfifo(&path);
+        let mut reader = attach(&path);
+
+        let mut logger = FifoLogger::new(&path).unwrap();
+        let dropped = logger.dropped();
+        for _ in 0..2000 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert!(dropped.count() > 0);
+
+        let mut output = available(&mut reader);
+        logger.flush().unwrap();
+        output.push_str(&available(&mut reader));
+        let row = String::from_utf8(encode_csv(&LogPacket::stub(), &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(output, row.repeat(2000 - dropped.count()));
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Instant;
+
+use iron::response::WriteBody;
+
+use super::{Clock, LogPacket, Sink};
+
+/// Wraps a streamed response body to time its first byte. The packet
+/// is held back until the body has been written, so its `total`
+/// covers the streaming too, and is sent once the body is dropped,
+/// written or not.
+pub struct TimedBody<S: Sink> {
+    body: Box<WriteBody>,
+    packet: Option<LogPacket>,
+    sink: Arc<S>,
+    clock: Arc<Clock>,
+    entered: Instant,
+}
+
+pub fn wrap<S: Sink>(body: Box<WriteBody>, packet: LogPacket, sink: Arc<S>, clock: Arc<Clock>, entered: Instant)
+                     -> TimedBody<S>
+{
+    TimedBody {
+        body: body,
+        packet: Some(packet),
+        sink: sink,
+        clock: clock,
+        entered: entered,
+    }
+}
+
+impl<S: Sink> WriteBody for TimedBody<S> {
+    fn write_body(&mut self, res: &mut Write) -> io::Result<()> {
+        let (result, first_byte) = {
+            let mut out = FirstByte {
+                out: res,
+                clock: &*self.clock,
+                at: None,
+            };
+            (self.body.write_body(&mut out), out.at)
+        };
+
+        let entered = self.entered;
+        let finished = self.clock.now_instant();
+        if let Some(ref mut packet) = self.packet {
+            packet.ttfb = first_byte.map(|at| at.duration_since(entered));
+            packet.total = finished.duration_since(entered);
+        }
+        result
+    }
+}
+
+impl<S: Sink> Drop for TimedBody<S> {
+    fn drop(&mut self) {
+        if let Some(packet) = self.packet.take() {
+            self.sink.send_with(move || packet);
+        }
+    }
+}
+
+/// Notes when the first byte was written through it.
+struct FirstByte<'a> {
+    out: &'a mut Write,
+    clock: &'a Clock,
+    at: Option<Instant>,
+}
+
+impl<'a> Write for FirstByte<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = try!(self.out.write(buf));
+        if written > 0 && self.at.is_none() {
+            self.at = Some(self.clock.now_instant());
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use std::thread;
+    use std::time::Duration;
+
+    use iron::prelude::*;
+    use iron::{status, AroundMiddleware};
+    use iron::response::BodyReader;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{MemoryLogger, StatisticLogger};
+
+    /// Waits before each read, like a slow query feeding a streamed
+    /// response.
+    struct Slow(Option<&'static [u8]>);
+
+    impl ::std::io::Read for Slow {
+        fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+            thread::sleep(Duration::from_millis(20));
+            let bytes = self.0.take().unwrap_or(b"");
+            buf[..bytes.len()].copy_from_slice(bytes);
+            Ok(bytes.len())
+        }
+    }
+
+    #[test]
+    fn the_first_byte_of_a_streamed_body_is_timed() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(|req: &mut Request| {
+            match req.url.path()[0] {
+                "stream" => Ok(Response::with((status::Ok, BodyReader(Slow(Some(b"Hello")))))),
+                _ => Ok(Response::with((status::Ok, "Hello"))),
+            }
+        }));
+
+        let res = request::get("http://127.0.0.1/stream", Headers::new(), &handler).expect("Request failed");
+        assert_eq!(response::extract_body_to_string(res), "Hello");
+        request::get("http://127.0.0.1/buffered", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        let streamed = &packets[0];
+        let ttfb = streamed.ttfb().expect("No first byte was timed");
+        assert!(ttfb >= Duration::from_millis(20), "{:?}", ttfb);
+        assert!(ttfb < streamed.total(), "{:?} / {:?}", ttfb, streamed.total());
+        assert!(streamed.total() >= Duration::from_millis(40), "{:?}", streamed.total());
+
+        let buffered = &packets[1];
+        assert_eq!(buffered.ttfb(), Some(buffered.total()));
+    }
+}
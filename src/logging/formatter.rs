@@ -0,0 +1,229 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{clf, json, CsvQuoting, Encoded, Encoding, LogPacket, StatusFormat, TimestampFormat};
+
+/// Renders packets for writers that are only concerned with where
+/// lines go, like `StreamLogger` and `FileLogger`.
+pub trait Formatter {
+    /// Writes one packet, including its line terminator.
+    fn format(&self, packet: &LogPacket, out: &mut Write) -> io::Result<()>;
+
+    /// Writes what starts a new, empty file, like a CSV header row.
+    /// Nothing by default.
+    fn header(&self, _out: &mut Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders packets as CSV rows with the chosen columns, preceded by a
+/// header row in new files.
+#[derive(Debug, Clone)]
+pub struct CsvFormatter {
+    encoding: Encoding,
+    delimiter: u8,
+    quoting: CsvQuoting,
+}
+
+impl CsvFormatter {
+    pub fn new() -> CsvFormatter {
+        CsvFormatter {
+            encoding: Encoding::default(),
+            delimiter: super::DEFAULT_DELIMITER,
+            quoting: CsvQuoting::default(),
+        }
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        CsvFormatter { encoding: encoding, ..self }
+    }
+
+    /// Separates fields with `delimiter` instead of a comma.
+    pub fn delimiter(self, delimiter: u8) -> Self {
+        CsvFormatter { delimiter: delimiter, ..self }
+    }
+
+    pub fn quoting(self, quoting: CsvQuoting) -> Self {
+        CsvFormatter { quoting: quoting, ..self }
+    }
+}
+
+impl Default for CsvFormatter {
+    fn default() -> CsvFormatter {
+        CsvFormatter::new()
+    }
+}
+
+impl Formatter for CsvFormatter {
+    fn format(&self, packet: &LogPacket, out: &mut Write) -> io::Result<()> {
+        let mut row = super::quoted_csv_writer(Vec::new(), self.delimiter, self.quoting);
+        try!(row.serialize(Encoded(packet, &self.encoding)).map_err(io::Error::other));
+        out.write_all(&try!(row.into_inner().map_err(|e| e.into_error())))
+    }
+
+    fn header(&self, out: &mut Write) -> io::Result<()> {
+        let mut header = super::quoted_csv_writer(Vec::new(), self.delimiter, self.quoting);
+        try!(header.write_record(self.encoding.header()).map_err(io::Error::other));
+        out.write_all(&try!(header.into_inner().map_err(|e| e.into_error())))
+    }
+}
+
+/// Renders packets as one JSON object per line, like `JsonLogger`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct JsonFormatter {
+    status: StatusFormat,
+}
+
+impl JsonFormatter {
+    pub fn new() -> JsonFormatter {
+        JsonFormatter::default()
+    }
+
+    /// See `JsonLogger::status_format`.
+    pub fn status_format(self, format: StatusFormat) -> Self {
+        JsonFormatter { status: format }
+    }
+}
+
+impl Formatter for JsonFormatter {
+    fn format(&self, packet: &LogPacket, out: &mut Write) -> io::Result<()> {
+        out.write_all(&try!(json::encode_with(packet, self.status)))
+    }
+}
+
+/// Renders packets as Combined Log Format lines, like
+/// `CommonLogFormatLogger`.
+#[derive(Debug, Copy, Clone)]
+pub struct CombinedLogFormatter {
+    combined: bool,
+    timestamps: TimestampFormat,
+}
+
+impl CombinedLogFormatter {
+    /// Renders the Combined Log Format.
+    pub fn new() -> CombinedLogFormatter {
+        CombinedLogFormatter {
+            combined: true,
+            timestamps: TimestampFormat::Apache(0),
+        }
+    }
+
+    /// Renders the Common Log Format, without the referer and user
+    /// agent.
+    pub fn common() -> CombinedLogFormatter {
+        CombinedLogFormatter { combined: false, ..CombinedLogFormatter::new() }
+    }
+
+    /// See `CommonLogFormatLogger::utc_offset`.
+    pub fn utc_offset(self, minutes: i32) -> Self {
+        CombinedLogFormatter { timestamps: TimestampFormat::Apache(minutes), ..self }
+    }
+}
+
+impl Default for CombinedLogFormatter {
+    fn default() -> CombinedLogFormatter {
+        CombinedLogFormatter::new()
+    }
+}
+
+impl Formatter for CombinedLogFormatter {
+    fn format(&self, packet: &LogPacket, out: &mut Write) -> io::Result<()> {
+        out.write_all(clf::line(packet, self.combined, &self.timestamps).as_bytes())
+    }
+}
+
+/// One of the formatters above, chosen by `FileLogger::new` from the
+/// extension of the file: `.jsonl`, `.ndjson` and `.json` files get
+/// JSON lines, `.log` files the Combined Log Format, and `.csv` files
+/// and any others CSV.
+#[derive(Debug, Clone)]
+pub enum FileFormat {
+    Csv(CsvFormatter),
+    Json(JsonFormatter),
+    CombinedLog(CombinedLogFormatter),
+}
+
+impl FileFormat {
+    pub fn from_path(path: &Path) -> FileFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl") | Some("ndjson") | Some("json") => FileFormat::Json(JsonFormatter::new()),
+            Some("log") => FileFormat::CombinedLog(CombinedLogFormatter::new()),
+            _ => FileFormat::Csv(CsvFormatter::new()),
+        }
+    }
+}
+
+impl Default for FileFormat {
+    fn default() -> FileFormat {
+        FileFormat::Csv(CsvFormatter::new())
+    }
+}
+
+impl Formatter for FileFormat {
+    fn format(&self, packet: &LogPacket, out: &mut Write) -> io::Result<()> {
+        match *self {
+            FileFormat::Csv(ref csv) => csv.format(packet, out),
+            FileFormat::Json(ref json) => json.format(packet, out),
+            FileFormat::CombinedLog(ref clf) => clf.format(packet, out),
+        }
+    }
+
+    fn header(&self, out: &mut Write) -> io::Result<()> {
+        match *self {
+            FileFormat::Csv(ref csv) => csv.header(out),
+            FileFormat::Json(ref json) => json.header(out),
+            FileFormat::CombinedLog(ref clf) => clf.header(out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::Column;
+
+    fn formatted<F: Formatter>(formatter: &F) -> String {
+        let mut out = Vec::new();
+        formatter.header(&mut out).unwrap();
+        formatter.format(&LogPacket::stub(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_rows_follow_a_header() {
+        let formatter = CsvFormatter::new()
+            .encoding(Encoding::new().columns(&[Column::Method, Column::Path, Column::UserAgent]))
+            .delimiter(b';')
+            .quoting(CsvQuoting::new().always());
+        assert_eq!(formatted(&formatter), "\"method\";\"path\";\"user_agent\"\n\"GET\";\"/\";\"iron-test\"\n");
+    }
+
+    #[test]
+    fn json_lines_have_no_header() {
+        let line = formatted(&JsonFormatter::new().status_format(StatusFormat::Name));
+        assert!(line.starts_with(r#"{"url":"http://127.0.0.1/","method":"GET","ip":"127.0.0.1:3000","status":"Ok","#),
+                "{}", line);
+        assert!(line.ends_with("}\n"));
+        assert_eq!(line.lines().count(), 1);
+    }
+
+    #[test]
+    fn file_formats_follow_the_extension() {
+        let format = |path: &str| FileFormat::from_path(Path::new(path));
+        assert!(matches!(format("logs/access.csv"), FileFormat::Csv(_)));
+        assert!(matches!(format("access.jsonl"), FileFormat::Json(_)));
+        assert!(matches!(format("access.json"), FileFormat::Json(_)));
+        assert!(matches!(format("access.log"), FileFormat::CombinedLog(_)));
+        assert!(matches!(format("access"), FileFormat::Csv(_)));
+        assert_eq!(formatted(&format("access.log")), formatted(&CombinedLogFormatter::new()));
+    }
+
+    #[test]
+    fn combined_log_format_lines_have_no_header() {
+        assert_eq!(formatted(&CombinedLogFormatter::new()),
+                   "127.0.0.1 - - [14/Jul/2017:02:40:00 +0000] \"GET / HTTP/1.1\" 200 13 \"-\" \"iron-test\"\n");
+        assert_eq!(formatted(&CombinedLogFormatter::common().utc_offset(60)),
+                   "127.0.0.1 - - [14/Jul/2017:03:40:00 +0100] \"GET / HTTP/1.1\" 200 13\n");
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+
+use super::{LogPacket, LogWriter};
+
+/// Sends a request counter and the request duration, named after the
+/// status class, to Graphite's plaintext protocol over TCP.
+///
+/// With the default `http` prefix, a successful request is reported
+/// as:
+///
+/// ```text
+/// http.2xx.count 1 1500000000
+/// http.2xx.response_time 1.500 1500000000
+/// ```
+///
+/// The timestamp is the start of the request, in seconds, and the
+/// duration is in milliseconds. The connection is kept open between
+/// requests; if it breaks, the logger reconnects and sends the lines
+/// once more before giving up on them.
+pub struct GraphiteLogger {
+    addrs: Vec<SocketAddr>,
+    stream: Option<TcpStream>,
+    prefix: String,
+}
+
+impl GraphiteLogger {
+    pub fn new<A>(addr: A) -> io::Result<GraphiteLogger>
+        where A: ToSocketAddrs
+    {
+        let addrs: Vec<_> = try!(addr.to_socket_addrs()).collect();
+        let stream = try!(TcpStream::connect(&addrs[..]));
+
+        Ok(GraphiteLogger {
+            addrs: addrs,
+            stream: Some(stream),
+            prefix: "http".into(),
+        })
+    }
+
+    /// Sets the path prepended to every metric name.
+    pub fn prefix(self, prefix: &str) -> Self {
+        GraphiteLogger { prefix: prefix.into(), ..self }
+    }
+
+    fn format(&self, packet: &LogPacket) -> String {
+        let timing = packet.timing;
+        let millis = timing.as_secs() as f64 * 1e3 + timing.subsec_nanos() as f64 / 1e6;
+        let timestamp = super::since_epoch(packet.start).as_secs();
+
+        format!("{prefix}.{class}.count 1 {ts}\n{prefix}.{class}.response_time {:.3} {ts}\n",
+                millis, prefix = self.prefix, class = super::status_class(packet.status), ts = timestamp)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(try!(TcpStream::connect(&self.addrs[..])));
+        }
+        let stream = self.stream.as_mut().expect("Not connected");
+        try!(stream.write_all(bytes));
+        stream.flush()
+    }
+}
+
+impl LogWriter for GraphiteLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let lines = self.format(packet);
+
+        let result = self.write(lines.as_bytes()).or_else(|_| {
+            self.stream = None;
+            self.write(lines.as_bytes())
+        });
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::{BufRead, BufReader};
+    use std::net::{Shutdown, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+
+    use iron::status;
+
+    fn received(listener: &TcpListener, n: usize) -> Vec<String> {
+        let (conn, _) = listener.accept().unwrap();
+        BufReader::new(conn).lines().take(n).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn metrics_are_sent_as_plaintext_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut logger = GraphiteLogger::new(listener.local_addr().unwrap()).unwrap().prefix("web.api");
+
+        let mut packet = LogPacket::stub();
+        logger.log(&packet).unwrap();
+        packet.status = Some(status::ServiceUnavailable);
+        logger.log(&packet).unwrap();
+
+        assert_eq!(received(&listener, 4), vec![
+            "web.api.2xx.count 1 1500000000",
+            "web.api.2xx.response_time 1.500 1500000000",
+            "web.api.5xx.count 1 1500000000",
+            "web.api.5xx.response_time 1.500 1500000000",
+        ]);
+    }
+
+    #[test]
+    fn the_logger_reconnects_after_a_failure() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut logger = GraphiteLogger::new(listener.local_addr().unwrap()).unwrap();
+        let (conn, _) = listener.accept().unwrap();
+        conn.shutdown(Shutdown::Both).unwrap();
+        drop(conn);
+
+        // Writes to a closed connection can succeed until the peer's
+        // reset arrives, so keep logging until the logger reconnects
+        listener.set_nonblocking(true).unwrap();
+        let conn = loop {
+            let _ = logger.log(&LogPacket::stub());
+            if let Ok((conn, _)) = listener.accept() {
+                break conn;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        conn.set_nonblocking(false).unwrap();
+
+        let lines: Vec<_> = BufReader::new(conn).lines().take(2).map(Result::unwrap).collect();
+        assert_eq!(lines, vec!["http.2xx.count 1 1500000000", "http.2xx.response_time 1.500 1500000000"]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use csv;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use super::{Encoded, Encoding, LogPacket, LogWriter};
+
+/// Records statistics to a gzip-compressed CSV file.
+///
+/// Each packet is flushed through the compressor, so everything
+/// logged so far can be read even while the file is still being
+/// written. Each time the file is opened a new gzip member is
+/// appended, which standard tools decompress as a single stream. The
+/// member is finished when the logger is dropped. New files start with
+/// a header row.
+pub struct GzFileLogger {
+    csv: csv::Writer<GzEncoder<File>>,
+    encoding: Encoding,
+    needs_header: bool,
+}
+
+impl GzFileLogger {
+    pub fn new<P>(path: P) -> io::Result<GzFileLogger>
+        where P: AsRef<Path>
+    {
+        let (file, is_empty) = try!(super::open_csv(path.as_ref()));
+        let encoder = GzEncoder::new(file, Compression::default());
+
+        Ok(GzFileLogger {
+            csv: super::csv_writer(encoder, super::DEFAULT_DELIMITER),
+            encoding: Encoding::default(),
+            needs_header: is_empty,
+        })
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        GzFileLogger { encoding: encoding, ..self }
+    }
+}
+
+impl LogWriter for GzFileLogger {
+    type Error = csv::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if self.needs_header {
+            try!(self.csv.write_record(self.encoding.header()));
+            self.needs_header = false;
+        }
+        self.csv.serialize(Encoded(packet, &self.encoding))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        try!(self.csv.flush());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+    use self::tempdir::TempDir;
+
+    #[test]
+    fn logs_can_be_decompressed() {
+        let dir = TempDir::new("gz").unwrap();
+        let path = dir.path().join("access.csv.gz");
+
+        for _ in 0..2 {
+            let mut logger = GzFileLogger::new(&path).unwrap();
+            logger.log(&LogPacket::stub()).unwrap();
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+
+        let mut contents = String::new();
+        MultiGzDecoder::new(File::open(&path).unwrap()).read_to_string(&mut contents).unwrap();
+
+        let header = String::from_utf8(super::super::csv_header(super::super::DEFAULT_DELIMITER, &Encoding::default())).unwrap();
+        let row = String::from_utf8(super::super::encode_csv(&LogPacket::stub(), &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(contents, header + &row.repeat(4));
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use iron::prelude::*;
+use iron::{status, Handler};
+
+use super::{json_response, lock, Clock, LoggerHandle};
+
+/// The most recent failure of a logger's writer, to record a packet
+/// or to flush.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastError {
+    pub message: String,
+    /// When it failed, from the logging threads' clock.
+    pub at: SystemTime,
+}
+
+/// Where the logging threads keep their last error. Clones share it.
+#[derive(Clone, Default)]
+pub struct Errors(Arc<Mutex<Option<(LastError, Instant)>>>);
+
+impl Errors {
+    pub fn record(&self, error: &fmt::Display, clock: &Clock) {
+        let last = LastError {
+            message: error.to_string(),
+            at: clock.now_system(),
+        };
+        *lock(&self.0) = Some((last, clock.now_instant()));
+    }
+
+    pub fn last(&self) -> Option<LastError> {
+        lock(&self.0).as_ref().map(|(last, _)| last.clone())
+    }
+
+    /// Whether the last error happened within `window` of `now`.
+    fn recent(&self, window: Duration, now: Instant) -> bool {
+        lock(&self.0).as_ref().is_some_and(|&(_, at)| now.saturating_duration_since(at) < window)
+    }
+}
+
+/// Serves the health of a logger's writer, for health checks: `503
+/// Service Unavailable` when it failed within the window, and `200 OK`
+/// otherwise, with a JSON object like
+/// `{"healthy":false,"last_error":{"message":"disk full","at":"2017-07-14T02:40:00.000000Z"}}`.
+pub struct HealthHandler {
+    handle: LoggerHandle,
+    window: Duration,
+}
+
+pub fn handler(handle: LoggerHandle, window: Duration) -> HealthHandler {
+    HealthHandler {
+        handle: handle,
+        window: window,
+    }
+}
+
+impl Handler for HealthHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        let errors = &self.handle.errors;
+        let healthy = !errors.recent(self.window, self.handle.clock.now_instant());
+        let last_error = errors.last().map(|last| json!({
+            "message": last.message,
+            "at": super::rfc3339(last.at),
+        }));
+
+        let status = if healthy { status::Ok } else { status::ServiceUnavailable };
+        json_response(&json!({"healthy": healthy, "last_error": last_error})).map(|res| res.set(status))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::io;
+
+    use iron::AroundMiddleware;
+    use serde_json::{self, Value};
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{LogPacket, LogWriter, StatisticLogger};
+
+    struct Failing;
+
+    impl LogWriter for Failing {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Err(io::Error::other("disk full"))
+        }
+    }
+
+    fn health(handler: &HealthHandler) -> (Option<status::Status>, Value) {
+        let res = request::get("http://127.0.0.1/healthz", Headers::new(), handler).unwrap();
+        let status = res.status;
+        (status, serde_json::from_str(&response::extract_body_to_string(res)).unwrap())
+    }
+
+    #[test]
+    fn writer_failures_are_reported_until_they_are_old() {
+        let logger = StatisticLogger::builder().on_error(|_packet, _e| {}).build(Failing);
+        let handle = logger.handle();
+        let recent = handle.health_handler(Duration::from_secs(60));
+        let old = handle.health_handler(Duration::from_secs(0));
+        assert_eq!(handle.last_error(), None);
+        assert_eq!(health(&recent), (Some(status::Ok), json!({"healthy": true, "last_error": null})));
+
+        let handler = logger.around(Box::new(|_: &mut Request| Ok(Response::with(status::Ok))));
+        let before = SystemTime::now();
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        assert!(handle.drain(Duration::from_secs(5)));
+
+        let last = handle.last_error().expect("The failure was not recorded");
+        assert_eq!(last.message, "disk full");
+        assert!(last.at >= before);
+
+        let (status, json) = health(&recent);
+        assert_eq!(status, Some(status::ServiceUnavailable));
+        assert_eq!(json["healthy"], false);
+        assert_eq!(json["last_error"]["message"], "disk full");
+        let (status, json) = health(&old);
+        assert_eq!(status, Some(status::Ok));
+        assert_eq!(json["last_error"]["message"], "disk full");
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use csv;
+use serde_json;
+use time;
+
+use super::{daily, Encoding, LogPacket, LogWriter};
+
+/// Records statistics to one CSV file per UTC hour, for archival. A
+/// path of `logs/access.csv` produces files like
+/// `logs/access-2024-06-01T13.csv`, each starting with a header row.
+/// As with `DailyFileLogger`, the hour is when the request started.
+///
+/// A manifest, `manifest.json` beside the files by default, lists
+/// every file with the start of its first and last request, in
+/// seconds since the epoch, and how many records it holds:
+///
+/// ```text
+/// [{"file":"access-2024-06-01T13.csv","first":1717246800.25,"last":1717250399.5,"records":5120}]
+/// ```
+///
+/// It is rewritten when a request starts a new file and when the
+/// logger is dropped, so the current file's entry lags behind until
+/// then. Each time a complete copy replaces the old one, so readers
+/// never see it half written. Files listed in an existing manifest
+/// stay listed.
+pub struct HourlyFileLogger {
+    path: PathBuf,
+    manifest: PathBuf,
+    current: Option<(String, File)>,
+    encoding: Encoding,
+    entries: BTreeMap<String, ManifestEntry>,
+    changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+    file: String,
+    first: f64,
+    last: f64,
+    records: u64,
+}
+
+impl HourlyFileLogger {
+    pub fn new<P>(path: P) -> io::Result<HourlyFileLogger>
+        where P: AsRef<Path>
+    {
+        let manifest = path.as_ref().with_file_name("manifest.json");
+        HourlyFileLogger::with_manifest(path, manifest)
+    }
+
+    /// Like `new`, but keeps the manifest at `manifest`.
+    pub fn with_manifest<P, M>(path: P, manifest: M) -> io::Result<HourlyFileLogger>
+        where P: AsRef<Path>,
+              M: AsRef<Path>,
+    {
+        let manifest = manifest.as_ref().to_path_buf();
+        let entries = match File::open(&manifest) {
+            Ok(file) => try!(serde_json::from_reader::<_, Vec<ManifestEntry>>(file)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(HourlyFileLogger {
+            path: path.as_ref().to_path_buf(),
+            manifest: manifest,
+            current: None,
+            encoding: Encoding::default(),
+            entries: entries.into_iter().map(|entry| (entry.file.clone(), entry)).collect(),
+            changed: false,
+        })
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Opens the file at `path`, for `hour`, unless it is already open, returning
+    /// whether it had to.
+    fn open(&mut self, hour: String, path: &Path) -> csv::Result<bool> {
+        if self.current.as_ref().is_some_and(|(current, _)| *current == hour) {
+            return Ok(false);
+        }
+
+        let (mut file, is_empty) = try!(super::open_csv(path));
+        if is_empty {
+            try!(file.write_all(&super::csv_header(super::DEFAULT_DELIMITER, &self.encoding)));
+        }
+        self.current = Some((hour, file));
+        Ok(true)
+    }
+
+    fn write_manifest(&mut self) -> io::Result<()> {
+        let entries: Vec<_> = self.entries.values().collect();
+        let json = try!(serde_json::to_vec(&entries));
+
+        let mut name = self.manifest.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+        name.push(".tmp");
+        let temporary = self.manifest.with_file_name(name);
+        {
+            let mut file = try!(File::create(&temporary));
+            try!(file.write_all(&json));
+            try!(file.sync_all());
+        }
+        try!(fs::rename(&temporary, &self.manifest));
+
+        self.changed = false;
+        Ok(())
+    }
+}
+
+fn utc_hour(packet: &LogPacket) -> String {
+    let since_epoch = super::since_epoch(packet.start);
+    let tm = time::at_utc(time::Timespec::new(since_epoch.as_secs() as i64, 0));
+    time::strftime("%Y-%m-%dT%H", &tm).expect("Unable to format hour")
+}
+
+impl LogWriter for HourlyFileLogger {
+    type Error = csv::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let row = try!(super::encode_csv(packet, &self.encoding));
+        let hour = utc_hour(packet);
+        let path = daily::dated_path(&self.path, &hour);
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+        let rolled = try!(self.open(hour, &path));
+        {
+            let file = &mut self.current.as_mut().expect("Hourly log file was not opened").1;
+            try!(file.write_all(&row));
+            try!(file.flush());
+        }
+
+        let start = super::since_epoch(packet.start);
+        let start = start.as_secs() as f64 + start.subsec_nanos() as f64 / 1e9;
+        let entry = self.entries.entry(name.clone()).or_insert_with(|| ManifestEntry {
+            file: name,
+            first: start,
+            last: start,
+            records: 0,
+        });
+        entry.first = entry.first.min(start);
+        entry.last = entry.last.max(start);
+        entry.records += 1;
+        self.changed = true;
+
+        if rolled {
+            try!(self.write_manifest());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HourlyFileLogger {
+    fn drop(&mut self) {
+        if self.changed {
+            if let Err(e) = self.write_manifest() {
+                error!("Unable to write log manifest: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::io::Read;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::Value;
+
+    use self::tempdir::TempDir;
+
+    fn manifest(path: &Path) -> Value {
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    #[test]
+    fn the_manifest_lists_each_hour_with_its_records() {
+        let dir = TempDir::new("hourly").unwrap();
+        let manifest_path = dir.path().join("manifest.json");
+        let two_am = UNIX_EPOCH + Duration::from_secs(1_500_000_000 - 2400);
+        let three_am = two_am + Duration::from_secs(3600);
+
+        let mut packet = LogPacket::stub();
+        {
+            let mut logger = HourlyFileLogger::new(dir.path().join("access.csv")).unwrap();
+            for &offset in &[0, 1800, 3599] {
+                packet.start = two_am + Duration::from_secs(offset);
+                logger.log(&packet).unwrap();
+            }
+            packet.start = three_am;
+            logger.log(&packet).unwrap();
+            // The first file is listed once the second is started
+            assert_eq!(manifest(&manifest_path)[0]["records"], 3);
+
+            packet.start = three_am + Duration::from_secs(60);
+            logger.log(&packet).unwrap();
+        }
+
+        assert_eq!(manifest(&manifest_path), json!([
+            {"file": "access-2017-07-14T02.csv", "first": 1_499_997_600.0, "last": 1_500_001_199.0, "records": 3},
+            {"file": "access-2017-07-14T03.csv", "first": 1_500_001_200.0, "last": 1_500_001_260.0, "records": 2},
+        ]));
+        assert!(dir.path().join("access-2017-07-14T03.csv").exists());
+
+        // Reopening keeps counting where the manifest left off, and
+        // flushing leaves the manifest alone until the file rolls
+        let mut logger = HourlyFileLogger::new(dir.path().join("access.csv")).unwrap();
+        logger.log(&packet).unwrap();
+        logger.log(&packet).unwrap();
+        logger.flush().unwrap();
+        assert_eq!(manifest(&manifest_path)[1]["records"], 3);
+        drop(logger);
+        assert_eq!(manifest(&manifest_path)[1]["records"], 4);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::Handler;
+
+use super::json_response;
+
+/// Counts the requests being served by a logger's handler, and the
+/// most served at once, for capacity planning. Requests are counted
+/// whether or not they are logged. Clones share the same counters; use
+/// `handler` to expose them as JSON.
+#[derive(Debug, Clone, Default)]
+pub struct InFlight(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    current: AtomicUsize,
+    max: AtomicUsize,
+}
+
+/// The counts of an `InFlight`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct InFlightCounts {
+    pub current: usize,
+    pub max: usize,
+}
+
+impl InFlight {
+    pub fn snapshot(&self) -> InFlightCounts {
+        InFlightCounts {
+            current: self.0.current.load(Ordering::SeqCst),
+            max: self.0.max.load(Ordering::SeqCst),
+        }
+    }
+
+    /// A handler serving the counts as a JSON object like
+    /// `{"current":2,"max":16}`.
+    pub fn handler(&self) -> InFlightHandler {
+        InFlightHandler(self.clone())
+    }
+}
+
+/// Counts a request until dropped, so a request whose handler panics
+/// still leaves the count.
+pub struct Guard<'a>(&'a Counters);
+
+pub fn enter<'a>(in_flight: &'a InFlight) -> Guard<'a> {
+    let current = in_flight.0.current.fetch_add(1, Ordering::SeqCst) + 1;
+    in_flight.0.max.fetch_max(current, Ordering::SeqCst);
+    Guard(&in_flight.0)
+}
+
+impl<'a> Drop for Guard<'a> {
+    fn drop(&mut self) {
+        self.0.current.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Serves the counts of an `InFlight` as JSON.
+pub struct InFlightHandler(InFlight);
+
+impl Handler for InFlightHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&self.0.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::sync::Barrier;
+    use std::thread;
+    use std::panic::{self, AssertUnwindSafe};
+
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{NullLogger, StatisticLogger};
+
+    #[test]
+    fn concurrent_requests_are_counted() {
+        let entered = Arc::new(Barrier::new(4));
+        let logger = StatisticLogger::new(NullLogger);
+        let in_flight = logger.in_flight();
+        let handler = Arc::new(logger.around(Box::new(move |_req: &mut Request| {
+            // Holds every request until all four are being served
+            entered.wait();
+            Ok(Response::with(status::Ok))
+        })));
+
+        let threads: Vec<_> = (0..4).map(|_| {
+            let handler = handler.clone();
+            thread::spawn(move || {
+                request::get("http://127.0.0.1/", Headers::new(), &*handler).expect("Request failed");
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(in_flight.snapshot(), InFlightCounts { current: 0, max: 4 });
+        let res = request::get("http://127.0.0.1/in-flight", Headers::new(), &in_flight.handler()).unwrap();
+        assert_eq!(response::extract_body_to_string(res), r#"{"current":0,"max":4}"#);
+    }
+
+    #[test]
+    fn panicking_requests_leave_the_count() {
+        let logger = StatisticLogger::new(NullLogger);
+        let in_flight = logger.in_flight();
+        let handler = logger.around(Box::new(|_req: &mut Request| -> IronResult<Response> {
+            panic!("Handler failed")
+        }));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            request::get("http://127.0.0.1/", Headers::new(), &handler)
+        }));
+        assert!(result.is_err());
+        assert_eq!(in_flight.snapshot(), InFlightCounts { current: 0, max: 1 });
+    }
+}
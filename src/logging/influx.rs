@@ -0,0 +1,230 @@
+use std::io;
+use std::mem;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use hyper::client::Client;
+use url::{self, Url};
+
+use super::{LogPacket, LogWriter};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+/// Larger payloads are split across datagrams, so they are not
+/// fragmented or truncated on the way.
+const MAX_DATAGRAM: usize = 1400;
+
+enum Transport {
+    Udp(UdpSocket),
+    Http(Box<Client>, Url),
+}
+
+/// Sends a point per request in the InfluxDB line protocol, such as:
+///
+/// ```text
+/// http_requests,method=GET,status_class=2xx status=200i,timing_ns=1500000i,response_len=13i 1500000000000000000
+/// ```
+///
+/// The timestamp is the start of the request, in nanoseconds. Points
+/// are sent together once 100 are waiting and whenever the logger is
+/// flushed, either as UDP datagrams or in a POST to the `/write`
+/// endpoint.
+pub struct InfluxLogger {
+    transport: Transport,
+    measurement: String,
+    batch_size: usize,
+    points: usize,
+    buffer: String,
+}
+
+impl InfluxLogger {
+    /// Sends to an InfluxDB UDP listener.
+    pub fn udp<A>(addr: A) -> io::Result<InfluxLogger>
+        where A: ToSocketAddrs
+    {
+        Ok(InfluxLogger::new(Transport::Udp(try!(super::connect_udp(addr)))))
+    }
+
+    /// POSTs to a write endpoint such as
+    /// `http://localhost:8086/write?db=web`.
+    pub fn http(url: &str) -> Result<InfluxLogger, url::ParseError> {
+        let mut client = Client::new();
+        client.set_read_timeout(Some(Duration::from_secs(10)));
+        client.set_write_timeout(Some(Duration::from_secs(10)));
+        Ok(InfluxLogger::new(Transport::Http(Box::new(client), try!(Url::parse(url)))))
+    }
+
+    fn new(transport: Transport) -> InfluxLogger {
+        InfluxLogger {
+            transport: transport,
+            measurement: "http_requests".into(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            points: 0,
+            buffer: String::new(),
+        }
+    }
+
+    /// Defaults to `http_requests`.
+    pub fn measurement(self, measurement: &str) -> Self {
+        InfluxLogger { measurement: measurement.into(), ..self }
+    }
+
+    /// Sends as soon as `size` points are waiting.
+    pub fn batch_size(self, size: usize) -> Self {
+        InfluxLogger { batch_size: size.max(1), ..self }
+    }
+
+    fn format(&self, packet: &LogPacket) -> String {
+        let mut fields = Vec::new();
+        if let Some(status) = packet.status {
+            fields.push(format!("status={}i", status.to_u16()));
+        }
+        let timing = packet.timing;
+        fields.push(format!("timing_ns={}i", timing.as_secs() * 1_000_000_000 + timing.subsec_nanos() as u64));
+        if let Some(len) = packet.response_len {
+            fields.push(format!("response_len={}i", len));
+        }
+
+        let start = super::since_epoch(packet.start);
+        format!("{},method={},status_class={} {} {}\n",
+                escape(&self.measurement, ", "),
+                escape(packet.method.as_ref(), ",= "),
+                super::status_class(packet.status),
+                fields.join(","),
+                start.as_secs() as u128 * 1_000_000_000 + start.subsec_nanos() as u128)
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let buffer = mem::take(&mut self.buffer);
+        self.points = 0;
+
+        match self.transport {
+            Transport::Udp(ref socket) => {
+                for datagram in datagrams(&buffer) {
+                    try!(socket.send(datagram.as_bytes()));
+                }
+                Ok(())
+            }
+            Transport::Http(ref client, ref url) => {
+                let mut response = try!(client.post(url.clone())
+                    .body(&buffer[..])
+                    .send()
+                    .map_err(io::Error::other));
+                try!(io::copy(&mut response, &mut io::sink()));
+                if response.status.is_success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("InfluxDB responded with {}", response.status)))
+                }
+            }
+        }
+    }
+}
+
+/// Backslash-escapes each of the characters in `special`.
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Splits the buffered lines into payloads of at most `MAX_DATAGRAM`
+/// bytes, unless a single line is longer than that.
+fn datagrams(buffer: &str) -> Vec<&str> {
+    let mut datagrams = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+    for (i, _) in buffer.match_indices('\n') {
+        if i + 1 - start > MAX_DATAGRAM && end > start {
+            datagrams.push(&buffer[start..end]);
+            start = end;
+        }
+        end = i + 1;
+    }
+    if end > start {
+        datagrams.push(&buffer[start..end]);
+    }
+    datagrams
+}
+
+impl LogWriter for InfluxLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let line = self.format(packet);
+        self.buffer.push_str(&line);
+        self.points += 1;
+
+        if self.points >= self.batch_size {
+            try!(self.send());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::str;
+
+    use iron::method::Method;
+    use iron::status;
+
+    #[test]
+    fn packets_are_formatted_as_line_protocol() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut logger = InfluxLogger::udp(server.local_addr().unwrap()).unwrap().batch_size(2);
+
+        let mut packet = LogPacket::stub();
+        logger.log(&packet).unwrap();
+        packet.method = Method::Extension("MY METHOD,X=1".into());
+        packet.status = None;
+        packet.response_len = None;
+        logger.log(&packet).unwrap();
+
+        let mut buf = [0; 512];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(str::from_utf8(&buf[..len]).unwrap(),
+                   "http_requests,method=GET,status_class=2xx status=200i,timing_ns=1500000i,response_len=13i \
+                    1500000000000000000\n\
+                    http_requests,method=MY\\ METHOD\\,X\\=1,status_class=unknown timing_ns=1500000i \
+                    1500000000000000000\n");
+    }
+
+    #[test]
+    fn large_batches_are_split_across_datagrams() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut logger = InfluxLogger::udp(server.local_addr().unwrap()).unwrap()
+            .measurement("api requests");
+
+        let mut packet = LogPacket::stub();
+        packet.status = Some(status::NotFound);
+        for _ in 0..20 {
+            logger.log(&packet).unwrap();
+        }
+        logger.flush().unwrap();
+
+        let mut buf = [0; 2048];
+        let mut lines = 0;
+        while lines < 20 {
+            let len = server.recv(&mut buf).unwrap();
+            assert!(len <= MAX_DATAGRAM, "{}", len);
+            let datagram = str::from_utf8(&buf[..len]).unwrap();
+            assert!(datagram.starts_with("api\\ requests,method=GET,status_class=4xx status=404i,"), "{}", datagram);
+            lines += datagram.lines().count();
+        }
+        assert_eq!(lines, 20);
+    }
+}
@@ -0,0 +1,164 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use iron::{AroundMiddleware, Handler};
+
+use super::{InFlight, LogHandler, LogPacket, LogWriter, Recorder, Sink, StatisticLoggerBuilder};
+
+type InlineWriter = Box<FnMut(&LogPacket)>;
+
+thread_local! {
+    // The writers of every `SyncStatisticLogger` used on this thread
+    static WRITERS: RefCell<Vec<(usize, InlineWriter)>> = RefCell::new(Vec::new());
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Logs each request on the thread that served it, without a logging
+/// thread or queue, so writers do not need to be `Send`. Each thread
+/// serving requests gets its own writer, made by a factory the first
+/// time it logs, and the writer is flushed after every packet.
+///
+/// Writing is part of serving the request, so a slow writer adds its
+/// latency to every response. This suits low-traffic servers and
+/// writers that are cheap, or that cannot be sent to another thread,
+/// like an `Rc`-based aggregator. Writers live as long as their
+/// thread, even once the logger has been dropped.
+///
+/// Configure it with `StatisticLoggerBuilder::build_inline`.
+pub struct SyncStatisticLogger {
+    recorder: Recorder<Inline>,
+    catch_panics: bool,
+    enabled: bool,
+    in_flight: InFlight,
+}
+
+impl SyncStatisticLogger {
+    /// Logs with writers made by calling `factory`.
+    pub fn new<F, L>(factory: F) -> SyncStatisticLogger
+        where F: Fn() -> L + Send + Sync + 'static,
+              L: LogWriter + 'static,
+    {
+        StatisticLoggerBuilder::new().build_inline(factory)
+    }
+
+    /// Counts the requests being served by the wrapped handler.
+    pub fn in_flight(&self) -> InFlight {
+        self.in_flight.clone()
+    }
+}
+
+pub fn logger(recorder: Recorder<Inline>, catch_panics: bool, enabled: bool) -> SyncStatisticLogger {
+    SyncStatisticLogger {
+        recorder: recorder,
+        catch_panics: catch_panics,
+        enabled: enabled,
+        in_flight: InFlight::default(),
+    }
+}
+
+impl AroundMiddleware for SyncStatisticLogger {
+    fn around(self, handler: Box<Handler>) -> Box<Handler> {
+        Box::new(LogHandler {
+            handler: handler,
+            recorder: self.recorder,
+            catch_panics: self.catch_panics,
+            in_flight: self.in_flight,
+            disabled: !self.enabled,
+        })
+    }
+}
+
+/// Writes packets with the current thread's writer.
+pub struct Inline {
+    id: usize,
+    make: Box<Fn() -> InlineWriter + Send + Sync>,
+}
+
+impl Inline {
+    pub fn new<F, L>(factory: F) -> Inline
+        where F: Fn() -> L + Send + Sync + 'static,
+              L: LogWriter + 'static,
+    {
+        Inline {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            make: Box::new(move || -> InlineWriter {
+                let mut writer = factory();
+                Box::new(move |packet: &LogPacket| {
+                    if let Err(e) = writer.log(packet).and_then(|()| writer.flush()) {
+                        error!("Unable to log request: {}", e);
+                    }
+                })
+            }),
+        }
+    }
+}
+
+impl Sink for Inline {
+    fn send_with<F>(&self, packet: F)
+        where F: FnOnce() -> LogPacket
+    {
+        let packet = packet();
+        WRITERS.with(|writers| {
+            let mut writers = writers.borrow_mut();
+            let index = match writers.iter().position(|&(id, _)| id == self.id) {
+                Some(index) => index,
+                None => {
+                    writers.push((self.id, (self.make)()));
+                    writers.len() - 1
+                }
+            };
+            (writers[index].1)(&packet);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::io;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    use iron::prelude::*;
+    use iron::status;
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    /// Holds an `Rc`, so it cannot be sent to a logging thread.
+    struct NotSend {
+        _rc: Rc<()>,
+        paths: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl LogWriter for NotSend {
+        type Error = io::Error;
+
+        fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+            self.paths.lock().unwrap().push(packet.path().to_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writers_that_are_not_send_log_inline() {
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let logged = paths.clone();
+        let logger = SyncStatisticLogger::new(move || NotSend {
+            _rc: Rc::new(()),
+            paths: logged.clone(),
+        });
+        let handler = logger.around(Box::new(|_req: &mut Request| Ok(Response::with(status::Ok))));
+
+        request::get("http://127.0.0.1/a", Headers::new(), &handler).expect("Request failed");
+        // Written before the response is returned
+        assert_eq!(*paths.lock().unwrap(), vec!["/a"]);
+        request::get("http://127.0.0.1/b", Headers::new(), &handler).expect("Request failed");
+        assert_eq!(*paths.lock().unwrap(), vec!["/a", "/b"]);
+    }
+}
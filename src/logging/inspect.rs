@@ -0,0 +1,74 @@
+use super::{LogPacket, LogWriter, Summary};
+
+/// Calls a closure with every packet before forwarding it, for simple
+/// integrations like pushing to a message bus or updating an in-app
+/// dashboard. The closure runs on the logging thread. Wrap a
+/// `NullLogger` to use the closure on its own.
+pub struct Inspect<L> {
+    logger: L,
+    on_log: Box<FnMut(&LogPacket) + Send>,
+}
+
+impl<L> Inspect<L> {
+    pub fn new<F>(logger: L, on_log: F) -> Inspect<L>
+        where F: FnMut(&LogPacket) + Send + 'static
+    {
+        Inspect {
+            logger: logger,
+            on_log: Box::new(on_log),
+        }
+    }
+}
+
+impl<L: LogWriter> LogWriter for Inspect<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        (self.on_log)(packet);
+        self.logger.log(packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use iron::prelude::*;
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::{NullLogger, StatisticLogger};
+
+    #[test]
+    fn every_packet_is_inspected() {
+        let urls = Arc::new(Mutex::new(Vec::new()));
+        let seen = urls.clone();
+        let logger = Inspect::new(NullLogger, move |packet: &LogPacket| {
+            seen.lock().unwrap().push(packet.url().to_string());
+        });
+
+        let handler = StatisticLogger::new(logger).around(Box::new(|_req: &mut Request| {
+            Ok(Response::with(status::Ok))
+        }));
+        request::get("http://127.0.0.1/a", Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/b?page=2", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        assert_eq!(*urls.lock().unwrap(), vec!["http://127.0.0.1/a", "http://127.0.0.1/b?page=2"]);
+    }
+}
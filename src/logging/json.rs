@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use serde::ser::{Serialize, Serializer};
+use serde_json::{self, Value};
+
+use super::{LogPacket, LogWriter, StatusFormat, Summary};
+
+/// Records statistics as one JSON object per line, suitable for log
+/// pipelines that ingest newline-delimited JSON. When `Summarized`,
+/// the last line is an object like
+/// `{"summary":{"requests":2,"response_bytes":26,"uptime_secs":60.0}}`.
+pub struct JsonLogger<W: Write> {
+    stream: W,
+    status: StatusFormat,
+}
+
+impl<W: Write> JsonLogger<W> {
+    pub fn new(stream: W) -> JsonLogger<W> {
+        JsonLogger {
+            stream: stream,
+            status: StatusFormat::default(),
+        }
+    }
+
+    /// Writes statuses as numbers like `404` by default, or as strings
+    /// like `"NotFound"`.
+    pub fn status_format(self, format: StatusFormat) -> Self {
+        JsonLogger { status: format, ..self }
+    }
+}
+
+impl<W: Write> LogWriter for JsonLogger<W> {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.stream.write_all(&try!(encode_with(packet, self.status)))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.stream.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        let line = json!({
+            "summary": {
+                "requests": summary.requests,
+                "response_bytes": summary.response_bytes,
+                "uptime_secs": as_secs(summary.uptime),
+            },
+        });
+        self.stream.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// Packets as a JSON array.
+pub fn encode_array(packets: &[LogPacket]) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(&PacketArray(packets))
+}
+
+/// Serializes packets as a JSON array.
+pub struct PacketArray<'a>(pub &'a [LogPacket]);
+
+impl<'a> Serialize for PacketArray<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(JsonPacket::from))
+    }
+}
+
+/// A packet as a single line of JSON, including the trailing newline.
+pub fn encode(packet: &LogPacket) -> serde_json::Result<Vec<u8>> {
+    encode_line(JsonPacket::from(packet))
+}
+
+/// Like `encode`, with statuses written as `status` says.
+pub fn encode_with(packet: &LogPacket, status: StatusFormat) -> serde_json::Result<Vec<u8>> {
+    encode_line(JsonPacket::new(packet, status))
+}
+
+fn encode_line(packet: JsonPacket) -> serde_json::Result<Vec<u8>> {
+    let mut line = try!(serde_json::to_vec(&packet));
+    line.push(b'\n');
+    Ok(line)
+}
+
+#[derive(Serialize)]
+struct JsonPacket {
+    url: String,
+    method: String,
+    ip: Option<String>,
+    status: Option<Value>,
+    start: f64,
+    timing_secs: f64,
+    response_len: Option<u64>,
+    user_agent: Option<String>,
+    referer: Option<String>,
+    request_id: String,
+    path: String,
+    query: Option<String>,
+    truncated: bool,
+    host: Option<String>,
+    content_type: Option<String>,
+    error: Option<String>,
+    request_len: Option<u64>,
+    request_headers: BTreeMap<String, String>,
+    response_headers: BTreeMap<String, String>,
+    scheme: String,
+    http_version: String,
+    tls_version: Option<String>,
+    tls_cipher: Option<String>,
+    overhead_secs: f64,
+    total_secs: f64,
+    ttfb_secs: Option<f64>,
+    tags: BTreeMap<String, String>,
+    fields: BTreeMap<String, String>,
+    route: String,
+    label: Option<String>,
+    count: u32,
+    sample_rate: f64,
+    seq: u64,
+    log_lag_secs: f64,
+}
+
+fn as_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+impl<'a> From<&'a LogPacket> for JsonPacket {
+    fn from(packet: &'a LogPacket) -> JsonPacket {
+        JsonPacket::new(packet, StatusFormat::default())
+    }
+}
+
+impl JsonPacket {
+    fn new(packet: &LogPacket, status: StatusFormat) -> JsonPacket {
+        let start = super::since_epoch(packet.start);
+
+        JsonPacket {
+            url: packet.url.to_string(),
+            method: packet.method.to_string(),
+            ip: packet.peer_addr().map(|ip| ip.to_string()),
+            status: packet.status.map(|s| match status {
+                StatusFormat::Code => Value::from(s.to_u16()),
+                StatusFormat::Name => Value::from(status.format(s)),
+            }),
+            start: as_secs(start),
+            timing_secs: as_secs(packet.timing),
+            response_len: packet.response_len,
+            user_agent: packet.user_agent.clone(),
+            referer: packet.referer.clone(),
+            request_id: packet.request_id.clone(),
+            path: packet.path().into(),
+            query: packet.query.clone(),
+            truncated: packet.truncated,
+            host: packet.host.clone(),
+            content_type: packet.content_type.clone(),
+            error: packet.error.clone(),
+            request_len: packet.request_len,
+            request_headers: packet.request_headers.clone(),
+            response_headers: packet.response_headers.clone(),
+            scheme: packet.scheme.clone(),
+            http_version: packet.http_version.clone(),
+            tls_version: packet.tls_version.clone(),
+            tls_cipher: packet.tls_cipher.clone(),
+            overhead_secs: as_secs(packet.overhead),
+            total_secs: as_secs(packet.total),
+            ttfb_secs: packet.ttfb.map(as_secs),
+            tags: packet.tags.clone(),
+            fields: packet.fields.clone(),
+            route: packet.route().unwrap_or(packet.path()).into(),
+            label: packet.label.clone(),
+            count: packet.count,
+            sample_rate: packet.sample_rate,
+            seq: packet.seq,
+            log_lag_secs: as_secs(packet.log_lag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::Value;
+
+    #[test]
+    fn packets_are_written_as_json_lines() {
+        let mut output = Vec::new();
+        {
+            let mut logger = JsonLogger::new(&mut output);
+            logger.log(&LogPacket::stub()).unwrap();
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let json: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(json["url"], "http://127.0.0.1/");
+        assert_eq!(json["method"], "GET");
+        assert_eq!(json["ip"], "127.0.0.1:3000");
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["start"], 1_500_000_000.0);
+        assert_eq!(json["timing_secs"], 0.0015);
+        assert_eq!(json["response_len"], 13);
+        assert_eq!(json["user_agent"], "iron-test");
+    }
+
+    #[test]
+    fn statuses_can_be_written_by_name() {
+        let mut output = Vec::new();
+        JsonLogger::new(&mut output).status_format(StatusFormat::Name).log(&LogPacket::stub()).unwrap();
+
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["status"], "Ok");
+    }
+
+    #[test]
+    fn summaries_are_written_as_json_lines() {
+        let mut output = Vec::new();
+        JsonLogger::new(&mut output).finalize(&Summary {
+            requests: 2,
+            response_bytes: 26,
+            uptime: Duration::from_millis(1500),
+        }).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(),
+                   "{\"summary\":{\"requests\":2,\"response_bytes\":26,\"uptime_secs\":1.5}}\n");
+    }
+}
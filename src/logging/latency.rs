@@ -0,0 +1,246 @@
+use std::io;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use iron::prelude::*;
+use iron::Handler;
+use serde_json::{Map, Value};
+
+use super::{json_response, lock, Aggregator, LogPacket, LogWriter};
+
+const DEFAULT_PERCENTILES: &[f64] = &[50.0, 90.0, 95.0, 99.0, 99.9];
+
+/// Values below this many microseconds are counted exactly; larger
+/// values share buckets whose width is under 1/64th of their value.
+const LINEAR_BUCKETS: u64 = 128;
+const SUB_BUCKETS: u64 = LINEAR_BUCKETS / 2;
+
+/// Tracks the distribution of request latencies so that percentiles
+/// like p50 and p99 can be read at any time. Clones share the same
+/// histogram; use `handler` to expose the percentiles as JSON.
+///
+/// Latencies are recorded with microsecond resolution in a
+/// log-linear histogram, so a reported percentile is the upper bound
+/// of a bucket and is at most about 1.6% above the true value.
+#[derive(Debug, Clone)]
+pub struct LatencyRecorder {
+    percentiles: Arc<Vec<f64>>,
+    histogram: Arc<Mutex<Histogram>>,
+}
+
+impl LatencyRecorder {
+    pub fn new() -> LatencyRecorder {
+        LatencyRecorder::with_percentiles(DEFAULT_PERCENTILES.to_vec())
+    }
+
+    /// Reports the given percentiles, each between 0 and 100.
+    pub fn with_percentiles(percentiles: Vec<f64>) -> LatencyRecorder {
+        LatencyRecorder {
+            percentiles: Arc::new(percentiles),
+            histogram: Arc::new(Mutex::new(Histogram::default())),
+        }
+    }
+
+    /// The number of latencies recorded so far.
+    pub fn count(&self) -> u64 {
+        lock(&self.histogram).total
+    }
+
+    /// The estimated number of requests so far, sampled or not, with
+    /// each latency standing for `1 / LogPacket::sample_rate` of them.
+    /// The percentiles are not weighted.
+    pub fn estimated_count(&self) -> f64 {
+        lock(&self.histogram).estimated
+    }
+
+    /// Each configured percentile with its current latency. All
+    /// latencies are zero until a request has been recorded.
+    pub fn percentiles(&self) -> Vec<(f64, Duration)> {
+        let histogram = lock(&self.histogram);
+        self.percentiles.iter().map(|&p| (p, histogram.percentile(p))).collect()
+    }
+
+    /// Clears the histogram, returning a recorder that holds the
+    /// latencies recorded before, to read its count and percentiles.
+    pub fn reset(&self) -> LatencyRecorder {
+        let histogram = mem::take(&mut *lock(&self.histogram));
+        LatencyRecorder {
+            percentiles: self.percentiles.clone(),
+            histogram: Arc::new(Mutex::new(histogram)),
+        }
+    }
+
+    /// A handler serving the count, its estimate and the percentiles,
+    /// in seconds, as a JSON object like
+    /// `{"count":10,"estimated_count":100.0,"p50":0.0015,"p99":0.25}`.
+    pub fn handler(&self) -> LatencyHandler {
+        LatencyHandler(self.clone())
+    }
+
+    fn to_json(&self) -> Value {
+        let mut json = Map::new();
+        json.insert("count".into(), self.count().into());
+        json.insert("estimated_count".into(), self.estimated_count().into());
+        for (p, latency) in self.percentiles() {
+            let secs = latency.as_secs() as f64 + latency.subsec_nanos() as f64 / 1e9;
+            json.insert(format!("p{}", p), secs.into());
+        }
+        Value::Object(json)
+    }
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> LatencyRecorder {
+        LatencyRecorder::new()
+    }
+}
+
+impl Aggregator for LatencyRecorder {
+    fn observe(&self, packet: &LogPacket) {
+        let micros = packet.timing.as_secs() * 1_000_000 + packet.timing.subsec_micros() as u64;
+        let mut histogram = lock(&self.histogram);
+        histogram.record(micros);
+        histogram.estimated += 1.0 / packet.sample_rate;
+    }
+
+    /// The percentiles served by `handler`.
+    fn snapshot(&self) -> Value {
+        self.to_json()
+    }
+}
+
+impl LogWriter for LatencyRecorder {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    estimated: f64,
+}
+
+impl Histogram {
+    fn record(&mut self, micros: u64) {
+        let index = bucket_index(micros);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.total += 1;
+    }
+
+    fn percentile(&self, percentile: f64) -> Duration {
+        let target = ((percentile / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+
+        let mut seen = 0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return Duration::from_micros(bucket_upper_bound(index));
+            }
+        }
+        Duration::from_micros(0)
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros < LINEAR_BUCKETS {
+        return micros as usize;
+    }
+    // Shift so the value lands in [SUB_BUCKETS, LINEAR_BUCKETS)
+    let shift = 63 - micros.leading_zeros() as u64 - 6;
+    (LINEAR_BUCKETS + (shift - 1) * SUB_BUCKETS + ((micros >> shift) - SUB_BUCKETS)) as usize
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < LINEAR_BUCKETS {
+        return index;
+    }
+    let shift = (index - LINEAR_BUCKETS) / SUB_BUCKETS + 1;
+    let sub_bucket = (index - LINEAR_BUCKETS) % SUB_BUCKETS + SUB_BUCKETS;
+    ((sub_bucket + 1) << shift) - 1
+}
+
+/// Serves the percentiles of a `LatencyRecorder` as JSON.
+pub struct LatencyHandler(LatencyRecorder);
+
+impl Handler for LatencyHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&self.0.to_json())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    fn within(actual: Duration, expected: Duration) -> bool {
+        actual >= expected && actual <= expected + expected / 64
+    }
+
+    #[test]
+    fn percentiles_follow_the_distribution() {
+        let mut recorder = LatencyRecorder::with_percentiles(vec![50.0, 99.0]);
+
+        let mut packet = LogPacket::stub();
+        for ms in 1..1001 {
+            packet.timing = Duration::from_millis(ms);
+            recorder.log(&packet).unwrap();
+        }
+
+        let percentiles = recorder.percentiles();
+        assert_eq!(percentiles[0].0, 50.0);
+        assert!(within(percentiles[0].1, Duration::from_millis(500)), "{:?}", percentiles);
+        assert_eq!(percentiles[1].0, 99.0);
+        assert!(within(percentiles[1].1, Duration::from_millis(990)), "{:?}", percentiles);
+    }
+
+    #[test]
+    fn sampled_latencies_are_scaled_back_up() {
+        let mut recorder = LatencyRecorder::new();
+        let mut packet = LogPacket::stub();
+        recorder.log(&packet).unwrap();
+        packet.sample_rate = 0.1;
+        recorder.log(&packet).unwrap();
+
+        assert_eq!(recorder.count(), 2);
+        assert!((recorder.estimated_count() - 11.0).abs() < 1e-9, "{}", recorder.estimated_count());
+    }
+
+    #[test]
+    fn buckets_cover_their_values() {
+        for &micros in &[0, 1, 127, 128, 129, 1_000, 65_535, 1_000_000, 123_456_789] {
+            let upper = bucket_upper_bound(bucket_index(micros));
+            assert!(upper >= micros && upper <= micros + micros / 64, "{} -> {}", micros, upper);
+        }
+    }
+
+    #[test]
+    fn percentiles_are_served_as_json() {
+        let mut recorder = LatencyRecorder::with_percentiles(vec![50.0, 99.9]);
+        recorder.log(&LogPacket::stub()).unwrap();
+
+        let res = request::get("http://127.0.0.1/latency", Headers::new(), &recorder.handler()).unwrap();
+        let json: Value = serde_json::from_str(&response::extract_body_to_string(res)).unwrap();
+
+        // 1500µs falls in the bucket covering 1488µs to 1503µs
+        assert_eq!(json["count"], 1);
+        assert_eq!(json["estimated_count"], 1.0);
+        assert_eq!(json["p50"], 0.001_503);
+        assert_eq!(json["p99.9"], 0.001_503);
+    }
+}
@@ -0,0 +1,153 @@
+use std::io;
+
+use iron::status::Status;
+use log::LogLevel;
+
+use super::{format_duration, syslog, LogPacket, LogWriter, Severity};
+
+/// Emits each request as a record through the `log` crate, so access
+/// lines go wherever the rest of the application's logs go:
+///
+/// ```text
+/// GET http://127.0.0.1/ 200 0.001500000s ip=127.0.0.1:3000 request_id=0b8cc2e6-...
+/// ```
+///
+/// Server errors are logged at the warn level and everything else at
+/// the info level, unless `severity_mapping` says otherwise. Records
+/// use the `access` target by default.
+pub struct LogCrateLogger {
+    target: String,
+    severity: syslog::SeverityMapping,
+}
+
+impl LogCrateLogger {
+    pub fn new() -> LogCrateLogger {
+        LogCrateLogger {
+            target: "access".into(),
+            severity: Box::new(syslog::default_severity),
+        }
+    }
+
+    /// Sets the target of the emitted records, for filtering them.
+    pub fn target(self, target: &str) -> Self {
+        LogCrateLogger { target: target.into(), ..self }
+    }
+
+    /// Chooses the level of each record from the status. Emergencies
+    /// through errors are logged as errors, and notices as info.
+    pub fn severity_mapping<F>(self, mapping: F) -> Self
+        where F: Fn(Option<Status>) -> Severity + Send + 'static
+    {
+        LogCrateLogger { severity: Box::new(mapping), ..self }
+    }
+}
+
+fn level(severity: Severity) -> LogLevel {
+    match severity {
+        Severity::Emergency | Severity::Alert | Severity::Critical | Severity::Error => LogLevel::Error,
+        Severity::Warning => LogLevel::Warn,
+        Severity::Notice | Severity::Informational => LogLevel::Info,
+        Severity::Debug => LogLevel::Debug,
+    }
+}
+
+impl Default for LogCrateLogger {
+    fn default() -> LogCrateLogger {
+        LogCrateLogger::new()
+    }
+}
+
+impl LogWriter for LogCrateLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let level = level((self.severity)(packet.status));
+        let status = packet.status
+            .map(|s| s.to_u16().to_string())
+            .unwrap_or_else(|| "-".into());
+
+        log!(target: &self.target, level, "{} {} {} {}s ip={} request_id={}",
+             packet.method, packet.url, status, format_duration(&packet.timing), super::format_ip(packet), packet.request_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::{Mutex, Once};
+
+    use iron::status;
+    use log::{self, Log, LogLevelFilter, LogMetadata, LogRecord};
+
+    static INIT: Once = Once::new();
+    static RECORDS: Mutex<Vec<(String, LogLevel, String)>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl Log for Capture {
+        fn enabled(&self, _metadata: &LogMetadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &LogRecord) {
+            let record = (record.target().to_owned(), record.level(), record.args().to_string());
+            RECORDS.lock().unwrap().push(record);
+        }
+    }
+
+    fn records_for(target: &str) -> Vec<(LogLevel, String)> {
+        RECORDS.lock().unwrap().iter()
+            .filter(|r| r.0 == target)
+            .map(|r| (r.1, r.2.clone()))
+            .collect()
+    }
+
+    fn capture() {
+        INIT.call_once(|| {
+            log::set_logger(|max| {
+                max.set(LogLevelFilter::Info);
+                Box::new(Capture)
+            }).unwrap();
+        });
+    }
+
+    #[test]
+    fn packets_are_emitted_as_log_records() {
+        capture();
+
+        let mut logger = LogCrateLogger::new().target("access-test");
+        logger.log(&LogPacket::stub()).unwrap();
+        let mut packet = LogPacket::stub();
+        packet.status = Some(status::BadGateway);
+        logger.log(&packet).unwrap();
+
+        let records = records_for("access-test");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, LogLevel::Info);
+        assert_eq!(records[0].1, "GET http://127.0.0.1/ 200 0.001500000s ip=127.0.0.1:3000 \
+                                  request_id=0b8cc2e6-2ba7-4b5c-9e3e-4f26e3a1a2de");
+        assert_eq!(records[1].0, LogLevel::Warn);
+        assert!(records[1].1.starts_with("GET http://127.0.0.1/ 502 "), "{}", records[1].1);
+    }
+
+    #[test]
+    fn levels_can_be_mapped_from_the_status() {
+        capture();
+
+        let mut logger = LogCrateLogger::new().target("access-mapped").severity_mapping(|status| match status {
+            Some(status::NotFound) => Severity::Error,
+            Some(status::Unregistered(499)) => Severity::Warning,
+            status => syslog::default_severity(status),
+        });
+        let mut packet = LogPacket::stub();
+        for &status in &[status::NotFound, status::Unregistered(499), status::Ok] {
+            packet.status = Some(status);
+            logger.log(&packet).unwrap();
+        }
+
+        let levels: Vec<_> = records_for("access-mapped").into_iter().map(|r| r.0).collect();
+        assert_eq!(levels, vec![LogLevel::Error, LogLevel::Warn, LogLevel::Info]);
+    }
+}
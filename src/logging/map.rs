@@ -0,0 +1,72 @@
+use super::{LogPacket, LogWriter, Summary};
+
+/// Rewrites every packet before forwarding it, to enrich or normalize
+/// what is recorded, such as lowercasing paths or replacing user
+/// agents. The closure runs on the logging thread and changes the
+/// packet with its setters, like `LogPacket::set_method`.
+pub struct MapLogger<L> {
+    logger: L,
+    map: Box<FnMut(LogPacket) -> LogPacket + Send>,
+}
+
+impl<L> MapLogger<L> {
+    pub fn new<F>(logger: L, map: F) -> MapLogger<L>
+        where F: FnMut(LogPacket) -> LogPacket + Send + 'static
+    {
+        MapLogger {
+            logger: logger,
+            map: Box::new(map),
+        }
+    }
+}
+
+impl<L: LogWriter> LogWriter for MapLogger<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let packet = (self.map)(packet.clone());
+        self.logger.log(&packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron;
+    use iron::method::Method;
+
+    use super::super::MemoryLogger;
+
+    #[test]
+    fn packets_are_rewritten_before_they_are_logged() {
+        let memory = MemoryLogger::new();
+        let mut logger = MapLogger::new(memory.clone(), |mut packet: LogPacket| {
+            let method = packet.method().to_string().to_uppercase().parse().unwrap();
+            packet.set_method(method);
+            let mut url = packet.url().clone();
+            let path = url.as_ref().path().to_lowercase();
+            url.as_mut().set_path(&path);
+            packet.set_url(url);
+            packet
+        });
+
+        let mut packet = LogPacket::stub();
+        packet.method = Method::Extension("purge".into());
+        packet.url = iron::Url::parse("http://127.0.0.1/Cache/Index.html?page=2").unwrap();
+        logger.log(&packet).unwrap();
+
+        let packets = memory.packets();
+        assert_eq!(*packets[0].method(), Method::Extension("PURGE".into()));
+        assert_eq!(packets[0].path(), "/cache/index.html");
+        assert_eq!(packets[0].query(), Some("page=2"));
+    }
+}
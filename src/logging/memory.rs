@@ -0,0 +1,63 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use super::{lock, LogPacket, LogWriter};
+
+/// Keeps every packet in memory. Clones share the same storage, so
+/// one clone can be given to a `StatisticLogger` while another is
+/// kept to inspect what was logged, which is mostly useful in tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLogger(Arc<Mutex<Vec<LogPacket>>>);
+
+impl MemoryLogger {
+    pub fn new() -> MemoryLogger {
+        MemoryLogger::default()
+    }
+
+    /// A copy of the packets logged so far.
+    pub fn packets(&self) -> Vec<LogPacket> {
+        lock(&self.0).clone()
+    }
+}
+
+impl LogWriter for MemoryLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        lock(&self.0).push(packet.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::prelude::*;
+    use iron::{status, AroundMiddleware};
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::StatisticLogger;
+
+    fn not_found(_req: &mut Request) -> IronResult<Response> {
+        Ok(Response::with(status::NotFound))
+    }
+
+    #[test]
+    fn logged_packets_can_be_inspected() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(not_found));
+
+        request::get("http://127.0.0.1/missing", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].status, Some(status::NotFound));
+        assert_eq!(packets[0].url.path(), ["missing"]);
+    }
+}
@@ -0,0 +1,86 @@
+//! Records requests through the `metrics` crate's facade, behind the
+//! `metrics` feature.
+
+use std::io;
+
+use metrics::{counter, histogram};
+
+use super::{LogPacket, LogWriter};
+
+/// Counts every request in `http_requests_total` and records its
+/// duration, in seconds, in `http_request_duration_seconds`, both
+/// labeled with the `method` and the `status` code, which is
+/// `unknown` when the response had none. Packets reach whichever
+/// exporter the app installed as the `metrics` recorder.
+#[derive(Debug, Default)]
+pub struct MetricsFacadeLogger;
+
+impl MetricsFacadeLogger {
+    pub fn new() -> MetricsFacadeLogger {
+        MetricsFacadeLogger
+    }
+}
+
+impl LogWriter for MetricsFacadeLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let labels = [
+            ("method", packet.method.to_string()),
+            ("status", packet.status.map(|s| s.to_u16().to_string()).unwrap_or_else(|| "unknown".into())),
+        ];
+        let seconds = packet.timing.as_secs() as f64 + packet.timing.subsec_nanos() as f64 / 1e9;
+        counter!("http_requests_total", &labels).increment(u64::from(packet.count));
+        histogram!("http_request_duration_seconds", &labels).record_many(seconds, packet.count as usize);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate metrics_util;
+
+    use super::*;
+
+    use iron::method::Method;
+    use metrics;
+
+    use self::metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    #[test]
+    fn requests_are_counted_and_timed_by_method_and_status() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let mut logger = MetricsFacadeLogger::new();
+            let mut packet = LogPacket::stub();
+            packet.count = 2;
+            logger.log(&packet).unwrap();
+
+            let mut packet = LogPacket::stub();
+            packet.method = Method::Post;
+            packet.status = None;
+            logger.log(&packet).unwrap();
+        });
+
+        let mut recorded: Vec<_> = snapshotter.snapshot().into_vec().into_iter()
+            .map(|(key, _, _, value)| {
+                let labels: Vec<_> = key.key().labels().map(|l| format!("{}={}", l.key(), l.value())).collect();
+                let value = match value {
+                    DebugValue::Counter(count) => vec![count as f64],
+                    DebugValue::Histogram(values) => values.into_iter().map(|value| value.into_inner()).collect(),
+                    DebugValue::Gauge(_) => panic!("Unexpected gauge"),
+                };
+                (key.key().name().to_owned(), labels.join(","), value)
+            })
+            .collect();
+        recorded.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+        assert_eq!(recorded, vec![
+            ("http_request_duration_seconds".to_owned(), "method=GET,status=200".to_owned(), vec![0.0015, 0.0015]),
+            ("http_request_duration_seconds".to_owned(), "method=POST,status=unknown".to_owned(), vec![0.0015]),
+            ("http_requests_total".to_owned(), "method=GET,status=200".to_owned(), vec![2.0]),
+            ("http_requests_total".to_owned(), "method=POST,status=unknown".to_owned(), vec![1.0]),
+        ]);
+    }
+}
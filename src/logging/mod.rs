@@ -0,0 +1,3434 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
+use std::time::{Instant, SystemTime, Duration};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::panic::{self, AssertUnwindSafe};
+use std::{error, fmt, io, thread, net};
+
+use csv;
+use time;
+use serde::ser::{Serialize, Serializer};
+use serde_json;
+use iron;
+use iron::prelude::*;
+use iron::{Handler, AroundMiddleware};
+use iron::headers::{ContentLength, ContentType, Host, Referer, UserAgent};
+use iron::method::Method;
+use iron::modifiers::Header;
+use iron::status::Status;
+
+mod aggregate;
+mod anonymize;
+mod background;
+mod boxed;
+mod builder;
+mod bytes;
+mod capture;
+mod clf;
+mod clock;
+mod coalesce;
+mod daily;
+mod dead_letter;
+mod emf;
+mod encoding;
+mod fallback;
+#[cfg(unix)]
+mod fifo;
+mod fields;
+mod first_byte;
+mod formatter;
+mod graphite;
+mod gz;
+mod health;
+mod hourly;
+mod in_flight;
+mod influx;
+mod inline;
+mod inspect;
+mod json;
+mod latency;
+mod log_crate;
+mod map;
+mod memory;
+#[cfg(feature = "metrics")]
+mod metrics_facade;
+mod mount;
+mod multi;
+mod network;
+mod null;
+mod only_status;
+#[cfg(feature = "otel")]
+mod otel;
+mod path_filter;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod prometheus;
+mod query;
+mod queue;
+mod rate;
+mod rate_limit;
+mod reader;
+mod redaction;
+mod request_id;
+mod retry;
+mod ring_buffer;
+mod rotating;
+mod route_pattern;
+mod routes;
+mod sampling;
+mod slow;
+mod split;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod stats;
+mod statsd;
+mod status_counts;
+mod stream;
+mod summary;
+mod syslog;
+mod tcp;
+mod tls;
+#[cfg(feature = "tracing")]
+mod tracing;
+mod webhook;
+mod window;
+
+use self::capture::Capture;
+use self::encoding::Encoded;
+use self::health::Errors;
+use self::queue::ContentionCounter;
+
+pub use self::aggregate::{Aggregator, AggregateLogger, AggregateHandler};
+pub use self::anonymize::IpAnonymization;
+pub use self::background::BackgroundLogger;
+pub use self::boxed::{BoxedLogWriter, BoxedError};
+pub use self::builder::{ErrorHandler, LogErrors, StatisticLoggerBuilder};
+pub use self::bytes::{ByteCounter, ByteCounts, ByteCountHandler};
+pub use self::clf::CommonLogFormatLogger;
+pub use self::clock::{Clock, SystemClock, TimingSource};
+pub use self::daily::DailyFileLogger;
+pub use self::dead_letter::DeadLetterLogger;
+pub use self::emf::EmfLogger;
+pub use self::encoding::{Column, Encoding, StatusFormat, TimingUnit, TimestampFormat};
+pub use self::fallback::{FallbackLogger, FallbackError};
+#[cfg(unix)]
+pub use self::fifo::FifoLogger;
+pub use self::fields::LogFields;
+pub use self::formatter::{Formatter, CsvFormatter, JsonFormatter, CombinedLogFormatter, FileFormat};
+pub use self::graphite::GraphiteLogger;
+pub use self::gz::GzFileLogger;
+pub use self::health::{HealthHandler, LastError};
+pub use self::hourly::HourlyFileLogger;
+pub use self::in_flight::{InFlight, InFlightCounts, InFlightHandler};
+pub use self::influx::InfluxLogger;
+pub use self::inline::SyncStatisticLogger;
+pub use self::inspect::Inspect;
+pub use self::json::JsonLogger;
+pub use self::latency::{LatencyRecorder, LatencyHandler};
+pub use self::log_crate::LogCrateLogger;
+pub use self::map::MapLogger;
+pub use self::memory::MemoryLogger;
+#[cfg(feature = "metrics")]
+pub use self::metrics_facade::MetricsFacadeLogger;
+pub use self::mount::PrefixLogger;
+pub use self::multi::{MultiLogger, MultiError, RoutingLogger};
+pub use self::network::NetworkOptions;
+pub use self::null::NullLogger;
+pub use self::only_status::OnlyStatus;
+#[cfg(feature = "otel")]
+pub use self::otel::OtelLogger;
+pub use self::path_filter::PathFilter;
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresLogger;
+pub use self::prometheus::{PrometheusRecorder, PrometheusHandler};
+pub use self::query::QueryLogging;
+pub use self::queue::{Contention, QueueStats, QueueStatsHandler};
+pub use self::rate::RequestRate;
+pub use self::rate_limit::{RateLimitLogger, RateLimitPolicy};
+pub use self::reader::{LogReader, LogRecord};
+pub use self::redaction::RedactionPolicy;
+pub use self::retry::RetryLogger;
+pub use self::ring_buffer::{RingBufferLogger, RingBufferHandler};
+pub use self::rotating::{RotatingFileLogger, FileRotationPolicy};
+pub use self::route_pattern::RoutePattern;
+pub use self::routes::{RouteAggregator, RouteStats};
+pub use self::sampling::{Sampling, TailSampling};
+pub use self::split::{BeforeLogger, AfterLogger};
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteLogger;
+pub use self::stats::{StatsRecorder, StatsHandler};
+pub use self::statsd::StatsdLogger;
+pub use self::status_counts::{StatusCounter, StatusCounts, StatusCountHandler};
+pub use self::stream::StreamLogger;
+pub use self::summary::{Summarized, Summary};
+pub use self::syslog::{SyslogLogger, Facility, Severity};
+pub use self::tcp::{TcpLogger, TcpFormat};
+pub use self::tls::TlsInfo;
+#[cfg(feature = "tracing")]
+pub use self::tracing::TracingLogger;
+pub use self::webhook::{WebhookLogger, WebhookError};
+pub use self::window::SlidingWindow;
+
+#[derive(Debug, Clone)]
+pub struct LogPacket {
+    seq: u64,
+    url: iron::Url,
+    method: Method,
+    ip: net::SocketAddr,
+    status: Option<Status>,
+    start: SystemTime,
+    timing: Duration,
+    response_len: Option<u64>,
+    user_agent: Option<String>,
+    referer: Option<String>,
+    request_id: String,
+    query: Option<String>,
+    truncated: bool,
+    host: Option<String>,
+    content_type: Option<String>,
+    error: Option<String>,
+    request_len: Option<u64>,
+    request_headers: BTreeMap<String, String>,
+    response_headers: BTreeMap<String, String>,
+    scheme: String,
+    http_version: String,
+    tls_version: Option<String>,
+    tls_cipher: Option<String>,
+    overhead: Duration,
+    total: Duration,
+    ttfb: Option<Duration>,
+    tags: BTreeMap<String, String>,
+    fields: BTreeMap<String, String>,
+    route: Option<String>,
+    label: Option<String>,
+    count: u32,
+    sample_rate: f64,
+    log_lag: Duration,
+    // When the packet was queued, until the logging thread takes it
+    enqueued: Option<Instant>,
+}
+
+impl LogPacket {
+    /// The position of the request among those the logger decided to
+    /// log, counting from 0. Packets dropped later, such as by a full
+    /// queue, leave a gap, so gaps in a shipped log reveal lost
+    /// records. Concurrent requests can be written slightly out of
+    /// order.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// The requested URL.
+    pub fn url(&self) -> &iron::Url {
+        &self.url
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The address of the client. It is unspecified, like
+    /// `0.0.0.0:0`, when the transport has no peer address, such as a
+    /// Unix socket; see `peer_addr`.
+    pub fn ip(&self) -> net::SocketAddr {
+        self.ip
+    }
+
+    /// The address of the client, unless the transport had none to
+    /// give. Writers log a placeholder, usually `-`, instead.
+    pub fn peer_addr(&self) -> Option<net::SocketAddr> {
+        Some(self.ip).filter(|addr| !addr.ip().is_unspecified())
+    }
+
+    /// The status of the response, or of the error response when the
+    /// handler failed. It is `None` only when the handler did not set
+    /// one, in which case Iron sends `404 Not Found`.
+    pub fn status(&self) -> Option<Status> {
+        self.status
+    }
+
+    /// When the request started being handled.
+    pub fn start(&self) -> SystemTime {
+        self.start
+    }
+
+    /// How long the wrapped handler took, including any middleware
+    /// it was linked with below this logger.
+    pub fn timing(&self) -> Duration {
+        self.timing
+    }
+
+    /// The size of the response body, when known up front, including
+    /// the body of an error response.
+    pub fn response_len(&self) -> Option<u64> {
+        self.response_len
+    }
+
+    /// The `User-Agent` header sent by the client.
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_ref().map(|ua| &ua[..])
+    }
+
+    /// The `Referer` header sent by the client, naming the page that
+    /// linked to the requested one.
+    pub fn referer(&self) -> Option<&str> {
+        self.referer.as_ref().map(|referer| &referer[..])
+    }
+
+    /// The `X-Request-Id` sent by the client, or the one generated for
+    /// this request.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// The path of the requested URL, without the query.
+    pub fn path(&self) -> &str {
+        self.url.as_ref().path()
+    }
+
+    /// The query of the requested URL, if it had one.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_ref().map(|q| &q[..])
+    }
+
+    /// Whether the URL was cut short for being longer than
+    /// `StatisticLoggerBuilder::max_url_len`, losing the end of its path or
+    /// query.
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The `Host` header sent by the client, including the port if
+    /// one was given. HTTP/1.0 clients may not send one.
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_ref().map(|h| &h[..])
+    }
+
+    /// The `Content-Type` of the response, if the handler set one.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_ref().map(|ct| &ct[..])
+    }
+
+    /// The error returned by the handler, if it failed.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|e| &e[..])
+    }
+
+    /// The size of the request body from its `Content-Length` header.
+    /// Chunked requests and those without the header have no size.
+    pub fn request_len(&self) -> Option<u64> {
+        self.request_len
+    }
+
+    /// The request headers on the logger's allow-list that the client
+    /// sent, keyed by the names given to `log_request_headers`.
+    pub fn request_headers(&self) -> &BTreeMap<String, String> {
+        &self.request_headers
+    }
+
+    /// The response headers on the logger's allow-list that the
+    /// handler set, keyed by the names given to `log_response_headers`.
+    pub fn response_headers(&self) -> &BTreeMap<String, String> {
+        &self.response_headers
+    }
+
+    /// The scheme of the request, `http` or `https`. Behind a proxy
+    /// this is the scheme between the proxy and the server, unless
+    /// `trust_forwarded_proto` is enabled.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// The protocol version of the request, like `HTTP/1.1`.
+    pub fn http_version(&self) -> &str {
+        &self.http_version
+    }
+
+    /// The TLS protocol version negotiated for the connection, when
+    /// the server set a `TlsInfo`. Plaintext requests have none.
+    pub fn tls_version(&self) -> Option<&str> {
+        self.tls_version.as_ref().map(|version| &version[..])
+    }
+
+    /// The TLS cipher suite negotiated for the connection, when the
+    /// server set a `TlsInfo`.
+    pub fn tls_cipher(&self) -> Option<&str> {
+        self.tls_cipher.as_ref().map(|cipher| &cipher[..])
+    }
+
+    /// How long the logging middleware itself took, outside the
+    /// wrapped handler, up to building this packet.
+    pub fn overhead(&self) -> Duration {
+        self.overhead
+    }
+
+    /// How long the request spent in the logging middleware, from
+    /// entering it to building this packet: the `timing` of the
+    /// wrapped handler plus the `overhead`. Middleware linked above
+    /// the logger is not included. For a streamed body, which is
+    /// only written after the middleware returns, it lasts until the
+    /// body has been written.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// The time to first byte: how long after entering the logging
+    /// middleware the first byte of the body was written. Bodies
+    /// known up front count as sent at once, so this is the `total`;
+    /// for streamed bodies it is `None` when nothing was written.
+    pub fn ttfb(&self) -> Option<Duration> {
+        self.ttfb
+    }
+
+    /// The static tags of the logger, such as the hostname, which tell
+    /// apart the instances of a fleet.
+    pub fn tags(&self) -> &BTreeMap<String, String> {
+        &self.tags
+    }
+
+    /// The fields the handler added with `LogFields`.
+    pub fn fields(&self) -> &BTreeMap<String, String> {
+        &self.fields
+    }
+
+    /// The route pattern set with `RoutePattern`, if any.
+    pub fn route(&self) -> Option<&str> {
+        self.route.as_ref().map(|route| &route[..])
+    }
+
+    /// The label computed by `StatisticLoggerBuilder::label`, if any.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_ref().map(|label| &label[..])
+    }
+
+    /// The number of requests the packet stands for, which is more
+    /// than 1 only for requests coalesced by
+    /// `StatisticLoggerBuilder::coalesce`.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The probability that the request was logged, below 1 only when
+    /// it was sampled with `Sampling` or `TailSampling`.
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// How many requests the packet stands for once sampling is
+    /// accounted for: its `count` divided by its `sample_rate`.
+    /// Aggregating writers add these up to estimate the true traffic.
+    pub fn estimated_count(&self) -> f64 {
+        self.count as f64 / self.sample_rate
+    }
+
+    /// How long the packet waited in the queue before the logging
+    /// thread took it. A lag that keeps growing means the writer
+    /// cannot keep up. It is zero for packets written without a queue.
+    pub fn log_lag(&self) -> Duration {
+        self.log_lag
+    }
+
+    /// Replaces the URL, and with it the path and query.
+    pub fn set_url(&mut self, url: iron::Url) {
+        self.query = url.query().map(String::from);
+        self.url = url;
+    }
+
+    pub fn set_method(&mut self, method: Method) {
+        self.method = method;
+    }
+
+    pub fn set_status(&mut self, status: Option<Status>) {
+        self.status = status;
+    }
+
+    pub fn set_user_agent(&mut self, user_agent: Option<String>) {
+        self.user_agent = user_agent;
+    }
+
+    pub fn set_referer(&mut self, referer: Option<String>) {
+        self.referer = referer;
+    }
+
+    pub fn set_host(&mut self, host: Option<String>) {
+        self.host = host;
+    }
+
+    pub fn set_error(&mut self, error: Option<String>) {
+        self.error = error;
+    }
+
+    /// The recorded request headers, to add or remove some.
+    pub fn request_headers_mut(&mut self) -> &mut BTreeMap<String, String> {
+        &mut self.request_headers
+    }
+
+    /// The recorded response headers, to add or remove some.
+    pub fn response_headers_mut(&mut self) -> &mut BTreeMap<String, String> {
+        &mut self.response_headers
+    }
+}
+
+#[cfg(test)]
+impl LogPacket {
+    pub fn stub() -> LogPacket {
+        LogPacket {
+            seq: 0,
+            url: iron::Url::parse("http://127.0.0.1/").unwrap(),
+            method: Method::Get,
+            ip: "127.0.0.1:3000".parse().unwrap(),
+            status: Some(::iron::status::Ok),
+            start: ::std::time::UNIX_EPOCH + Duration::new(1_500_000_000, 0),
+            timing: Duration::new(0, 1_500_000),
+            response_len: Some(13),
+            user_agent: Some("iron-test".into()),
+            referer: None,
+            request_id: "0b8cc2e6-2ba7-4b5c-9e3e-4f26e3a1a2de".into(),
+            query: None,
+            truncated: false,
+            host: Some("127.0.0.1".into()),
+            content_type: Some("text/plain".into()),
+            error: None,
+            request_len: Some(0),
+            request_headers: BTreeMap::new(),
+            response_headers: BTreeMap::new(),
+            scheme: "http".into(),
+            http_version: "HTTP/1.1".into(),
+            tls_version: None,
+            tls_cipher: None,
+            overhead: Duration::new(0, 2_000),
+            total: Duration::new(0, 1_502_000),
+            ttfb: Some(Duration::new(0, 1_502_000)),
+            tags: BTreeMap::new(),
+            fields: BTreeMap::new(),
+            route: None,
+            label: None,
+            count: 1,
+            sample_rate: 1.0,
+            log_lag: Duration::default(),
+            enqueued: None,
+        }
+    }
+}
+
+fn format_duration(duration: &Duration) -> String {
+    format!("{}.{:09}", duration.as_secs(), duration.subsec_nanos())
+}
+
+/// The client's address, or `-` when there is none.
+fn format_ip(packet: &LogPacket) -> String {
+    packet.peer_addr().map_or_else(|| "-".into(), |addr| addr.to_string())
+}
+
+/// The time elapsed since the Unix epoch. A clock set before 1970 is
+/// treated as the epoch itself, so logging never fails on it.
+fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(::std::time::UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with microseconds.
+fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = since_epoch(time);
+    let tm = time::at_utc(time::Timespec::new(since_epoch.as_secs() as i64, 0));
+    format!("{}.{:06}Z",
+            time::strftime("%Y-%m-%dT%H:%M:%S", &tm).expect("Unable to format timestamp"),
+            since_epoch.subsec_micros())
+}
+
+impl Serialize for LogPacket {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Encoded(self, &Encoding::default()).serialize(serializer)
+    }
+}
+
+const DEFAULT_CAPACITY: usize = 1024;
+const DEFAULT_THREAD_NAME: &str = "playground-logger";
+const DEFAULT_CONTENTION_THRESHOLD: Duration = Duration::from_millis(1);
+
+/// What to do with a packet when the logging queue is full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wait for the logging thread to make room. Request handling
+    /// slows to the speed of the writer.
+    #[default]
+    Block,
+    /// Discard the packet that could not be queued.
+    DropNewest,
+    /// Discard the oldest queued packet to make room.
+    DropOldest,
+}
+
+/// When the logging thread flushes its writer, trading durability for
+/// throughput: the fewer flushes, the more packets are lost should
+/// the process crash. Whatever the strategy, the writer is flushed
+/// when the logger shuts down.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FlushStrategy {
+    /// Flush after every packet.
+    #[default]
+    EveryPacket,
+    /// Flush after every `n` packets.
+    EveryN(usize),
+    /// Flush once the oldest unflushed packet has waited this long.
+    Interval(Duration),
+    /// Only flush when the logger shuts down.
+    OnDropOnly,
+    /// Flush once no packet has come in for `quiet`, or after
+    /// `max_batch` packets. A burst is written in one batch, while a
+    /// lone packet is flushed as soon as the queue goes quiet.
+    WhenQuiet { quiet: Duration, max_batch: usize },
+}
+
+/// Whether file writers also have the OS write their data to disk,
+/// for logs that must survive a crash of the machine and not just of
+/// the process. Flushing only hands data to the OS. Each sync waits
+/// for the disk, which can take milliseconds, so syncing every write
+/// can hold the logging thread to a few hundred packets per second.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SyncPolicy {
+    /// Leave it to the OS.
+    #[default]
+    Never,
+    /// Sync whenever the writer is flushed, as the `FlushStrategy`
+    /// says.
+    EveryBatch,
+    /// Sync after every packet.
+    EveryWrite,
+}
+
+/// The number of packets discarded because the logging queue was
+/// full, the logging thread has stopped, or a drain gave up on them.
+/// Remains readable after the logger has been installed.
+#[derive(Debug, Clone)]
+pub struct DroppedPackets(Arc<AtomicUsize>);
+
+impl DroppedPackets {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Drains a `StatisticLogger` from elsewhere in the application, such
+/// as a `SIGTERM` handler, even after it has been installed.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<Lifecycle>);
+
+impl ShutdownHandle {
+    /// Stops queueing packets, counting those of later requests as
+    /// dropped, then waits up to `timeout` for the logging threads to
+    /// write and flush the queued ones. Returns whether they finished
+    /// in time.
+    ///
+    /// If not, such as when the writer is stuck on a dead collector,
+    /// the packets still queued are abandoned: they are counted as
+    /// dropped and by `abandoned`, and a warning says how many there
+    /// were. The threads are no longer waited for, even when the
+    /// logger is dropped, so a stuck writer cannot hold up shutdown.
+    pub fn drain(&self, timeout: Duration) -> bool {
+        self.0.shutdown(Some(timeout))
+    }
+
+    /// The packets abandoned by a `drain` that timed out.
+    pub fn abandoned(&self) -> usize {
+        self.0.abandoned.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads a `StatisticLogger`'s counters and drains it after `around`
+/// has consumed it. Take one with `StatisticLogger::handle` before
+/// installing the logger; clones share the same state.
+#[derive(Clone)]
+pub struct LoggerHandle {
+    capacity: usize,
+    dropped: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+    lifecycle: Arc<Lifecycle>,
+    in_flight: InFlight,
+    clock: Arc<Clock>,
+    errors: Errors,
+    contention: Arc<ContentionCounter>,
+}
+
+impl LoggerHandle {
+    /// See `DroppedPackets::count`.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The packets waiting to be written.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn queue_stats(&self) -> QueueStats {
+        QueueStats {
+            depth: self.queued(),
+            capacity: self.capacity,
+            dropped: self.dropped(),
+        }
+    }
+
+    /// A handler serving the `queue_stats` as JSON.
+    pub fn queue_stats_handler(&self) -> QueueStatsHandler {
+        queue::handler(self.clone())
+    }
+
+    /// How long requests have waited to queue their packets.
+    pub fn contention(&self) -> Contention {
+        self.contention.snapshot()
+    }
+
+    /// The requests being served by the wrapped handler.
+    pub fn in_flight(&self) -> InFlightCounts {
+        self.in_flight.snapshot()
+    }
+
+    /// See `ShutdownHandle::drain`.
+    pub fn drain(&self, timeout: Duration) -> bool {
+        self.lifecycle.shutdown(Some(timeout))
+    }
+
+    /// See `ShutdownHandle::abandoned`.
+    pub fn abandoned(&self) -> usize {
+        self.lifecycle.abandoned.load(Ordering::Relaxed)
+    }
+
+    /// The writer's most recent failure to log a packet or to flush,
+    /// if it ever failed.
+    pub fn last_error(&self) -> Option<LastError> {
+        self.errors.last()
+    }
+
+    /// A handler for health checks, such as `/healthz`, that fails
+    /// while the writer's last error is less than `window` old.
+    pub fn health_handler(&self, window: Duration) -> HealthHandler {
+        health::handler(self.clone(), window)
+    }
+
+    /// Queues a copy of `packet` to be written again, such as one kept
+    /// by a `RingBufferLogger`, without waiting for room in the queue.
+    /// Unlike requests, packets that cannot be queued are not counted
+    /// as dropped; the error says why instead.
+    pub fn try_log(&self, packet: &LogPacket) -> Result<(), LoggingError> {
+        let tx = match self.lifecycle.sender() {
+            Some(tx) => tx,
+            None => return Err(LoggingError::ChannelClosed),
+        };
+
+        let mut packet = packet.clone();
+        packet.enqueued = Some(self.clock.now_instant());
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        tx.try_send(packet).map_err(|e| {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            match e {
+                TrySendError::Full(_) => LoggingError::QueueFull,
+                TrySendError::Disconnected(_) => LoggingError::ChannelClosed,
+            }
+        })
+    }
+}
+
+/// Why a `StatisticLogger` could not be started or take a packet.
+#[derive(Debug)]
+pub enum LoggingError {
+    /// The logger has been drained, or its logging threads have all
+    /// stopped.
+    ChannelClosed,
+    /// The queue was full.
+    QueueFull,
+    /// A writer failed to record a packet. Writers behind a queue
+    /// report their failures to the error handler instead, so this
+    /// is for handlers and wrappers passing them on.
+    Writer(BoxedError),
+    /// A logging thread could not be spawned.
+    Spawn(io::Error),
+}
+
+impl fmt::Display for LoggingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoggingError::ChannelClosed => write!(f, "The logger has shut down"),
+            LoggingError::QueueFull => write!(f, "The logging queue is full"),
+            LoggingError::Writer(ref e) => write!(f, "Unable to log request: {}", e),
+            LoggingError::Spawn(ref e) => write!(f, "Unable to spawn logger thread: {}", e),
+        }
+    }
+}
+
+impl error::Error for LoggingError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            LoggingError::Writer(ref e) => Some(e),
+            LoggingError::Spawn(ref e) => Some(e),
+            LoggingError::ChannelClosed | LoggingError::QueueFull => None,
+        }
+    }
+}
+
+impl From<BoxedError> for LoggingError {
+    fn from(error: BoxedError) -> LoggingError {
+        LoggingError::Writer(error)
+    }
+}
+
+/// Groups statuses for aggregation, such as `2xx` or `4xx`.
+fn status_class(status: Option<Status>) -> &'static str {
+    match status.map(|s| s.to_u16() / 100) {
+        Some(1) => "1xx",
+        Some(2) => "2xx",
+        Some(3) => "3xx",
+        Some(4) => "4xx",
+        Some(5) => "5xx",
+        _ => "unknown",
+    }
+}
+
+/// Locks shared writer state. Statistics remain usable even if a
+/// thread panicked while updating them.
+fn lock<'a, T>(mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Serves `body` as JSON, for the handlers reporting statistics.
+fn json_response<T>(body: &T) -> IronResult<Response>
+    where T: Serialize + ?Sized
+{
+    match serde_json::to_vec(body) {
+        Ok(body) => Ok(Response::with((Status::Ok, Header(ContentType::json()), body))),
+        Err(e) => Err(IronError::new(e, Status::InternalServerError)),
+    }
+}
+
+const DEFAULT_DELIMITER: u8 = b',';
+
+/// When CSV fields are quoted. By default, only fields containing the
+/// delimiter, a quote or a line break are, which any CSV parser reads
+/// back unchanged. Quotes within a field are doubled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CsvQuoting {
+    always: bool,
+    quote: u8,
+}
+
+impl CsvQuoting {
+    /// Quotes fields with `"` only when they need it.
+    pub fn new() -> CsvQuoting {
+        CsvQuoting {
+            always: false,
+            quote: b'"',
+        }
+    }
+
+    /// Quotes every field, for consumers that split lines naively.
+    pub fn always(self) -> Self {
+        CsvQuoting { always: true, ..self }
+    }
+
+    /// Quotes fields with `quote` instead, such as `b'\''`.
+    pub fn quote(self, quote: u8) -> Self {
+        CsvQuoting { quote: quote, ..self }
+    }
+}
+
+impl Default for CsvQuoting {
+    fn default() -> CsvQuoting {
+        CsvQuoting::new()
+    }
+}
+
+/// Creates a CSV writer. Header rows are managed separately so that
+/// appending to an existing file does not repeat them.
+fn csv_writer<W: Write>(stream: W, delimiter: u8) -> csv::Writer<W> {
+    quoted_csv_writer(stream, delimiter, CsvQuoting::default())
+}
+
+fn quoted_csv_writer<W: Write>(stream: W, delimiter: u8, quoting: CsvQuoting) -> csv::Writer<W> {
+    let style = if quoting.always { csv::QuoteStyle::Always } else { csv::QuoteStyle::Necessary };
+    csv::WriterBuilder::new()
+        .has_headers(false)
+        .delimiter(delimiter)
+        .quote_style(style)
+        .quote(quoting.quote)
+        .from_writer(stream)
+}
+
+fn csv_header(delimiter: u8, encoding: &Encoding) -> Vec<u8> {
+    let mut header = csv_writer(Vec::new(), delimiter);
+    header.write_record(encoding.header()).expect("Unable to encode CSV header");
+    header.into_inner().expect("Unable to encode CSV header")
+}
+
+/// Opens a CSV file for appending, along with whether it is empty and
+/// so still needs a header row. Headers are written with the first
+/// row, once the writer's encoding is known.
+fn open_csv(path: &Path) -> io::Result<(File, bool)> {
+    let file = try!(OpenOptions::new().append(true).create(true).open(path));
+    let is_empty = try!(file.metadata()).len() == 0;
+    Ok((file, is_empty))
+}
+
+/// A UDP socket connected to the first address `addr` resolves to.
+fn connect_udp<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
+    let addr = try!(try!(addr.to_socket_addrs()).next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "No address to send statistics to")
+    }));
+
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => "0.0.0.0:0".parse().expect("Invalid local address"),
+        SocketAddr::V6(_) => "[::]:0".parse().expect("Invalid local address"),
+    };
+
+    let socket = try!(UdpSocket::bind(local));
+    try!(socket.connect(addr));
+    Ok(socket)
+}
+
+/// Encodes a single packet as a CSV row, including the line terminator.
+fn encode_csv(packet: &LogPacket, encoding: &Encoding) -> csv::Result<Vec<u8>> {
+    let mut row = csv_writer(Vec::new(), DEFAULT_DELIMITER);
+    try!(row.serialize(Encoded(packet, encoding)));
+    row.into_inner().map_err(|e| e.into_error().into())
+}
+
+/// Decides which requests are logged.
+type RequestFilter = Box<Fn(&Request) -> bool + Send + Sync>;
+
+/// Computes the label of a request.
+type RequestLabel = Box<Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Logs basic request / response statistics
+pub struct StatisticLogger {
+    capacity: usize,
+    worker: Worker,
+    capture: Capture,
+    filter: Option<RequestFilter>,
+    label: Option<RequestLabel>,
+    sampling: Option<Sampling>,
+    clock: Arc<Clock>,
+    timing: TimingSource,
+    catch_panics: bool,
+    in_flight: InFlight,
+    // Requests are passed straight through when nothing would be logged
+    enabled: bool,
+    discards: bool,
+}
+
+/// A target for statistics to be written to
+pub trait LogWriter {
+    type Error: error::Error;
+
+    fn log(&mut self, log: &LogPacket) -> Result<(), Self::Error>;
+
+    /// Ensures previously logged packets have reached their
+    /// destination. Called by the logging thread after each batch.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Records a final summary once logging stops. Only called for
+    /// writers wrapped in `Summarized`; ignored by default.
+    fn finalize(&mut self, _summary: &Summary) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Whether the writer throws every packet away, like
+    /// `NullLogger`, so that a `StatisticLogger` need not build them
+    /// at all. Wrappers that only pass packets on should ask the
+    /// writers they wrap.
+    fn discards_everything(&self) -> bool {
+        false
+    }
+}
+
+const DEFAULT_FILE_BUFFER: usize = 64 * 1024;
+
+/// Records statistics to a file, in the format its extension calls
+/// for: CSV for `.csv` files, JSON lines for `.jsonl` and the Combined
+/// Log Format for `.log`, as `FileFormat` explains.
+///
+/// Rows are buffered in memory and written to the file when the
+/// buffer fills up, when the logging thread flushes after a batch,
+/// and when the logger is dropped. Any `Formatter` given to
+/// `with_formatter` is used regardless of the extension.
+pub struct FileLogger<F: Formatter = FileFormat> {
+    file: BufWriter<File>,
+    formatter: F,
+    needs_header: bool,
+    sync: SyncPolicy,
+}
+
+impl FileLogger {
+    /// Appends to the file, writing a header row first when the file
+    /// is new or empty and its format has one. Up to 64KiB of rows are
+    /// buffered.
+    pub fn new<P>(path: P) -> io::Result<FileLogger>
+        where P: AsRef<Path>
+    {
+        let format = FileFormat::from_path(path.as_ref());
+        FileLogger::open(path.as_ref(), true, DEFAULT_FILE_BUFFER, format)
+    }
+
+    /// Appends to the file without ever writing a header row.
+    pub fn headerless<P>(path: P) -> io::Result<FileLogger>
+        where P: AsRef<Path>
+    {
+        let format = FileFormat::from_path(path.as_ref());
+        FileLogger::open(path.as_ref(), false, DEFAULT_FILE_BUFFER, format)
+    }
+
+    /// Like `new`, but buffers up to `capacity` bytes of rows.
+    pub fn with_buffer_capacity<P>(path: P, capacity: usize) -> io::Result<FileLogger>
+        where P: AsRef<Path>
+    {
+        let format = FileFormat::from_path(path.as_ref());
+        FileLogger::open(path.as_ref(), true, capacity, format)
+    }
+
+    /// Writes CSV whatever the extension, separating fields with
+    /// `delimiter` instead of a comma, such as `b'\t'` for
+    /// tab-separated values.
+    pub fn with_delimiter<P>(path: P, delimiter: u8) -> io::Result<FileLogger>
+        where P: AsRef<Path>
+    {
+        let format = FileFormat::Csv(CsvFormatter::new().delimiter(delimiter));
+        FileLogger::open(path.as_ref(), true, DEFAULT_FILE_BUFFER, format)
+    }
+
+    /// Writes CSV whatever the extension, quoting fields as `quoting`
+    /// says.
+    pub fn with_quoting<P>(path: P, quoting: CsvQuoting) -> io::Result<FileLogger>
+        where P: AsRef<Path>
+    {
+        let format = FileFormat::Csv(CsvFormatter::new().quoting(quoting));
+        FileLogger::open(path.as_ref(), true, DEFAULT_FILE_BUFFER, format)
+    }
+
+    /// Chooses the columns of CSV files; other formats ignore it.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        if let FileFormat::Csv(ref mut csv) = self.formatter {
+            *csv = csv.clone().encoding(encoding);
+        }
+        self
+    }
+}
+
+impl<F: Formatter> FileLogger<F> {
+    /// Appends packets rendered by `formatter`, such as a
+    /// `JsonFormatter`, starting new or empty files with its header.
+    pub fn with_formatter<P>(path: P, formatter: F) -> io::Result<FileLogger<F>>
+        where P: AsRef<Path>
+    {
+        FileLogger::open(path.as_ref(), true, DEFAULT_FILE_BUFFER, formatter)
+    }
+
+    /// Syncs the file to disk as `policy` says; see `SyncPolicy`.
+    pub fn sync(mut self, policy: SyncPolicy) -> Self {
+        self.sync = policy;
+        self
+    }
+
+    fn open(path: &Path, header: bool, capacity: usize, formatter: F) -> io::Result<FileLogger<F>> {
+        let (file, is_empty) = try!(open_csv(path));
+        Ok(FileLogger {
+            file: BufWriter::with_capacity(capacity, file),
+            formatter: formatter,
+            needs_header: header && is_empty,
+            sync: SyncPolicy::default(),
+        })
+    }
+
+    fn sync_data(&mut self) -> io::Result<()> {
+        try!(self.file.flush());
+        self.file.get_ref().sync_data()
+    }
+}
+
+impl<F: Formatter> Drop for FileLogger<F> {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.flush() {
+            error!("Unable to write request log: {}", e);
+        }
+    }
+}
+
+impl<F: Formatter> LogWriter for FileLogger<F> {
+    type Error = csv::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if self.needs_header {
+            try!(self.formatter.header(&mut self.file));
+            self.needs_header = false;
+        }
+        try!(self.formatter.format(packet, &mut self.file));
+        if self.sync == SyncPolicy::EveryWrite {
+            try!(self.sync_data());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        match self.sync {
+            SyncPolicy::EveryBatch => try!(self.sync_data()),
+            _ => try!(self.file.flush()),
+        }
+        Ok(())
+    }
+}
+
+impl StatisticLogger {
+    /// Queues up to 1024 packets; see `with_capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logging thread cannot be spawned; see `try_new`.
+    pub fn new<L>(logger: L) -> StatisticLogger
+        where L: LogWriter + Send + 'static
+    {
+        StatisticLoggerBuilder::new().build(logger)
+    }
+
+    /// Configures every option in one place; see
+    /// `StatisticLoggerBuilder`.
+    pub fn builder() -> StatisticLoggerBuilder {
+        StatisticLoggerBuilder::new()
+    }
+
+    /// Like `new`, but returns an error if the logging thread cannot
+    /// be spawned.
+    pub fn try_new<L>(logger: L) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static
+    {
+        StatisticLoggerBuilder::new().try_build(logger)
+    }
+
+    /// Like `with_capacity`, but returns an error if the logging
+    /// thread cannot be spawned.
+    pub fn try_with_capacity<L>(logger: L, capacity: usize) -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static
+    {
+        StatisticLoggerBuilder::new().capacity(capacity).try_build(logger)
+    }
+
+    /// Queues at most `capacity` packets; see
+    /// `StatisticLoggerBuilder::capacity`. Packets that cannot be
+    /// written are reported with `error!`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logging thread cannot be spawned; see
+    /// `try_with_capacity`.
+    pub fn with_capacity<L>(logger: L, capacity: usize) -> StatisticLogger
+        where L: LogWriter + Send + 'static
+    {
+        StatisticLogger::try_with_capacity(logger, capacity).expect("Unable to spawn logger thread")
+    }
+
+    /// Calls `on_error` for every packet the writer fails to record;
+    /// see `StatisticLoggerBuilder::on_error`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logging thread cannot be spawned; see
+    /// `try_with_error_handler`.
+    pub fn with_error_handler<L, F>(logger: L, capacity: usize, on_error: F) -> StatisticLogger
+        where L: LogWriter + Send + 'static,
+              F: FnMut(&LogPacket, &L::Error) + Send + 'static,
+    {
+        StatisticLogger::try_with_error_handler(logger, capacity, on_error).expect("Unable to spawn logger thread")
+    }
+
+    /// Like `with_error_handler`, but returns an error if the logging
+    /// thread cannot be spawned.
+    pub fn try_with_error_handler<L, F>(logger: L, capacity: usize, on_error: F)
+                                        -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static,
+              F: FnMut(&LogPacket, &L::Error) + Send + 'static,
+    {
+        StatisticLoggerBuilder::new().capacity(capacity).on_error(on_error).try_build(logger)
+    }
+
+    /// Flushes the writer after every `batch_size` packets, or once
+    /// the oldest unflushed packet has waited for `interval`; see
+    /// `StatisticLoggerBuilder::batch`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the logging thread cannot be spawned; see
+    /// `try_with_batching`.
+    pub fn with_batching<L>(logger: L, capacity: usize, batch_size: usize, interval: Duration) -> StatisticLogger
+        where L: LogWriter + Send + 'static
+    {
+        StatisticLogger::try_with_batching(logger, capacity, batch_size, interval)
+            .expect("Unable to spawn logger thread")
+    }
+
+    /// Like `with_batching`, but returns an error if the logging
+    /// thread cannot be spawned.
+    pub fn try_with_batching<L>(logger: L, capacity: usize, batch_size: usize, interval: Duration)
+                                -> Result<StatisticLogger, LoggingError>
+        where L: LogWriter + Send + 'static
+    {
+        StatisticLoggerBuilder::new()
+            .capacity(capacity)
+            .batch(batch_size, interval)
+            .try_build(logger)
+    }
+
+    fn spawn<L, F>(loggers: Vec<L>, capacity: usize, on_error: F, batch: Batch, threads: Threads,
+                   clock: Arc<Clock>) -> io::Result<StatisticLogger>
+        where L: LogWriter + Send + 'static,
+              F: FnMut(&LogPacket, &L::Error) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let worker_rx = Arc::new(Mutex::new(rx));
+        let rx = Arc::downgrade(&worker_rx);
+        let queued = Arc::new(AtomicUsize::new(0));
+        let on_error = Arc::new(Mutex::new(on_error));
+        let errors = Errors::default();
+        let discards = loggers.iter().all(LogWriter::discards_everything);
+
+        // Each thread holds the queue's lock while it waits, so idle
+        // threads in a pool wake up now and then to let the others
+        // flush on time.
+        let pooled = loggers.len() > 1;
+        let idle = if pooled {
+            Some(batch.interval.unwrap_or_default().max(Duration::from_millis(10)))
+        } else {
+            None
+        };
+
+        let mut handles = Vec::with_capacity(loggers.len());
+        for (index, logger) in loggers.into_iter().enumerate() {
+            let name = if pooled {
+                format!("{}-{}", threads.name, index)
+            } else {
+                threads.name.clone()
+            };
+            let mut thread = thread::Builder::new().name(name);
+            if let Some(size) = threads.stack_size {
+                thread = thread.stack_size(size);
+            }
+
+            let worker_rx = worker_rx.clone();
+            let worker_queued = queued.clone();
+            let on_error = on_error.clone();
+            let clock = clock.clone();
+            let errors = errors.clone();
+            handles.push(try!(thread.spawn(move || {
+                drain(logger, &worker_rx, &worker_queued, batch, idle, &*clock, |packet, e| {
+                    errors.record(e, &*clock);
+                    match packet {
+                        Some(packet) => {
+                            let mut on_error = on_error.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                            (*on_error)(packet, e)
+                        }
+                        None => error!("Unable to flush request log: {}", e),
+                    }
+                })
+            })));
+        }
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        Ok(StatisticLogger {
+            capacity: capacity,
+            worker: Worker {
+                lifecycle: Arc::new(Lifecycle {
+                    tx: RwLock::new(Some(tx)),
+                    threads: Mutex::new(handles),
+                    rx: rx.clone(),
+                    queued: queued.clone(),
+                    dropped: dropped.clone(),
+                    abandoned: AtomicUsize::new(0),
+                    detached: AtomicBool::new(false),
+                }),
+                rx: rx,
+                policy: OverflowPolicy::default(),
+                dropped: dropped,
+                capacity: capacity,
+                queued: queued,
+                clock: clock.clone(),
+                errors: errors,
+                contention: Arc::default(),
+                contention_threshold: DEFAULT_CONTENTION_THRESHOLD,
+            },
+            capture: Capture::default(),
+            filter: None,
+            label: None,
+            sampling: None,
+            clock: clock,
+            timing: TimingSource::default(),
+            catch_panics: false,
+            in_flight: InFlight::default(),
+            enabled: true,
+            discards: discards,
+        })
+    }
+
+    /// The maximum number of packets waiting to be written.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Tracks packets discarded by the overflow policy.
+    pub fn dropped(&self) -> DroppedPackets {
+        DroppedPackets(self.worker.dropped.clone())
+    }
+
+    /// Drains the logger on demand; see `ShutdownHandle::drain`.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(self.worker.lifecycle.clone())
+    }
+
+    /// Counts the requests being served by the wrapped handler.
+    pub fn in_flight(&self) -> InFlight {
+        self.in_flight.clone()
+    }
+
+    /// The depth of the queue and the packets dropped so far. Use a
+    /// `handle` to follow them once the logger has been installed.
+    pub fn queue_stats(&self) -> QueueStats {
+        self.handle().queue_stats()
+    }
+
+    /// Controls the logger once it has been installed.
+    pub fn handle(&self) -> LoggerHandle {
+        LoggerHandle {
+            capacity: self.capacity,
+            dropped: self.worker.dropped.clone(),
+            queued: self.worker.queued.clone(),
+            lifecycle: self.worker.lifecycle.clone(),
+            in_flight: self.in_flight.clone(),
+            clock: self.worker.clock.clone(),
+            errors: self.worker.errors.clone(),
+            contention: self.worker.contention.clone(),
+        }
+    }
+
+    /// Splits the logger into middleware for `Chain::link_before` and
+    /// `Chain::link_after`, for chains that cannot be wrapped with
+    /// `around`. The timing covers everything linked between the
+    /// two. Panics are never caught.
+    pub fn split(self) -> (BeforeLogger, AfterLogger) {
+        split::split(self.into_recorder())
+    }
+
+    fn into_recorder(self) -> Recorder {
+        Recorder {
+            sink: Arc::new(self.worker),
+            capture: self.capture,
+            filter: self.filter,
+            label: self.label,
+            sampling: self.sampling,
+            clock: self.clock,
+            timing: self.timing,
+            seq: AtomicU64::new(0),
+        }
+    }
+}
+
+impl AroundMiddleware for StatisticLogger {
+    fn around(self, handler: Box<Handler>) -> Box<Handler> {
+        let catch_panics = self.catch_panics;
+        let in_flight = self.in_flight.clone();
+        let disabled = !self.enabled || self.discards;
+        Box::new(LogHandler {
+            handler: handler,
+            recorder: self.into_recorder(),
+            catch_panics: catch_panics,
+            in_flight: in_flight,
+            disabled: disabled,
+        })
+    }
+}
+
+/// Owns the logging threads. Shutting down waits for every queued
+/// packet to be written.
+struct Worker {
+    lifecycle: Arc<Lifecycle>,
+    // Only the logging threads keep the receiver alive, so sending
+    // fails instead of blocking forever should they all die.
+    rx: Weak<Mutex<Receiver<LogPacket>>>,
+    policy: OverflowPolicy,
+    dropped: Arc<AtomicUsize>,
+    capacity: usize,
+    // Packets sent but not yet received by the logging thread
+    queued: Arc<AtomicUsize>,
+    // The logging threads' clock, to time how long packets are queued
+    clock: Arc<Clock>,
+    errors: Errors,
+    contention: Arc<ContentionCounter>,
+    contention_threshold: Duration,
+}
+
+/// When the logging thread flushes its writer: after `size` packets,
+/// or once the oldest unflushed one has waited for `interval`. When
+/// `quiet`, the wait starts over with every packet instead.
+#[derive(Debug, Copy, Clone)]
+struct Batch {
+    size: usize,
+    interval: Option<Duration>,
+    quiet: bool,
+}
+
+impl Default for Batch {
+    fn default() -> Batch {
+        Batch::from(FlushStrategy::default())
+    }
+}
+
+impl From<FlushStrategy> for Batch {
+    fn from(strategy: FlushStrategy) -> Batch {
+        let (size, interval, quiet) = match strategy {
+            FlushStrategy::EveryPacket => (1, None, false),
+            FlushStrategy::EveryN(n) => (n.max(1), None, false),
+            FlushStrategy::Interval(interval) => (usize::MAX, Some(interval), false),
+            FlushStrategy::OnDropOnly => (usize::MAX, None, false),
+            FlushStrategy::WhenQuiet { quiet, max_batch } => (max_batch.max(1), Some(quiet), true),
+        };
+        Batch {
+            size: size,
+            interval: interval,
+            quiet: quiet,
+        }
+    }
+}
+
+/// How the logging threads are spawned.
+struct Threads {
+    name: String,
+    stack_size: Option<usize>,
+}
+
+impl Default for Threads {
+    fn default() -> Threads {
+        Threads {
+            name: DEFAULT_THREAD_NAME.into(),
+            stack_size: None,
+        }
+    }
+}
+
+/// Writes packets from the queue until every sender is gone. Without
+/// an `idle` timeout, the thread sleeps until the next packet while
+/// nothing is waiting to be flushed. Errors are given to `on_error`
+/// with the packet that failed, or without one if flushing failed.
+fn drain<L, F>(mut logger: L, rx: &Mutex<Receiver<LogPacket>>, queued: &AtomicUsize, batch: Batch,
+               idle: Option<Duration>, clock: &Clock, mut on_error: F)
+    where L: LogWriter,
+          F: FnMut(Option<&LogPacket>, &L::Error),
+{
+    let mut unflushed = 0;
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let wait = match deadline {
+            Some(deadline) if unflushed > 0 => Some(deadline.saturating_duration_since(clock.now_instant())),
+            _ => idle,
+        };
+
+        match next_packet(rx, wait) {
+            Ok(mut packet) => {
+                queued.fetch_sub(1, Ordering::Relaxed);
+                if let Some(enqueued) = packet.enqueued.take() {
+                    packet.log_lag = clock.now_instant().saturating_duration_since(enqueued);
+                }
+                if let Err(e) = logger.log(&packet) {
+                    on_error(Some(&packet), &e);
+                }
+                if unflushed == 0 || batch.quiet {
+                    deadline = batch.interval.map(|interval| clock.now_instant() + interval);
+                }
+                unflushed += 1;
+                // A steady stream of packets never times out, so the
+                // deadline is checked here as well
+                if unflushed < batch.size && !is_due(deadline, clock) {
+                    continue;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) if unflushed == 0 || !is_due(deadline, clock) => continue,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        flush(&mut logger, &mut on_error);
+        unflushed = 0;
+    }
+
+    if unflushed > 0 {
+        flush(&mut logger, &mut on_error);
+    }
+}
+
+fn is_due(deadline: Option<Instant>, clock: &Clock) -> bool {
+    deadline.is_some_and(|deadline| clock.now_instant() >= deadline)
+}
+
+fn next_packet(rx: &Mutex<Receiver<LogPacket>>, wait: Option<Duration>) -> Result<LogPacket, RecvTimeoutError> {
+    let rx = lock(rx);
+    match wait {
+        Some(wait) => rx.recv_timeout(wait),
+        None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    }
+}
+
+fn flush<L, F>(logger: &mut L, on_error: &mut F)
+    where L: LogWriter,
+          F: FnMut(Option<&LogPacket>, &L::Error),
+{
+    if let Err(e) = logger.flush() {
+        on_error(None, &e);
+    }
+}
+
+/// The sending half of the queue and the threads receiving from it,
+/// shared with any `ShutdownHandle`s.
+struct Lifecycle {
+    // Only ever cloned or taken, which a panic cannot leave half done,
+    // so the locks here are used even when poisoned. Requests only
+    // read the sender, so they do not wait for each other.
+    tx: RwLock<Option<SyncSender<LogPacket>>>,
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+    rx: Weak<Mutex<Receiver<LogPacket>>>,
+    queued: Arc<AtomicUsize>,
+    dropped: Arc<AtomicUsize>,
+    // Packets discarded by a drain that timed out
+    abandoned: AtomicUsize,
+    // Whether a drain stopped waiting for the threads
+    detached: AtomicBool,
+}
+
+impl Lifecycle {
+    /// Closes the queue and joins the logging threads, abandoning
+    /// them and what is left of the queue once `timeout` has passed.
+    /// Returns whether they all finished.
+    fn shutdown(&self, timeout: Option<Duration>) -> bool {
+        // Dropping the last sender ends the receive loop
+        match self.tx.write() {
+            Ok(mut tx) => tx.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut threads = lock(&self.threads);
+        loop {
+            let mut index = 0;
+            while index < threads.len() {
+                if deadline.is_none() || threads[index].is_finished() {
+                    if threads.remove(index).join().is_err() {
+                        error!("Logger thread panicked; some statistics may have been lost");
+                    }
+                } else {
+                    index += 1;
+                }
+            }
+
+            match deadline {
+                _ if threads.is_empty() => return !self.detached.load(Ordering::Relaxed),
+                Some(deadline) if Instant::now() >= deadline => {
+                    self.abandon(&mut threads);
+                    return false;
+                }
+                _ => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+    }
+
+    /// A sender for the queue, unless it has been closed.
+    fn sender(&self) -> Option<SyncSender<LogPacket>> {
+        match self.tx.read() {
+            Ok(tx) => tx.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Discards the packets the threads have not taken from the queue
+    /// and stops waiting for the threads, which may be stuck in the
+    /// writer, so dropping the logger does not wait for them either.
+    fn abandon(&self, threads: &mut Vec<thread::JoinHandle<()>>) {
+        let mut abandoned = 0;
+        if let Some(rx) = self.rx.upgrade() {
+            let rx = lock(&rx);
+            while rx.try_recv().is_ok() {
+                abandoned += 1;
+            }
+        }
+        self.queued.fetch_sub(abandoned, Ordering::Relaxed);
+        self.dropped.fetch_add(abandoned, Ordering::Relaxed);
+        self.abandoned.fetch_add(abandoned, Ordering::Relaxed);
+
+        threads.clear();
+        self.detached.store(true, Ordering::Relaxed);
+        warn!("Gave up waiting for the logging thread; abandoned {} queued packet(s)", abandoned);
+    }
+}
+
+impl Worker {
+    fn send(&self, mut packet: LogPacket) {
+        let sending = self.clock.now_instant();
+        let tx = match self.lifecycle.sender() {
+            Some(tx) => tx,
+            None => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        packet.enqueued = Some(self.clock.now_instant());
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let queued = match self.policy {
+            OverflowPolicy::Block => tx.send(packet).is_ok(),
+            OverflowPolicy::DropNewest => tx.try_send(packet).is_ok(),
+            OverflowPolicy::DropOldest => self.send_evicting(&tx, packet),
+        };
+        let waited = self.clock.now_instant().saturating_duration_since(sending);
+        self.contention.record(waited, self.contention_threshold);
+
+        if !queued {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn send_evicting(&self, tx: &SyncSender<LogPacket>, mut packet: LogPacket) -> bool {
+        loop {
+            packet = match tx.try_send(packet) {
+                Ok(()) => return true,
+                Err(TrySendError::Full(packet)) => packet,
+                Err(TrySendError::Disconnected(_)) => return false,
+            };
+
+            let rx = match self.rx.upgrade() {
+                Some(rx) => rx,
+                None => return false,
+            };
+
+            // Make room by discarding the head of the queue
+            let rx = lock(&rx);
+            if rx.try_recv().is_ok() {
+                self.queued.fetch_sub(1, Ordering::Relaxed);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.lifecycle.shutdown(None);
+    }
+}
+
+/// Where a `Recorder` delivers its packets, from any thread.
+trait Sink: Send + Sync + 'static {
+    /// Delivers the packet built by `packet`, which is not called when
+    /// the packet would be discarded anyway.
+    fn send_with<F>(&self, packet: F)
+        where F: FnOnce() -> LogPacket;
+}
+
+impl Sink for Worker {
+    /// Builds and queues a packet according to the overflow policy.
+    /// Logging is best-effort, so packets that cannot be queued are
+    /// counted as dropped rather than reported.
+    ///
+    /// When the queue is already full and the packet would be
+    /// discarded anyway, it is not built at all.
+    fn send_with<F>(&self, packet: F)
+        where F: FnOnce() -> LogPacket
+    {
+        let full = self.capacity > 0 && self.queued.load(Ordering::Relaxed) >= self.capacity;
+        if full && self.policy == OverflowPolicy::DropNewest {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        self.send(packet())
+    }
+}
+
+/// Builds a packet for each request, from the state stamped when the
+/// request came in, and hands it to a sink. Shared by `LogHandler`,
+/// the split middleware and `SyncStatisticLogger`.
+struct Recorder<S = Worker> {
+    // Shared with the streamed bodies the recorder is timing
+    sink: Arc<S>,
+    capture: Capture,
+    filter: Option<RequestFilter>,
+    label: Option<RequestLabel>,
+    sampling: Option<Sampling>,
+    clock: Arc<Clock>,
+    timing: TimingSource,
+    // The next packet's sequence number
+    seq: AtomicU64,
+}
+
+/// What is known about a request before the handler runs.
+struct Started {
+    entered: Instant,
+    request_id: String,
+    logged: bool,
+    overhead: Duration,
+    start: SystemTime,
+    before: Instant,
+}
+
+impl<S: Sink> Recorder<S> {
+    fn start(&self, req: &Request) -> Started {
+        let clock = &*self.clock;
+        let entered = clock.now_instant();
+        let request_id = request_id::find_or_generate(req);
+        let logged = self.filter.as_ref().is_none_or(|filter| filter(req));
+        let start = clock.now_system();
+        let before = clock.now_instant();
+
+        Started {
+            entered: entered,
+            request_id: request_id,
+            logged: logged,
+            overhead: before.duration_since(entered),
+            start: start,
+            before: before,
+        }
+    }
+
+    fn finish(&self, req: &Request, response_result: &mut IronResult<Response>, started: Started) {
+        let clock = &*self.clock;
+        let returned = clock.now_instant();
+        let Started { entered, request_id, logged, overhead, start, before } = started;
+        let timing = match self.timing {
+            TimingSource::Monotonic => returned.duration_since(before),
+            TimingSource::WallClock => clock.now_system().duration_since(start).unwrap_or_default(),
+        };
+
+        request_id::set(response_result, &request_id);
+
+        if !logged {
+            return;
+        }
+        let mut sample_rate = 1.0;
+        if let Some(ref sampling) = self.sampling {
+            let status = match *response_result {
+                Ok(ref response) => response.status,
+                Err(ref failure) => failure.response.status,
+            };
+            sample_rate = sampling.rate(req.url.as_ref().path(), status);
+            if !sampling::keep_at(sampling, sample_rate) {
+                return;
+            }
+        }
+
+        let streamed = match *response_result {
+            Ok(ref response) => is_streamed(response),
+            Err(ref failure) => is_streamed(&failure.response),
+        };
+        // Taken before the sink can drop the packet, to leave a gap
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+
+        let build = || {
+            let response = match *response_result {
+                Ok(ref response) => response,
+                Err(ref failure) => &failure.response,
+            };
+
+            let url = self.capture.redaction.apply_url(&self.capture.query.apply(&req.url));
+            let (url, truncated) = self.capture.truncate_url(url);
+            let tls = tls::get(req);
+            let mut packet = LogPacket {
+                seq: seq,
+                query: url.query().map(String::from),
+                truncated: truncated,
+                url: url,
+                method: req.method.clone(),
+                ip: self.capture.client_ip(req),
+                status: response.status,
+                start: start,
+                timing: timing,
+                response_len: body_len(response),
+                user_agent: req.headers.get::<UserAgent>().map(|ua| ua.0.clone()),
+                referer: req.headers.get::<Referer>().map(|referer| referer.0.clone()),
+                request_id: request_id,
+                host: req.headers.get::<Host>().map(format_host),
+                content_type: response.headers.get::<ContentType>().map(|ct| ct.to_string()),
+                error: response_result.as_ref().err().map(|failure| failure.error.to_string()),
+                request_len: req.headers.get::<ContentLength>().map(|len| len.0),
+                request_headers: self.capture.redaction.apply_headers(
+                    capture::headers(&req.headers, &self.capture.request_headers)),
+                response_headers: self.capture.redaction.apply_headers(
+                    capture::headers(&response.headers, &self.capture.response_headers)),
+                scheme: self.capture.scheme(req),
+                http_version: req.version.to_string(),
+                tls_version: tls.as_ref().map(|tls| tls.version.clone()),
+                tls_cipher: tls.map(|tls| tls.cipher),
+                overhead: Duration::default(),
+                total: Duration::default(),
+                ttfb: None,
+                tags: self.capture.tags.clone(),
+                fields: fields::get(req),
+                route: route_pattern::get(req),
+                label: self.label.as_ref().and_then(|label| label(req)),
+                count: 1,
+                sample_rate: sample_rate,
+                log_lag: Duration::default(),
+                enqueued: None,
+            };
+
+            let finished = clock.now_instant();
+            packet.overhead = overhead + finished.duration_since(returned);
+            packet.total = finished.duration_since(entered);
+            packet
+        };
+
+        if !streamed {
+            return self.sink.send_with(|| {
+                let mut packet = build();
+                packet.ttfb = Some(packet.total);
+                packet
+            });
+        }
+
+        let packet = build();
+        let response = match *response_result {
+            Ok(ref mut response) => response,
+            Err(ref mut failure) => &mut failure.response,
+        };
+        let body = response.body.take().expect("Streamed response without a body");
+        response.body = Some(Box::new(first_byte::wrap(body, packet, self.sink.clone(), self.clock.clone(), entered)));
+    }
+}
+
+struct LogHandler<S = Worker> {
+    handler: Box<Handler>,
+    recorder: Recorder<S>,
+    catch_panics: bool,
+    in_flight: InFlight,
+    disabled: bool,
+}
+
+impl<S> LogHandler<S> {
+    fn handle_inner(&self, req: &mut Request) -> IronResult<Response> {
+        if !self.catch_panics {
+            return self.handler.handle(req);
+        }
+        match panic::catch_unwind(AssertUnwindSafe(|| self.handler.handle(req))) {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = payload.downcast_ref::<&str>().map(|&m| m.to_owned())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "Box<dyn Any>".into());
+                Err(IronError::new(HandlerPanicked(message), iron::status::InternalServerError))
+            }
+        }
+    }
+}
+
+/// The error logged when the wrapped handler panics.
+#[derive(Debug)]
+struct HandlerPanicked(String);
+
+impl fmt::Display for HandlerPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handler panicked: {}", self.0)
+    }
+}
+
+impl error::Error for HandlerPanicked {}
+
+impl<S> Handler for LogHandler<S>
+    where S: Sink + Send + Sync + 'static
+{
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let _in_flight = in_flight::enter(&self.in_flight);
+        if self.disabled {
+            // Downstream services still rely on the correlation ID
+            let request_id = request_id::find_or_generate(req);
+            let mut response_result = self.handle_inner(req);
+            request_id::set(&mut response_result, &request_id);
+            return response_result;
+        }
+        let started = self.recorder.start(req);
+        let mut response_result = self.handle_inner(req);
+        self.recorder.finish(req, &mut response_result, started);
+        response_result
+    }
+}
+
+fn format_host(host: &Host) -> String {
+    match host.port {
+        Some(port) => format!("{}:{}", host.hostname, port),
+        None => host.hostname.clone(),
+    }
+}
+
+/// Whether the body is streamed from a reader, rather than known up
+/// front.
+fn is_streamed(response: &Response) -> bool {
+    response.body.is_some() && body_len(response).is_none()
+}
+
+/// The size of the response body, when it is known up front. Iron
+/// sets `Content-Length` for in-memory and file bodies, but not for
+/// bodies streamed from an arbitrary reader.
+fn body_len(response: &Response) -> Option<u64> {
+    match response.body {
+        None => Some(0),
+        Some(_) => response.headers.get::<ContentLength>().map(|len| len.0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::io::Read;
+
+    use std::sync::mpsc::{Receiver, Sender};
+
+    use ::iron::status;
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+    use self::tempdir::TempDir;
+
+    struct CountingLogger(Arc<AtomicUsize>);
+
+    impl LogWriter for CountingLogger {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            thread::sleep(Duration::from_millis(10));
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct LagRecorder(Arc<Mutex<Vec<Duration>>>);
+
+    impl LogWriter for LagRecorder {
+        type Error = io::Error;
+
+        fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+            thread::sleep(Duration::from_millis(20));
+            self.0.lock().unwrap().push(packet.log_lag());
+            Ok(())
+        }
+    }
+
+    struct GatedLogger {
+        started: Sender<()>,
+        release: Receiver<()>,
+        paths: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl LogWriter for GatedLogger {
+        type Error = io::Error;
+
+        fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+            let _ = self.started.send(());
+            let _ = self.release.recv();
+            self.paths.lock().unwrap().push(packet.url.path().join("/"));
+            Ok(())
+        }
+    }
+
+    struct FailsOnceLogger {
+        failed: bool,
+        count: Arc<AtomicUsize>,
+    }
+
+    impl LogWriter for FailsOnceLogger {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            if !self.failed {
+                self.failed = true;
+                return Err(io::Error::other("disk full"));
+            }
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct PanickingLogger;
+
+    impl LogWriter for PanickingLogger {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            panic!("Writer exploded");
+        }
+    }
+
+    fn overflow_with(policy: OverflowPolicy) -> (Vec<String>, usize) {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let paths = Arc::new(Mutex::new(Vec::new()));
+
+        let logger = StatisticLogger::builder().capacity(1).overflow_policy(policy).build(GatedLogger {
+            started: started_tx,
+            release: release_rx,
+            paths: paths.clone(),
+        });
+        let dropped = logger.dropped();
+        let handler = logger.around(Box::new(ok_handler));
+
+        request::get("http://127.0.0.1/1", Headers::new(), &handler).expect("Request failed");
+        started_rx.recv().unwrap();
+        for path in &["2", "3", "4"] {
+            let url = format!("http://127.0.0.1/{}", path);
+            request::get(&url, Headers::new(), &handler).expect("Request failed");
+        }
+
+        drop(release_tx);
+        drop(handler);
+
+        let paths = paths.lock().unwrap().clone();
+        (paths, dropped.count())
+    }
+
+    struct CsvCapture(Arc<Mutex<Vec<String>>>);
+
+    impl LogWriter for CsvCapture {
+        type Error = csv::Error;
+
+        fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+            let row = try!(encode_csv(packet, &Encoding::default()));
+            self.0.lock().unwrap().push(String::from_utf8(row).unwrap());
+            Ok(())
+        }
+    }
+
+    /// Runs the requests through a logged handler, returning the CSV
+    /// row written for each.
+    fn csv_rows<H, F>(handler: H, requests: F) -> Vec<String>
+        where H: Handler,
+              F: FnOnce(&Box<Handler>),
+    {
+        let rows = Arc::new(Mutex::new(Vec::new()));
+        let handler = StatisticLogger::new(CsvCapture(rows.clone())).around(Box::new(handler));
+        requests(&handler);
+        drop(handler);
+
+        let rows = rows.lock().unwrap().clone();
+        rows
+    }
+
+    /// The named field of a CSV row logged by `csv_rows`.
+    fn column(row: &str, name: &str) -> String {
+        let index = Column::DEFAULT.iter().position(|c| c.name() == name).expect("Unknown column");
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(row.as_bytes());
+        let record = reader.records().next().expect("Empty row").expect("Invalid row");
+        record[index].to_owned()
+    }
+
+    struct FlushCounter(Arc<AtomicUsize>);
+
+    impl LogWriter for FlushCounter {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Stands still until advanced, starting at a fixed wall-clock
+    /// time.
+    #[derive(Clone)]
+    struct FakeClock {
+        base: Instant,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl FakeClock {
+        fn new() -> FakeClock {
+            FakeClock {
+                base: Instant::now(),
+                elapsed: Arc::new(Mutex::new(Duration::from_secs(0))),
+            }
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.elapsed.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now_system(&self) -> SystemTime {
+            ::std::time::UNIX_EPOCH + Duration::from_secs(1_500_000_000) + *self.elapsed.lock().unwrap()
+        }
+
+        fn now_instant(&self) -> Instant {
+            self.base + *self.elapsed.lock().unwrap()
+        }
+    }
+
+    fn ok_handler(_req: &mut Request) -> IronResult<Response> {
+        Ok(Response::with(status::Ok))
+    }
+
+    fn hello_handler(_req: &mut Request) -> IronResult<Response> {
+        Ok(Response::with((status::Ok, "Hello, world!")))
+    }
+
+    #[test]
+    fn durations_keep_leading_zeros_in_the_fraction() {
+        assert_eq!(format_duration(&Duration::new(1, 42)), "1.000000042");
+        assert_eq!(format_duration(&Duration::new(0, 500_000_000)), "0.500000000");
+    }
+
+    fn read(path: &Path) -> String {
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn new_files_start_with_a_header() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        let mut logger = FileLogger::new(&path).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.flush().unwrap();
+
+        let contents = read(&path);
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], Encoding::default().header().join(","));
+    }
+
+    #[test]
+    fn files_only_get_the_selected_columns() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        let encoding = Encoding::new().columns(&[Column::Method, Column::Path, Column::Status]);
+        let mut logger = FileLogger::new(&path).unwrap().encoding(encoding);
+        logger.log(&LogPacket::stub()).unwrap();
+        drop(logger);
+
+        assert_eq!(read(&path), "method,path,status\nGET,/,200\n");
+    }
+
+    #[test]
+    fn buffered_rows_reach_the_file_when_flushed() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        let mut logger = FileLogger::with_buffer_capacity(&path, 1024 * 1024).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(read(&path).lines().count(), 0);
+
+        logger.flush().unwrap();
+        assert_eq!(read(&path).lines().count(), 2);
+
+        logger.log(&LogPacket::stub()).unwrap();
+        drop(logger);
+        assert_eq!(read(&path).lines().count(), 3);
+    }
+
+    #[test]
+    fn synced_rows_are_on_disk_without_a_flush() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        let mut logger = FileLogger::with_buffer_capacity(&path, 1024 * 1024).unwrap().sync(SyncPolicy::EveryWrite);
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(read(&path).lines().count(), 2);
+
+        let mut logger = FileLogger::new(&path).unwrap().sync(SyncPolicy::EveryBatch);
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.flush().unwrap();
+        assert_eq!(read(&path).lines().count(), 3);
+    }
+
+    #[test]
+    fn rows_are_written_when_the_logger_is_dropped() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        let mut logger = FileLogger::new(&path).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        drop(logger);
+
+        let contents = read(&path);
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(column(lines[2], "url"), "http://127.0.0.1/");
+    }
+
+    #[test]
+    fn cloned_packets_encode_the_same() {
+        let mut packet = LogPacket::stub();
+        packet.request_headers.insert("Accept".into(), "text/html".into());
+        let clone = packet.clone();
+
+        assert_eq!(clone.url(), packet.url());
+        assert_eq!(clone.ip(), packet.ip());
+        assert_eq!(clone.status(), packet.status());
+        assert_eq!(clone.start(), packet.start());
+        assert_eq!(clone.timing(), packet.timing());
+        assert_eq!(clone.request_headers(), packet.request_headers());
+        assert_eq!(encode_csv(&clone, &Encoding::default()).unwrap(),
+                   encode_csv(&packet, &Encoding::default()).unwrap());
+    }
+
+    #[test]
+    fn clocks_before_the_epoch_do_not_stop_logging() {
+        let mut packet = LogPacket::stub();
+        packet.start = ::std::time::UNIX_EPOCH - Duration::from_secs(1);
+
+        let row = String::from_utf8(encode_csv(&packet, &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(column(&row, "start"), "0.000000000");
+        assert!(json::encode(&packet).is_ok());
+    }
+
+    #[test]
+    fn fields_can_be_separated_with_tabs() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.tsv");
+
+        let mut packet = LogPacket::stub();
+        packet.url = iron::Url::parse("http://127.0.0.1/search?tags=a,b").unwrap();
+        let mut logger = FileLogger::with_delimiter(&path, b'\t').unwrap();
+        logger.log(&packet).unwrap();
+        drop(logger);
+
+        let contents = read(&path);
+        let mut reader = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(contents.as_bytes());
+        assert_eq!(reader.headers().unwrap().iter().collect::<Vec<_>>(), Encoding::default().header());
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(&record[1], "http://127.0.0.1/search?tags=a,b");
+        assert_eq!(&record[2], "GET");
+        assert!(!contents.contains('"'), "{}", contents);
+    }
+
+    #[test]
+    fn files_can_be_written_in_other_formats() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.log");
+
+        let mut logger = FileLogger::with_formatter(&path, CombinedLogFormatter::common()).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        drop(logger);
+
+        let line = "127.0.0.1 - - [14/Jul/2017:02:40:00 +0000] \"GET / HTTP/1.1\" 200 13\n";
+        assert_eq!(read(&path), format!("{}{}", line, line));
+    }
+
+    #[test]
+    fn the_format_follows_the_extension_unless_overridden() {
+        let dir = TempDir::new("file").unwrap();
+        let write = |name: &str, logger: FileLogger| {
+            let mut logger = logger;
+            logger.log(&LogPacket::stub()).unwrap();
+            drop(logger);
+            read(&dir.path().join(name))
+        };
+        let new = |name: &str| FileLogger::new(dir.path().join(name)).unwrap();
+
+        let csv = write("access.csv", new("access.csv"));
+        assert_eq!(csv.lines().next(), Some(&Encoding::default().header().join(",")[..]));
+        assert_eq!(csv.lines().count(), 2);
+
+        let jsonl = write("access.jsonl", new("access.jsonl"));
+        assert_eq!(jsonl.into_bytes(), json::encode(&LogPacket::stub()).unwrap());
+
+        let log = write("access.log", new("access.log"));
+        assert_eq!(log, "127.0.0.1 - - [14/Jul/2017:02:40:00 +0000] \"GET / HTTP/1.1\" 200 13 \"-\" \"iron-test\"\n");
+
+        let path = dir.path().join("csv.log");
+        let overridden = write("csv.log", FileLogger::with_formatter(&path, FileFormat::default()).unwrap());
+        assert_eq!(overridden.lines().count(), 2);
+    }
+
+    #[test]
+    fn quoted_fields_are_read_back_unchanged() {
+        let dir = TempDir::new("file").unwrap();
+        let mut packet = LogPacket::stub();
+        // Quotes are percent-encoded in URLs, but not in user agents
+        packet.url = iron::Url::parse("http://127.0.0.1/search?tags=a,b").unwrap();
+        packet.user_agent = Some("curl \"7.0\", like wget".into());
+
+        for &(name, quoting) in &[("necessary", CsvQuoting::new()), ("always", CsvQuoting::new().always())] {
+            let path = dir.path().join(name);
+            let mut logger = FileLogger::with_quoting(&path, quoting).unwrap();
+            logger.log(&packet).unwrap();
+            drop(logger);
+
+            let contents = read(&path);
+            let mut reader = csv::Reader::from_reader(contents.as_bytes());
+            let record = reader.records().next().unwrap().unwrap();
+            assert_eq!(&record[1], "http://127.0.0.1/search?tags=a,b");
+            assert_eq!(&record[8], "curl \"7.0\", like wget");
+            assert_eq!(contents.contains("\"GET\""), name == "always", "{}", contents);
+        }
+
+        let path = dir.path().join("single");
+        let mut logger = FileLogger::with_quoting(&path, CsvQuoting::new().quote(b'\'')).unwrap();
+        logger.log(&packet).unwrap();
+        drop(logger);
+        let contents = read(&path);
+        assert!(contents.contains("\n0,'http://127.0.0.1/search?tags=a,b',GET,"), "{}", contents);
+    }
+
+    #[test]
+    fn existing_files_do_not_get_another_header() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        for _ in 0..2 {
+            let mut logger = FileLogger::new(&path).unwrap();
+            logger.log(&LogPacket::stub()).unwrap();
+            logger.flush().unwrap();
+        }
+
+        let contents = read(&path);
+        assert_eq!(contents.lines().filter(|line| line.starts_with("seq,url,")).count(), 1);
+        assert_eq!(contents.lines().count(), 3);
+    }
+
+    #[test]
+    fn headerless_files_only_contain_rows() {
+        let dir = TempDir::new("file").unwrap();
+        let path = dir.path().join("access.csv");
+
+        let mut logger = FileLogger::headerless(&path).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.flush().unwrap();
+
+        assert!(read(&path).starts_with("0,http://127.0.0.1/,GET,"));
+    }
+
+    #[test]
+    fn dropping_the_handler_writes_queued_packets() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = StatisticLogger::new(CountingLogger(count.clone()));
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn bounded_queue_still_logs_every_packet() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = StatisticLogger::with_capacity(CountingLogger(count.clone()), 1);
+        assert_eq!(logger.capacity(), 1);
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..5 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn packets_waiting_for_a_slow_writer_record_their_lag() {
+        let lags = Arc::new(Mutex::new(Vec::new()));
+        let handler = StatisticLogger::with_capacity(LagRecorder(lags.clone()), 16).around(Box::new(ok_handler));
+
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        let lags = lags.lock().unwrap();
+        assert_eq!(lags.len(), 3);
+        assert!(lags[2] >= Duration::from_millis(20), "{:?}", *lags);
+    }
+
+    #[test]
+    fn requests_do_not_wait_for_each_other_to_queue() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = StatisticLogger::new(CountingLogger(count.clone()));
+        let lifecycle = logger.worker.lifecycle.clone();
+        let handler = logger.around(Box::new(ok_handler));
+
+        {
+            // Stands in for another request taking the sender
+            let _tx = lifecycle.tx.read().unwrap();
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn requests_are_still_logged_once_the_channel_lock_is_poisoned() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = StatisticLogger::new(CountingLogger(count.clone()));
+        let lifecycle = logger.worker.lifecycle.clone();
+        let poisoner = thread::spawn(move || {
+            let _tx = lifecycle.tx.write().unwrap();
+            panic!("Poisoning the logger channel");
+        });
+        assert!(poisoner.join().is_err());
+        assert!(logger.worker.lifecycle.tx.is_poisoned());
+
+        let handler = logger.around(Box::new(ok_handler));
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn packets_are_logged_through_a_handle_until_the_queue_is_full_or_closed() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let logger = StatisticLogger::with_capacity(GatedLogger {
+            started: started_tx,
+            release: release_rx,
+            paths: paths.clone(),
+        }, 1);
+        let handle = logger.handle();
+
+        let mut packet = LogPacket::stub();
+        packet.url = iron::Url::parse("http://127.0.0.1/1").unwrap();
+        handle.try_log(&packet).unwrap();
+        started_rx.recv().unwrap();
+        packet.url = iron::Url::parse("http://127.0.0.1/2").unwrap();
+        handle.try_log(&packet).unwrap();
+        match handle.try_log(&packet) {
+            Err(LoggingError::QueueFull) => {}
+            other => panic!("Expected a full queue, got {:?}", other),
+        }
+
+        drop(release_tx);
+        assert!(handle.drain(Duration::from_secs(5)));
+        match handle.try_log(&packet) {
+            Err(LoggingError::ChannelClosed) => {}
+            other => panic!("Expected a closed channel, got {:?}", other),
+        }
+        assert_eq!(*paths.lock().unwrap(), ["1", "2"]);
+        assert_eq!(handle.dropped(), 0);
+        drop(logger);
+    }
+
+    #[test]
+    fn logging_errors_can_be_told_apart() {
+        let writer = LoggingError::from(BoxedError::new(io::Error::other("disk full")));
+        match writer {
+            LoggingError::Writer(ref e) => assert_eq!(e.to_string(), "disk full"),
+            ref other => panic!("Expected a writer failure, got {:?}", other),
+        }
+        assert_eq!(writer.to_string(), "Unable to log request: disk full");
+        assert_eq!(error::Error::source(&writer).unwrap().to_string(), "disk full");
+
+        let spawn = LoggingError::Spawn(io::Error::other("out of threads"));
+        assert!(matches!(spawn, LoggingError::Spawn(ref e) if e.to_string() == "out of threads"));
+        assert_eq!(spawn.to_string(), "Unable to spawn logger thread: out of threads");
+
+        assert!(error::Error::source(&LoggingError::QueueFull).is_none());
+        assert_eq!(LoggingError::QueueFull.to_string(), "The logging queue is full");
+        assert_eq!(LoggingError::ChannelClosed.to_string(), "The logger has shut down");
+    }
+
+    #[test]
+    fn draining_writes_queued_packets_and_stops_logging() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = StatisticLogger::with_capacity(CountingLogger(count.clone()), 16);
+        let shutdown = logger.shutdown_handle();
+        let dropped = logger.dropped();
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..8 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        assert!(shutdown.drain(Duration::from_secs(5)));
+        assert_eq!(count.load(Ordering::SeqCst), 8);
+
+        // Requests are still served, but no longer logged
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        assert_eq!(dropped.count(), 1);
+        assert!(shutdown.drain(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn draining_gives_up_on_a_stuck_writer() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let logger = StatisticLogger::with_capacity(GatedLogger {
+            started: started_tx,
+            release: release_rx,
+            paths: Arc::default(),
+        }, 16);
+        let handle = logger.handle();
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..5 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        // One packet is stuck in the writer, the rest are queued
+        started_rx.recv().unwrap();
+
+        let started = Instant::now();
+        assert!(!handle.drain(Duration::from_millis(50)));
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        assert_eq!(handle.abandoned(), 4);
+        assert_eq!(handle.dropped(), 4);
+        assert_eq!(handle.queued(), 0);
+
+        // Neither dropping the logger nor draining again waits for it
+        drop(handler);
+        assert!(!handle.drain(Duration::from_secs(5)));
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        drop(release_tx);
+    }
+
+    #[test]
+    fn requests_blocked_on_a_full_queue_are_counted() {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let logger = StatisticLogger::builder().capacity(1).overflow_policy(OverflowPolicy::Block).build(GatedLogger {
+            started: started_tx,
+            release: release_rx,
+            paths: Arc::default(),
+        });
+        let handle = logger.handle();
+        let handler = Arc::new(logger.around(Box::new(ok_handler)));
+
+        // Neither queueing into an empty queue counts
+        request::get("http://127.0.0.1/", Headers::new(), &*handler).expect("Request failed");
+        started_rx.recv().unwrap();
+        request::get("http://127.0.0.1/", Headers::new(), &*handler).expect("Request failed");
+        assert_eq!(handle.contention(), Contention::default());
+
+        let blocked = {
+            let handler = handler.clone();
+            thread::spawn(move || {
+                request::get("http://127.0.0.1/", Headers::new(), &*handler).expect("Request failed");
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        drop(release_tx);
+        blocked.join().unwrap();
+
+        let contention = handle.contention();
+        assert_eq!(contention.slow_sends, 1);
+        assert!(contention.max_blocked >= Duration::from_millis(40), "{:?}", contention);
+        assert_eq!(contention.blocked, contention.max_blocked);
+    }
+
+    #[test]
+    fn installed_loggers_are_controlled_through_their_handle() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let logger = StatisticLogger::new(CountingLogger(count.clone()));
+        let handle = logger.handle();
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        assert_eq!(handle.in_flight(), InFlightCounts { current: 0, max: 1 });
+        assert!(handle.clone().drain(Duration::from_secs(5)));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(handle.queued(), 0);
+
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        assert_eq!(handle.dropped(), 1);
+    }
+
+    #[test]
+    fn batches_are_flushed_when_full_and_at_shutdown() {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let writer = FlushCounter(flushes.clone());
+        let logger = StatisticLogger::with_batching(writer, 16, 3, Duration::from_secs(3600));
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..7 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        assert_eq!(flushes.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn batches_are_flushed_after_the_interval() {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let writer = FlushCounter(flushes.clone());
+        let logger = StatisticLogger::with_batching(writer, 16, 100, Duration::from_millis(10));
+        let handler = logger.around(Box::new(ok_handler));
+
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        for _ in 0..100 {
+            if flushes.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+        drop(handler);
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+    }
+
+    /// Records each call as `L` for log or `F` for flush.
+    #[derive(Clone, Default)]
+    struct CallRecorder(Arc<Mutex<String>>);
+
+    impl CallRecorder {
+        fn calls(&self) -> String {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl LogWriter for CallRecorder {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push('L');
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push('F');
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flushes_follow_the_strategy() {
+        let flushed = |strategy: FlushStrategy| {
+            let calls = CallRecorder::default();
+            let handler = StatisticLogger::builder()
+                .flush_strategy(strategy)
+                .build(calls.clone())
+                .around(Box::new(ok_handler));
+            for _ in 0..7 {
+                request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+            }
+            drop(handler);
+            calls.calls()
+        };
+
+        assert_eq!(flushed(FlushStrategy::EveryPacket), "LFLFLFLFLFLFLF");
+        assert_eq!(flushed(FlushStrategy::EveryN(3)), "LLLFLLLFLF");
+        assert_eq!(flushed(FlushStrategy::OnDropOnly), "LLLLLLLF");
+    }
+
+    #[test]
+    fn interval_flushes_are_timed_with_the_clock() {
+        let clock = FakeClock::new();
+        let calls = CallRecorder::default();
+        let handler = StatisticLogger::builder()
+            .clock(clock.clone())
+            .flush_strategy(FlushStrategy::Interval(Duration::from_secs(60)))
+            .build(calls.clone())
+            .around(Box::new(ok_handler));
+
+        let logged = |calls: &CallRecorder, expected: &str| {
+            for _ in 0..200 {
+                if calls.calls() == expected {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            panic!("Expected {:?}, got {:?}", expected, calls.calls());
+        };
+
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        logged(&calls, "L");
+        clock.advance(Duration::from_secs(30));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        logged(&calls, "LL");
+        clock.advance(Duration::from_secs(30));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        logged(&calls, "LLLF");
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        assert_eq!(calls.calls(), "LLLFLF");
+    }
+
+    #[test]
+    fn quiet_flushes_wait_for_bursts_to_end() {
+        let clock = FakeClock::new();
+        let calls = CallRecorder::default();
+        let handler = StatisticLogger::builder()
+            .clock(clock.clone())
+            .flush_strategy(FlushStrategy::WhenQuiet { quiet: Duration::from_millis(20), max_batch: 4 })
+            .build(calls.clone())
+            .around(Box::new(ok_handler));
+
+        let logged = |calls: &CallRecorder, expected: &str| {
+            for _ in 0..200 {
+                if calls.calls() == expected {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(5));
+            }
+            panic!("Expected {:?}, got {:?}", expected, calls.calls());
+        };
+
+        // However long the burst takes, the clock says it came at once
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        logged(&calls, "LLL");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(calls.calls(), "LLL");
+        clock.advance(Duration::from_millis(20));
+        logged(&calls, "LLLF");
+
+        clock.advance(Duration::from_secs(10));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        logged(&calls, "LLLFL");
+        clock.advance(Duration::from_millis(20));
+        logged(&calls, "LLLFLF");
+
+        // Long bursts are flushed in batches
+        for _ in 0..5 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        logged(&calls, "LLLFLFLLLLFL");
+        drop(handler);
+        assert_eq!(calls.calls(), "LLLFLFLLLLFLF");
+    }
+
+    #[test]
+    fn write_failures_do_not_stop_logging() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let reported = errors.clone();
+
+        let writer = FailsOnceLogger { failed: false, count: count.clone() };
+        let logger = StatisticLogger::with_error_handler(writer, 16, move |_packet, e| {
+            reported.lock().unwrap().push(e.to_string());
+        });
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(*errors.lock().unwrap(), ["disk full"]);
+    }
+
+    #[test]
+    fn requests_succeed_after_the_logging_thread_dies() {
+        let logger = StatisticLogger::with_capacity(PanickingLogger, 1);
+        let dropped = logger.dropped();
+        let handler = logger.around(Box::new(ok_handler));
+
+        for _ in 0..100 {
+            let response = request::get("http://127.0.0.1/", Headers::new(), &handler);
+            assert_eq!(response.expect("Request failed").status, Some(status::Ok));
+
+            if dropped.count() > 0 {
+                return;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        panic!("Packets were never reported as dropped");
+    }
+
+    #[test]
+    fn method_is_logged_after_the_url() {
+        let rows = csv_rows(ok_handler, |handler| {
+            request::post("http://127.0.0.1/", Headers::new(), "", handler).expect("Request failed");
+        });
+
+        assert!(rows[0].starts_with("0,http://127.0.0.1/,POST,"), "{}", rows[0]);
+    }
+
+    #[test]
+    fn fixed_size_response_bodies_are_measured() {
+        let rows = csv_rows(hello_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect("Request failed");
+        });
+
+        assert!(rows[0].contains(",13,"), "{}", rows[0]);
+    }
+
+    fn logged_ip(trusted_proxies: usize, forwarded_for: Option<&str>) -> String {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLogger::builder().trust_forwarded_for(trusted_proxies).build(memory.clone());
+        let handler = logger.around(Box::new(ok_handler));
+
+        let mut headers = Headers::new();
+        if let Some(forwarded_for) = forwarded_for {
+            headers.set_raw("X-Forwarded-For", vec![forwarded_for.as_bytes().to_vec()]);
+        }
+        request::get("http://127.0.0.1/", headers, &handler).expect("Request failed");
+        drop(handler);
+
+        memory.packets()[0].ip.to_string()
+    }
+
+    #[test]
+    fn forwarded_for_is_used_when_trusted() {
+        assert_eq!(logged_ip(1, Some("10.0.0.1, 192.168.1.1")), "192.168.1.1:0");
+        assert_eq!(logged_ip(2, Some("10.0.0.1, 192.168.1.1")), "10.0.0.1:0");
+        assert_eq!(logged_ip(1, Some("[::1]:8080")), "[::1]:8080");
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_without_enough_trusted_proxies() {
+        // Sent by the client straight to the server
+        assert_eq!(logged_ip(0, Some("203.0.113.9")), "127.0.0.1:3000");
+        assert_eq!(logged_ip(3, Some("203.0.113.9, 10.0.0.1")), "127.0.0.1:3000");
+    }
+
+    #[test]
+    fn remote_addr_is_used_without_forwarded_for() {
+        assert_eq!(logged_ip(1, None), "127.0.0.1:3000");
+    }
+
+    #[test]
+    fn remote_addr_is_used_when_forwarded_for_is_malformed() {
+        assert_eq!(logged_ip(1, Some("not-an-address")), "127.0.0.1:3000");
+    }
+
+    #[test]
+    fn query_parameters_can_be_redacted() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::builder()
+            .query_logging(QueryLogging::Redact(vec!["token".into()]))
+            .build(memory.clone())
+            .around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/search?token=abc&page=2", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.url().to_string(), "http://127.0.0.1/search?token=***&page=2");
+        assert_eq!(packet.query(), Some("token=***&page=2"));
+        assert_eq!(packet.path(), "/search");
+    }
+
+    #[test]
+    fn long_urls_are_truncated() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::builder().max_url_len(40).build(memory.clone()).around(Box::new(ok_handler));
+        let long = format!("http://127.0.0.1/search?q={}", "a".repeat(10_000));
+        let escaped = format!("http://127.0.0.1/search?q={}", "%20".repeat(100));
+        request::get(&long, Headers::new(), &handler).expect("Request failed");
+        request::get(&escaped, Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/search?q=a", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets[0].url().to_string(), "http://127.0.0.1/search?q=aaaaaaaaaaa...");
+        assert_eq!(packets[0].query(), Some("q=aaaaaaaaaaa..."));
+        assert!(packets[0].truncated());
+        // Escapes are kept whole
+        assert_eq!(packets[1].url().to_string(), "http://127.0.0.1/search?q=%20%20%20...");
+        assert!(packets[1].truncated());
+        assert_eq!(packets[2].url().to_string(), "http://127.0.0.1/search?q=a");
+        assert!(!packets[2].truncated());
+
+        let encoding = Encoding::default().columns(&[Column::Path, Column::Truncated]);
+        assert_eq!(encode_csv(&packets[0], &encoding).unwrap(), b"/search,true\n");
+        assert_eq!(encode_csv(&packets[2], &encoding).unwrap(), b"/search,false\n");
+    }
+
+    #[test]
+    fn query_parameters_and_headers_are_redacted_by_policy() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::builder()
+            .log_request_headers(&["authorization", "Accept"])
+            .redaction(RedactionPolicy::new().query_params(&["api_key"]).headers(&["Authorization"]))
+            .build(memory.clone())
+            .around(Box::new(ok_handler));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Authorization", vec![b"Bearer abc".to_vec()]);
+        headers.set_raw("Accept", vec![b"text/html".to_vec()]);
+        request::get("http://127.0.0.1/search?api_key=abc&page=2", headers, &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.query(), Some("api_key=***&page=2"));
+        assert_eq!(packet.request_headers()["authorization"], "***");
+        assert_eq!(packet.request_headers()["Accept"], "text/html");
+    }
+
+    #[test]
+    fn requests_without_a_peer_address_log_a_placeholder() {
+        let memory = MemoryLogger::new();
+        let logged = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+        // Stands in for a transport without socket addresses
+        let handler = move |req: &mut Request| {
+            req.remote_addr = "0.0.0.0:0".parse().unwrap();
+            logged.handle(req)
+        };
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.peer_addr(), None);
+        let encoding = Encoding::default().columns(&[Column::Ip, Column::Status]);
+        assert_eq!(encode_csv(packet, &encoding).unwrap(), b"-,200\n");
+        let encoding = encoding.missing_ip("unknown");
+        assert_eq!(encode_csv(packet, &encoding).unwrap(), b"unknown,200\n");
+        let json = String::from_utf8(json::encode(packet).unwrap()).unwrap();
+        assert!(json.contains(r#""ip":null"#), "{}", json);
+        assert!(clf::line(packet, false, &TimestampFormat::Epoch).starts_with("- - - "));
+
+        assert_eq!(LogPacket::stub().peer_addr(), Some("127.0.0.1:3000".parse().unwrap()));
+    }
+
+    #[test]
+    fn missing_peer_addresses_are_not_given_pseudonyms() {
+        let memory = MemoryLogger::new();
+        let logged = StatisticLogger::builder()
+            .anonymize_ip(IpAnonymization::HashSalted("pepper".into()))
+            .build(memory.clone())
+            .around(Box::new(ok_handler));
+        let handler = move |req: &mut Request| {
+            req.remote_addr = "0.0.0.0:0".parse().unwrap();
+            logged.handle(req)
+        };
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.peer_addr(), None);
+        let encoding = Encoding::default().columns(&[Column::Ip]);
+        assert_eq!(encode_csv(packet, &encoding).unwrap(), b"-\n");
+    }
+
+    #[test]
+    fn scheme_is_logged() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        request::get("https://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets[0].scheme(), "http");
+        assert_eq!(packets[1].scheme(), "https");
+        let row = String::from_utf8(encode_csv(&packets[1], &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(column(&row, "scheme"), "https");
+    }
+
+    #[test]
+    fn packets_are_numbered_without_gaps_across_threads() {
+        let memory = MemoryLogger::new();
+        let handler = Arc::new(StatisticLogger::new(memory.clone()).around(Box::new(ok_handler)));
+        let threads: Vec<_> = (0..4).map(|_| {
+            let handler = handler.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    request::get("http://127.0.0.1/", Headers::new(), &*handler).expect("Request failed");
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        drop(handler);
+
+        // Concurrent requests may reach the queue out of order
+        let mut seqs: Vec<_> = memory.packets().iter().map(LogPacket::seq).collect();
+        seqs.sort();
+        assert_eq!(seqs, (0..100).collect::<Vec<_>>());
+
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+        for _ in 0..3 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+        let packets = memory.packets();
+        assert_eq!(packets.iter().map(LogPacket::seq).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        let encoding = Encoding::default().columns(&[Column::Seq, Column::Status]);
+        assert_eq!(encode_csv(&packets[2], &encoding).unwrap(), b"2,200\n");
+    }
+
+    #[test]
+    fn http_version_is_logged() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.http_version(), "HTTP/1.1");
+        let encoding = Encoding::default().columns(&[Column::HttpVersion]);
+        assert_eq!(encode_csv(packet, &encoding).unwrap(), b"HTTP/1.1\n");
+        let json = String::from_utf8(json::encode(packet).unwrap()).unwrap();
+        assert!(json.contains(r#""http_version":"HTTP/1.1""#), "{}", json);
+    }
+
+    #[test]
+    fn overhead_is_recorded_apart_from_the_handler() {
+        fn slow_handler(_req: &mut Request) -> IronResult<Response> {
+            thread::sleep(Duration::from_millis(20));
+            Ok(Response::with(status::Ok))
+        }
+
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(slow_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert!(packet.timing() >= Duration::from_millis(20), "{:?}", packet.timing());
+        assert!(packet.overhead() > Duration::from_secs(0));
+        assert!(packet.overhead() < Duration::from_millis(5), "{:?}", packet.overhead());
+    }
+
+    #[test]
+    fn panics_are_logged_as_server_errors() {
+        fn panicking_handler(_req: &mut Request) -> IronResult<Response> {
+            panic!("Handler exploded");
+        }
+
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::builder().catch_panics().build(memory.clone()).around(Box::new(panicking_handler));
+        let response = request::get("http://127.0.0.1/", Headers::new(), &handler);
+        drop(handler);
+
+        assert_eq!(response.unwrap_err().response.status, Some(status::InternalServerError));
+        let packets = memory.packets();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].status(), Some(status::InternalServerError));
+        assert_eq!(packets[0].error(), Some("Handler panicked: Handler exploded"));
+    }
+
+    #[test]
+    fn panics_are_not_caught_by_default() {
+        fn panicking_handler(_req: &mut Request) -> IronResult<Response> {
+            panic!("Handler exploded");
+        }
+
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(panicking_handler));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            request::get("http://127.0.0.1/", Headers::new(), &handler)
+        }));
+        drop(handler);
+
+        assert!(result.is_err());
+        assert_eq!(memory.packets().len(), 0);
+    }
+
+    #[test]
+    fn the_clock_can_be_replaced() {
+        let clock = FakeClock::new();
+        let handler_clock = clock.clone();
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::builder().clock(clock.clone()).build(memory.clone()).around(Box::new(
+            move |_req: &mut Request| -> IronResult<Response> {
+                handler_clock.advance(Duration::from_millis(250));
+                Ok(Response::with(status::Ok))
+            }));
+
+        clock.advance(Duration::from_secs(10));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.start(), ::std::time::UNIX_EPOCH + Duration::from_secs(1_500_000_010));
+        assert_eq!(packet.timing(), Duration::from_millis(250));
+        assert_eq!(packet.overhead(), Duration::from_secs(0));
+    }
+
+    /// A `FakeClock` whose wall-clock time can also be stepped, like
+    /// an NTP adjustment, without moving its monotonic time.
+    #[derive(Clone)]
+    struct SteppedClock {
+        clock: FakeClock,
+        step: Arc<Mutex<Duration>>,
+    }
+
+    impl Clock for SteppedClock {
+        fn now_system(&self) -> SystemTime {
+            self.clock.now_system() + *self.step.lock().unwrap()
+        }
+
+        fn now_instant(&self) -> Instant {
+            self.clock.now_instant()
+        }
+    }
+
+    #[test]
+    fn timings_are_measured_with_the_chosen_source() {
+        let timing = |source: TimingSource| {
+            let clock = SteppedClock { clock: FakeClock::new(), step: Arc::default() };
+            let handler_clock = clock.clone();
+            let memory = MemoryLogger::new();
+            let handler = StatisticLogger::builder()
+                .clock(clock)
+                .timing_source(source)
+                .build(memory.clone())
+                .around(Box::new(move |_req: &mut Request| -> IronResult<Response> {
+                    handler_clock.clock.advance(Duration::from_millis(250));
+                    *handler_clock.step.lock().unwrap() += Duration::from_secs(1);
+                    Ok(Response::with(status::Ok))
+                }));
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+            drop(handler);
+
+            let packet = &memory.packets()[0];
+            assert_eq!(packet.start(), ::std::time::UNIX_EPOCH + Duration::from_secs(1_500_000_000));
+            packet.timing()
+        };
+
+        assert_eq!(timing(TimingSource::Monotonic), Duration::from_millis(250));
+        assert_eq!(timing(TimingSource::WallClock), Duration::from_millis(1250));
+    }
+
+    #[test]
+    fn the_total_time_includes_downstream_middleware_and_overhead() {
+        let clock = FakeClock::new();
+        let (filter_clock, middleware_clock, handler_clock) = (clock.clone(), clock.clone(), clock.clone());
+        let memory = MemoryLogger::new();
+        let logger = StatisticLogger::builder()
+            .clock(clock.clone())
+            .filter(move |_req| {
+                filter_clock.advance(Duration::from_millis(5));
+                true
+            })
+            .build(memory.clone());
+
+        let mut chain = Chain::new(move |_req: &mut Request| -> IronResult<Response> {
+            handler_clock.advance(Duration::from_millis(10));
+            Ok(Response::with(status::Ok))
+        });
+        chain.link_before(move |_req: &mut Request| -> IronResult<()> {
+            middleware_clock.advance(Duration::from_millis(20));
+            Ok(())
+        });
+        let handler = logger.around(Box::new(chain));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.timing(), Duration::from_millis(30));
+        assert_eq!(packet.overhead(), Duration::from_millis(5));
+        assert_eq!(packet.total(), Duration::from_millis(35));
+    }
+
+    #[test]
+    fn forwarded_proto_is_used_when_trusted() {
+        let logged = |trusted: bool, proto: Option<&str>| {
+            let memory = MemoryLogger::new();
+            let builder = StatisticLogger::builder();
+            let builder = if trusted { builder.trust_forwarded_proto() } else { builder };
+            let logger = builder.build(memory.clone());
+            let handler = logger.around(Box::new(ok_handler));
+
+            let mut headers = Headers::new();
+            if let Some(proto) = proto {
+                headers.set_raw("X-Forwarded-Proto", vec![proto.as_bytes().to_vec()]);
+            }
+            request::get("http://127.0.0.1/", headers, &handler).expect("Request failed");
+            drop(handler);
+
+            memory.packets()[0].scheme().to_owned()
+        };
+
+        assert_eq!(logged(true, Some("HTTPS")), "https");
+        assert_eq!(logged(true, Some("https, http")), "https");
+        assert_eq!(logged(true, None), "http");
+        assert_eq!(logged(false, Some("https")), "http");
+    }
+
+    #[test]
+    fn user_agent_is_logged() {
+        let rows = csv_rows(ok_handler, |handler| {
+            let mut headers = Headers::new();
+            headers.set(UserAgent("health-checker/1.0".into()));
+            request::get("http://127.0.0.1/", headers, handler).expect("Request failed");
+        });
+
+        assert!(rows[0].contains(",health-checker/1.0,"), "{}", rows[0]);
+    }
+
+    #[test]
+    fn referer_is_logged() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+        let mut headers = Headers::new();
+        headers.set(Referer("https://example.com/search?q=iron".into()));
+        request::get("http://127.0.0.1/", headers, &handler).expect("Request failed");
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let encoding = Encoding::default().columns(&[Column::Path, Column::Referer]);
+        let rows: Vec<_> = memory.packets().iter()
+            .map(|packet| String::from_utf8(encode_csv(packet, &encoding).unwrap()).unwrap())
+            .collect();
+        assert_eq!(rows, vec!["/,https://example.com/search?q=iron\n", "/,\n"]);
+    }
+
+    #[test]
+    fn path_and_query_are_logged_separately() {
+        let rows = csv_rows(ok_handler, |handler| {
+            request::get("http://127.0.0.1/search/all?a=1&b=2", Headers::new(), handler).expect("Request failed");
+            request::get("http://127.0.0.1/search/all", Headers::new(), handler).expect("Request failed");
+        });
+
+        assert_eq!(column(&rows[0], "path"), "/search/all");
+        assert_eq!(column(&rows[0], "query"), "a=1&b=2");
+        assert_eq!(column(&rows[1], "path"), "/search/all");
+        assert_eq!(column(&rows[1], "query"), "");
+    }
+
+    #[test]
+    fn host_is_logged() {
+        let rows = csv_rows(ok_handler, |handler| {
+            let mut headers = Headers::new();
+            headers.set(Host { hostname: "shop.example.com".into(), port: Some(8080) });
+            request::get("http://127.0.0.1/", headers, handler).expect("Request failed");
+
+            let mut headers = Headers::new();
+            headers.set(Host { hostname: "blog.example.com".into(), port: None });
+            request::get("http://127.0.0.1/", headers, handler).expect("Request failed");
+
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect("Request failed");
+        });
+
+        assert_eq!(column(&rows[0], "host"), "shop.example.com:8080");
+        assert_eq!(column(&rows[1], "host"), "blog.example.com");
+        assert_eq!(column(&rows[2], "host"), "");
+    }
+
+    #[test]
+    fn content_type_is_logged() {
+        fn json_handler(_req: &mut Request) -> IronResult<Response> {
+            Ok(Response::with((iron::status::Ok, ContentType::json().0, "{}")))
+        }
+
+        fn failing_handler(_req: &mut Request) -> IronResult<Response> {
+            let response = Response::with((iron::status::InternalServerError, ContentType::html().0));
+            Err(IronError { error: Box::new(io::Error::other("boom")), response: response })
+        }
+
+        let json_rows = csv_rows(json_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect("Request failed");
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect("Request failed");
+        });
+        let failed_rows = csv_rows(failing_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect_err("Request succeeded");
+        });
+        let plain_rows = csv_rows(ok_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect("Request failed");
+        });
+
+        assert_eq!(column(&json_rows[0], "content_type"), "application/json");
+        assert_eq!(column(&failed_rows[0], "content_type"), "text/html; charset=utf-8");
+        assert_eq!(column(&plain_rows[0], "content_type"), "");
+    }
+
+    #[test]
+    fn handler_errors_are_logged() {
+        fn failing_handler(_req: &mut Request) -> IronResult<Response> {
+            Err(IronError::new(io::Error::other("database unavailable"), iron::status::ServiceUnavailable))
+        }
+
+        let failed_rows = csv_rows(failing_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect_err("Request succeeded");
+        });
+        let ok_rows = csv_rows(ok_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect("Request failed");
+        });
+
+        assert_eq!(column(&failed_rows[0], "status"), "503");
+        assert_eq!(column(&failed_rows[0], "error"), "database unavailable");
+        assert_eq!(column(&ok_rows[0], "error"), "");
+    }
+
+    #[test]
+    fn error_response_sizes_are_logged() {
+        fn failing_handler(_req: &mut Request) -> IronResult<Response> {
+            Err(IronError::new(io::Error::other("database unavailable"),
+                               (iron::status::ServiceUnavailable, "Try again later")))
+        }
+
+        let rows = csv_rows(failing_handler, |handler| {
+            request::get("http://127.0.0.1/", Headers::new(), handler).expect_err("Request succeeded");
+        });
+
+        assert_eq!(column(&rows[0], "status"), "503");
+        assert_eq!(column(&rows[0], "response_len"), "15");
+    }
+
+    #[test]
+    fn request_body_sizes_are_logged() {
+        let rows = csv_rows(ok_handler, |handler| {
+            request::post("http://127.0.0.1/upload", Headers::new(), "twelve bytes", handler).expect("Request failed");
+        });
+
+        assert_eq!(column(&rows[0], "request_len"), "12");
+    }
+
+    #[test]
+    fn only_listed_request_headers_are_logged() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLogger::builder().log_request_headers(&["Accept", "X-Tenant"]).build(memory.clone());
+        let handler = logger.around(Box::new(ok_handler));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Accept", vec![b"text/html".to_vec()]);
+        headers.set_raw("x-tenant", vec![b"acme".to_vec()]);
+        headers.set_raw("Authorization", vec![b"Bearer secret".to_vec()]);
+        request::get("http://127.0.0.1/", headers, &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        let logged: Vec<_> = packet.request_headers().iter().map(|(k, v)| (&k[..], &v[..])).collect();
+        assert_eq!(logged, vec![("Accept", "text/html"), ("X-Tenant", "acme")]);
+
+        let row = String::from_utf8(encode_csv(packet, &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(column(&row, "request_headers"), r#"{"Accept":"text/html","X-Tenant":"acme"}"#);
+    }
+
+    #[test]
+    fn static_tags_are_attached_to_every_packet() {
+        let memory = MemoryLogger::new();
+        let logger = StatisticLogger::builder().tag("service", "api").tag("region", "eu-west-1").build(memory.clone());
+        let handler = logger.around(Box::new(ok_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.tags()["service"], "api");
+        let encoding = Encoding::default().columns(&[Column::Tags]);
+        let row = String::from_utf8(encode_csv(packet, &encoding).unwrap()).unwrap();
+        assert_eq!(row, "\"{\"\"region\"\":\"\"eu-west-1\"\",\"\"service\"\":\"\"api\"\"}\"\n");
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&json::encode(packet).unwrap()).unwrap()["tags"]["region"],
+                   "eu-west-1");
+
+        let hostname = StatisticLogger::builder().tag_hostname().build(NullLogger).capture.tags;
+        assert_eq!(hostname.get("hostname"), capture::hostname().as_ref());
+    }
+
+    #[test]
+    fn listed_response_headers_are_logged() {
+        fn cached_handler(_req: &mut Request) -> IronResult<Response> {
+            let mut response = Response::with(status::Ok);
+            response.headers.set_raw("X-Cache", vec![b"HIT".to_vec()]);
+            response.headers.set_raw("X-Internal", vec![b"node-7".to_vec()]);
+            Ok(response)
+        }
+
+        let memory = MemoryLogger::new();
+        let logger = StatisticLogger::builder().log_response_headers(&["X-Cache"]).build(memory.clone());
+        let handler = logger.around(Box::new(cached_handler));
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packet = &memory.packets()[0];
+        assert_eq!(packet.response_headers().len(), 1);
+        assert_eq!(packet.response_headers()["X-Cache"], "HIT");
+
+        let row = String::from_utf8(encode_csv(packet, &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(column(&row, "response_headers"), r#"{"X-Cache":"HIT"}"#);
+    }
+
+    #[test]
+    fn request_ids_are_reused() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+
+        let mut headers = Headers::new();
+        headers.set_raw("X-Request-Id", vec![b"upstream-1234".to_vec()]);
+        let response = request::get("http://127.0.0.1/", headers, &handler).expect("Request failed");
+        drop(handler);
+
+        assert_eq!(response.headers.get_raw("X-Request-Id"), Some(&[b"upstream-1234".to_vec()][..]));
+        assert_eq!(memory.packets()[0].request_id(), "upstream-1234");
+    }
+
+    #[test]
+    fn request_ids_are_generated() {
+        let memory = MemoryLogger::new();
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(ok_handler));
+
+        let response = request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let sent = response.headers.get_raw("X-Request-Id").expect("No request ID was sent");
+        let logged = memory.packets()[0].request_id().to_owned();
+        assert_eq!(logged.len(), 36);
+        assert_eq!(sent, &[logged.into_bytes()][..]);
+    }
+
+    #[test]
+    fn requests_pass_straight_through_when_nothing_is_logged() {
+        let memory = MemoryLogger::new();
+        let disabled = StatisticLogger::builder().enabled(false).build(memory.clone()).around(Box::new(ok_handler));
+        let null = StatisticLogger::new(MultiLogger::new().writer(NullLogger::new()).writer(NullLogger::new()))
+            .around(Box::new(ok_handler));
+        let logged = StatisticLogger::new(MultiLogger::new().writer(NullLogger::new()).writer(memory.clone()))
+            .around(Box::new(ok_handler));
+
+        for handler in &[&disabled, &null] {
+            let response = request::get("http://127.0.0.1/", Headers::new(), *handler).expect("Request failed");
+            assert_eq!(response.status, Some(iron::status::Ok));
+            assert!(response.headers.get_raw("X-Request-Id").is_some());
+
+            let mut headers = Headers::new();
+            headers.set_raw("X-Request-Id", vec![b"upstream-7".to_vec()]);
+            let response = request::get("http://127.0.0.1/", headers, *handler).expect("Request failed");
+            assert_eq!(response.headers.get_raw("X-Request-Id"), Some(&[b"upstream-7".to_vec()][..]));
+        }
+        request::get("http://127.0.0.1/", Headers::new(), &logged).expect("Request failed");
+        drop((disabled, null, logged));
+
+        assert_eq!(memory.packets().len(), 1);
+    }
+
+    #[test]
+    fn drop_newest_discards_packets_that_do_not_fit() {
+        let (paths, dropped) = overflow_with(OverflowPolicy::DropNewest);
+        assert_eq!(paths, ["1", "2"]);
+        assert_eq!(dropped, 2);
+    }
+
+    #[test]
+    fn drop_oldest_discards_queued_packets() {
+        let (paths, dropped) = overflow_with(OverflowPolicy::DropOldest);
+        assert_eq!(paths, ["1", "4"]);
+        assert_eq!(dropped, 2);
+    }
+
+    #[test]
+    fn statistics_that_cannot_be_serialized_are_a_server_error() {
+        let mut keys = BTreeMap::new();
+        keys.insert((1, 2), 3);
+        let err = json_response(&keys).unwrap_err();
+        assert_eq!(err.response.status, Some(status::InternalServerError));
+
+        let res = json_response(&json!({"requests": 1})).unwrap();
+        assert_eq!(res.headers.get::<ContentType>(), Some(&ContentType::json()));
+        assert_eq!(response::extract_body_to_string(res), r#"{"requests":1}"#);
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::{AroundMiddleware, Handler};
+
+/// Only logs the requests below some path prefixes, such as `/api`,
+/// with the wrapped logger. Other requests go straight to the handler,
+/// without being timed or creating a packet, which keeps the logger
+/// off busy routes like static assets. A prefix matches whole path
+/// segments, so `/api` matches `/api` and `/api/users` but not
+/// `/apiary`.
+pub struct PrefixLogger<M> {
+    logger: M,
+    prefixes: Vec<Vec<String>>,
+}
+
+impl<M: AroundMiddleware> PrefixLogger<M> {
+    /// Logs nothing until a prefix is added.
+    pub fn new(logger: M) -> PrefixLogger<M> {
+        PrefixLogger {
+            logger: logger,
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Also logs the requests below `prefix`.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefixes.push(prefix.split('/').filter(|s| !s.is_empty()).map(String::from).collect());
+        self
+    }
+}
+
+impl<M: AroundMiddleware> AroundMiddleware for PrefixLogger<M> {
+    fn around(self, handler: Box<Handler>) -> Box<Handler> {
+        let unlogged = Arc::new(handler);
+        let shared = unlogged.clone();
+        let logged = self.logger.around(Box::new(move |req: &mut Request| shared.handle(req)));
+
+        Box::new(PrefixHandler {
+            prefixes: self.prefixes,
+            logged: logged,
+            unlogged: unlogged,
+        })
+    }
+}
+
+struct PrefixHandler {
+    prefixes: Vec<Vec<String>>,
+    logged: Box<Handler>,
+    unlogged: Arc<Box<Handler>>,
+}
+
+impl PrefixHandler {
+    fn matches(&self, path: &[&str]) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            prefix.len() <= path.len() && path.iter().zip(prefix).all(|(path, prefix)| path == prefix)
+        })
+    }
+}
+
+impl Handler for PrefixHandler {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        if self.matches(&req.url.path()) {
+            self.logged.handle(req)
+        } else {
+            self.unlogged.handle(req)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::status;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{MemoryLogger, StatisticLogger};
+
+    #[test]
+    fn only_requests_below_a_prefix_are_logged() {
+        let memory = MemoryLogger::new();
+        let logger = PrefixLogger::new(StatisticLogger::new(memory.clone())).prefix("/api").prefix("/admin/");
+        let handler = logger.around(Box::new(|req: &mut Request| {
+            Ok(Response::with((status::Ok, req.url.path().join("/"))))
+        }));
+
+        for path in &["/static/x", "/api/x", "/apiary", "/admin", "/"] {
+            let res = request::get(&format!("http://127.0.0.1{}", path), Headers::new(), &handler).unwrap();
+            assert_eq!(response::extract_body_to_string(res), path[1..]);
+        }
+        drop(handler);
+
+        let paths: Vec<_> = memory.packets().iter().map(|packet| packet.path().to_owned()).collect();
+        assert_eq!(paths, vec!["/api/x", "/admin"]);
+    }
+}
@@ -0,0 +1,309 @@
+use std::{error, fmt};
+
+use super::{LogPacket, LogWriter, Summary};
+use super::boxed::{BoxedLogWriter, BoxedError};
+
+/// Records every packet to each of several writers, such as CSV to a
+/// file for archival and the Combined Log Format to stdout for people
+/// to read:
+///
+/// ```no_run
+/// use playground_middleware::{BackgroundLogger, CombinedLogFormatter, FileLogger, MultiLogger};
+/// use playground_middleware::{StatisticLogger, StreamLogger};
+///
+/// let stdout = StreamLogger::with_formatter(std::io::stdout(), CombinedLogFormatter::new());
+/// let logger = MultiLogger::new()
+///     .writer(FileLogger::new("access.csv").unwrap())
+///     .writer(BackgroundLogger::new(stdout, 1024).unwrap());
+/// let logger = StatisticLogger::new(logger);
+/// ```
+///
+/// Every writer is given each packet, in the same order, one writer
+/// after the other, even when an earlier writer fails. If any writer
+/// fails, all of the failures are reported together as a
+/// `MultiError`. A slow writer holds up the others unless it is
+/// wrapped in a `BackgroundLogger`; such a writer may then fall
+/// behind the others, and misses the packets its queue had no room
+/// for.
+#[derive(Default)]
+pub struct MultiLogger {
+    writers: Vec<Box<LogWriter<Error = BoxedError> + Send>>,
+}
+
+impl MultiLogger {
+    pub fn new() -> MultiLogger {
+        MultiLogger::default()
+    }
+
+    pub fn writer<L>(mut self, writer: L) -> Self
+        where L: LogWriter + Send + 'static,
+              L::Error: Send + 'static,
+    {
+        self.writers.push(Box::new(BoxedLogWriter::new(writer)));
+        self
+    }
+}
+
+impl LogWriter for MultiLogger {
+    type Error = MultiError;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let errors = self.writers.iter_mut()
+            .filter_map(|writer| writer.log(packet).err())
+            .collect();
+        MultiError::check(errors)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let errors = self.writers.iter_mut()
+            .filter_map(|writer| writer.flush().err())
+            .collect();
+        MultiError::check(errors)
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        let errors = self.writers.iter_mut()
+            .filter_map(|writer| writer.finalize(summary).err())
+            .collect();
+        MultiError::check(errors)
+    }
+
+    fn discards_everything(&self) -> bool {
+        self.writers.iter().all(|writer| writer.discards_everything())
+    }
+}
+
+type Classifier = Box<Fn(&LogPacket) -> usize + Send>;
+
+/// Records each packet to one of several writers, chosen by a
+/// classifier returning the writer's index in the order they were
+/// added, such as successes to one file and errors to another:
+///
+/// ```no_run
+/// use playground_middleware::{FileLogger, RoutingLogger};
+///
+/// let logger = RoutingLogger::new(|packet| {
+///     match packet.status() {
+///         Some(status) if !status.is_client_error() && !status.is_server_error() => 0,
+///         _ => 1,
+///     }
+/// })
+///     .writer(FileLogger::new("access.csv").unwrap())
+///     .writer(FileLogger::new("errors.csv").unwrap());
+/// ```
+///
+/// Packets classified past the last writer are discarded. Flushing
+/// flushes every writer.
+pub struct RoutingLogger {
+    classifier: Classifier,
+    writers: Vec<Box<LogWriter<Error = BoxedError> + Send>>,
+}
+
+impl RoutingLogger {
+    pub fn new<F>(classifier: F) -> RoutingLogger
+        where F: Fn(&LogPacket) -> usize + Send + 'static
+    {
+        RoutingLogger {
+            classifier: Box::new(classifier),
+            writers: Vec::new(),
+        }
+    }
+
+    pub fn writer<L>(mut self, writer: L) -> Self
+        where L: LogWriter + Send + 'static,
+              L::Error: Send + 'static,
+    {
+        self.writers.push(Box::new(BoxedLogWriter::new(writer)));
+        self
+    }
+}
+
+impl LogWriter for RoutingLogger {
+    type Error = MultiError;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let index = (self.classifier)(packet);
+        match self.writers.get_mut(index) {
+            Some(writer) => writer.log(packet).map_err(|e| MultiError(vec![e])),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let errors = self.writers.iter_mut()
+            .filter_map(|writer| writer.flush().err())
+            .collect();
+        MultiError::check(errors)
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        let errors = self.writers.iter_mut()
+            .filter_map(|writer| writer.finalize(summary).err())
+            .collect();
+        MultiError::check(errors)
+    }
+
+    fn discards_everything(&self) -> bool {
+        self.writers.iter().all(|writer| writer.discards_everything())
+    }
+}
+
+/// The failures of each writer in a `MultiLogger` or `RoutingLogger`
+/// that could not record a packet.
+#[derive(Debug)]
+pub struct MultiError(Vec<BoxedError>);
+
+impl MultiError {
+    fn check(errors: Vec<BoxedError>) -> Result<(), MultiError> {
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(MultiError(errors))
+        }
+    }
+
+    pub fn errors(&self) -> &[BoxedError] {
+        &self.0
+    }
+}
+
+impl fmt::Display for MultiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} writer(s) failed", self.0.len()));
+        for error in &self.0 {
+            try!(write!(f, "; {}", error));
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for MultiError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::{self, Write};
+
+    use iron::status;
+
+    use super::super::{clf, BackgroundLogger, Column, CombinedLogFormatter, Encoding, MemoryLogger};
+    use super::super::{StreamLogger, TimestampFormat};
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting(Arc<AtomicUsize>);
+
+    impl LogWriter for Counting {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    impl LogWriter for Failing {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            Err(io::Error::other("collector unreachable"))
+        }
+    }
+
+    #[test]
+    fn every_writer_receives_the_packet() {
+        let first = Arc::new(AtomicUsize::new(0));
+        let second = Arc::new(AtomicUsize::new(0));
+        let mut logger = MultiLogger::new()
+            .writer(Counting(first.clone()))
+            .writer(Counting(second.clone()));
+
+        logger.log(&LogPacket::stub()).unwrap();
+
+        assert_eq!(first.load(Ordering::SeqCst), 1);
+        assert_eq!(second.load(Ordering::SeqCst), 1);
+    }
+
+    /// A `Vec<u8>` that is still readable once moved into a writer.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn each_writer_gets_its_own_format() {
+        let csv = Shared::default();
+        let clf = Shared::default();
+        let encoding = Encoding::new().columns(&[Column::Method, Column::Path, Column::Status]);
+        let clf_writer = StreamLogger::with_formatter(clf.clone(), CombinedLogFormatter::common());
+        let mut logger = MultiLogger::new()
+            .writer(StreamLogger::new(csv.clone()).encoding(encoding))
+            .writer(BackgroundLogger::new(clf_writer, 16).unwrap());
+
+        logger.log(&LogPacket::stub()).unwrap();
+        let mut packet = LogPacket::stub();
+        packet.status = Some(status::NotFound);
+        logger.log(&packet).unwrap();
+        logger.flush().unwrap();
+        drop(logger);
+
+        assert_eq!(String::from_utf8(csv.0.lock().unwrap().clone()).unwrap(), "GET,/,200\nGET,/,404\n");
+        let clf = String::from_utf8(clf.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = clf.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], clf::line(&LogPacket::stub(), false, &TimestampFormat::Apache(0)).trim_end());
+        assert!(lines[1].contains(r#""GET / HTTP/1.1" 404 "#), "{}", lines[1]);
+    }
+
+    #[test]
+    fn failures_do_not_stop_later_writers() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut logger = MultiLogger::new()
+            .writer(Failing)
+            .writer(Counting(count.clone()));
+
+        let error = logger.log(&LogPacket::stub()).unwrap_err();
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(error.errors().len(), 1);
+        assert_eq!(error.to_string(), "1 writer(s) failed; collector unreachable");
+    }
+
+    #[test]
+    fn packets_are_routed_by_the_classifier() {
+        let access = MemoryLogger::new();
+        let errors = MemoryLogger::new();
+        let mut logger = RoutingLogger::new(|packet| packet.status().map_or(1, |s| s.is_server_error() as usize))
+            .writer(access.clone())
+            .writer(errors.clone());
+
+        let mut packet = LogPacket::stub();
+        logger.log(&packet).unwrap();
+        packet.status = Some(status::InternalServerError);
+        logger.log(&packet).unwrap();
+        logger.log(&packet).unwrap();
+
+        assert_eq!(access.packets().len(), 1);
+        assert_eq!(access.packets()[0].status(), Some(status::Ok));
+        assert_eq!(errors.packets().len(), 2);
+        assert_eq!(errors.packets()[0].status(), Some(status::InternalServerError));
+    }
+
+    #[test]
+    fn packets_without_a_writer_are_discarded() {
+        let mut logger = RoutingLogger::new(|_packet| 1).writer(Failing);
+
+        logger.log(&LogPacket::stub()).unwrap();
+    }
+}
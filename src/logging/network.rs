@@ -0,0 +1,178 @@
+use std::cmp;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+
+/// How network writers connect to their collector, so that a collector
+/// that hangs fails the write instead of stalling the logging thread.
+///
+/// Connecting and writing time out after 10 seconds by default. After
+/// a failed reconnect, the writer waits `reconnect_backoff` before
+/// trying again, doubling the wait after each failure up to
+/// `max_reconnect_backoff`; records logged meanwhile fail straight
+/// away. Only the write timeout applies to UDP sockets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NetworkOptions {
+    connect_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    keepalive: Option<Duration>,
+    reconnect_backoff: Duration,
+    max_reconnect_backoff: Duration,
+}
+
+impl NetworkOptions {
+    pub fn new() -> NetworkOptions {
+        NetworkOptions {
+            connect_timeout: Some(Duration::from_secs(10)),
+            write_timeout: Some(Duration::from_secs(10)),
+            keepalive: None,
+            reconnect_backoff: Duration::from_secs(1),
+            max_reconnect_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets how long to wait for a connection; `None` waits as long
+    /// as the operating system does.
+    pub fn connect_timeout(self, timeout: Option<Duration>) -> Self {
+        NetworkOptions { connect_timeout: timeout, ..self }
+    }
+
+    /// Sets how long a write may block; `None` blocks indefinitely.
+    pub fn write_timeout(self, timeout: Option<Duration>) -> Self {
+        NetworkOptions { write_timeout: timeout, ..self }
+    }
+
+    /// Sends TCP keepalive probes after the connection has been idle
+    /// for `idle`, so that a vanished collector is noticed.
+    pub fn keepalive(self, idle: Duration) -> Self {
+        NetworkOptions { keepalive: Some(idle), ..self }
+    }
+
+    pub fn reconnect_backoff(self, backoff: Duration) -> Self {
+        NetworkOptions { reconnect_backoff: backoff, ..self }
+    }
+
+    pub fn max_reconnect_backoff(self, backoff: Duration) -> Self {
+        NetworkOptions { max_reconnect_backoff: backoff, ..self }
+    }
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        NetworkOptions::new()
+    }
+}
+
+/// A TCP connection that is reopened when it breaks, backing off
+/// after failed attempts as the options say.
+pub struct Connection {
+    addrs: Vec<SocketAddr>,
+    options: NetworkOptions,
+    stream: Option<TcpStream>,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl Connection {
+    /// Connects to the first of `addrs` that accepts.
+    pub fn open(addrs: Vec<SocketAddr>, options: NetworkOptions) -> io::Result<Connection> {
+        let stream = try!(connect(&addrs, &options));
+        Ok(Connection {
+            addrs: addrs,
+            options: options,
+            stream: Some(stream),
+            backoff: options.reconnect_backoff,
+            retry_at: None,
+        })
+    }
+
+    /// Closes the connection, so the next write reconnects.
+    pub fn reset(&mut self) {
+        self.stream = None;
+    }
+
+    pub fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.stream.is_none() {
+            try!(self.reconnect());
+        }
+        let stream = self.stream.as_mut().expect("Not connected");
+        try!(stream.write_all(bytes));
+        stream.flush()
+    }
+
+    fn reconnect(&mut self) -> io::Result<()> {
+        if let Some(retry_at) = self.retry_at {
+            if Instant::now() < retry_at {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "Waiting before reconnecting"));
+            }
+        }
+
+        match connect(&self.addrs, &self.options) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.backoff = self.options.reconnect_backoff;
+                self.retry_at = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.retry_at = Some(Instant::now() + self.backoff);
+                self.backoff = cmp::min(self.backoff * 2, self.options.max_reconnect_backoff);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Applies the write timeout to a UDP socket.
+pub fn configure_udp(socket: &UdpSocket, options: &NetworkOptions) -> io::Result<()> {
+    socket.set_write_timeout(options.write_timeout)
+}
+
+fn connect(addrs: &[SocketAddr], options: &NetworkOptions) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::InvalidInput, "No address to send statistics to");
+    for addr in addrs {
+        match connect_to(*addr, options) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn connect_to(addr: SocketAddr, options: &NetworkOptions) -> io::Result<TcpStream> {
+    let socket = try!(Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP)));
+    match options.connect_timeout {
+        Some(timeout) => try!(socket.connect_timeout(&addr.into(), timeout)),
+        None => try!(socket.connect(&addr.into())),
+    }
+    try!(socket.set_write_timeout(options.write_timeout));
+    if let Some(idle) = options.keepalive {
+        try!(socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle)));
+    }
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::TcpListener;
+
+    #[test]
+    fn failed_reconnects_back_off() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let options = NetworkOptions::new().reconnect_backoff(Duration::from_secs(60));
+        let mut connection = Connection::open(vec![addr], options).unwrap();
+
+        drop(listener);
+        connection.reset();
+        assert_eq!(connection.write_all(b"a").unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+        // Waits out the backoff instead of trying again
+        let listener = TcpListener::bind(addr).unwrap();
+        assert_eq!(connection.write_all(b"a").unwrap_err().kind(), io::ErrorKind::NotConnected);
+        drop(listener);
+    }
+}
@@ -0,0 +1,30 @@
+use std::io;
+
+use super::{LogPacket, LogWriter};
+
+/// Discards every packet, for turning request logging off without
+/// removing the middleware.
+///
+/// The `StatisticLogger` recognizes it and passes requests straight
+/// to the handler without building packets, so only the idle logging
+/// thread remains.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NullLogger;
+
+impl NullLogger {
+    pub fn new() -> NullLogger {
+        NullLogger
+    }
+}
+
+impl LogWriter for NullLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn discards_everything(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,74 @@
+use iron::status::Status;
+
+use super::{LogPacket, LogWriter, Summary};
+
+/// Forwards only the packets whose response status matches a
+/// predicate, so busy endpoints can log just the interesting
+/// requests. The predicate receives `None` when no status was set.
+pub struct OnlyStatus<L> {
+    logger: L,
+    keep: Box<Fn(Option<Status>) -> bool + Send>,
+}
+
+impl<L> OnlyStatus<L> {
+    pub fn new<F>(logger: L, keep: F) -> OnlyStatus<L>
+        where F: Fn(Option<Status>) -> bool + Send + 'static
+    {
+        OnlyStatus {
+            logger: logger,
+            keep: Box::new(keep),
+        }
+    }
+
+    /// Keeps client and server errors (status 400 and above).
+    ///
+    /// Responses without a status are kept as well: they usually mean
+    /// something went wrong before a status could be chosen.
+    pub fn errors(logger: L) -> OnlyStatus<L> {
+        OnlyStatus::new(logger, |status| status.is_none_or(|s| s.to_u16() >= 400))
+    }
+}
+
+impl<L: LogWriter> LogWriter for OnlyStatus<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if (self.keep)(packet.status) {
+            self.logger.log(packet)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron::status;
+
+    use super::super::MemoryLogger;
+
+    #[test]
+    fn only_errors_are_forwarded() {
+        let memory = MemoryLogger::new();
+        let mut logger = OnlyStatus::errors(memory.clone());
+
+        for status in &[Some(status::Ok), Some(status::InternalServerError), None] {
+            let mut packet = LogPacket::stub();
+            packet.status = *status;
+            logger.log(&packet).unwrap();
+        }
+
+        let statuses: Vec<_> = memory.packets().iter().map(|p| p.status).collect();
+        assert_eq!(statuses, [Some(status::InternalServerError), None]);
+    }
+}
@@ -0,0 +1,145 @@
+//! Records requests into OpenTelemetry metrics, behind the `otel`
+//! feature.
+
+use std::io;
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+
+use super::{LogPacket, LogWriter};
+
+/// Records the duration and count of every request into OpenTelemetry
+/// instruments, which reach whatever pipeline the app configured for
+/// the meter. Measurements carry the attributes of the HTTP semantic
+/// conventions: `http.request.method`, `http.response.status_code`
+/// when the response had one, and `http.route` when a `RoutePattern`
+/// was set. Paths are never used as routes, so the number of series
+/// stays bounded.
+pub struct OtelLogger {
+    duration: Histogram<f64>,
+    requests: Counter<u64>,
+}
+
+impl OtelLogger {
+    /// Creates the `http.server.request.duration` histogram, in
+    /// seconds, and the `http.server.request.count` counter on `meter`.
+    pub fn new(meter: &Meter) -> OtelLogger {
+        OtelLogger::with_instruments(
+            meter.f64_histogram("http.server.request.duration")
+                .with_unit("s")
+                .with_description("Duration of HTTP server requests.")
+                .build(),
+            meter.u64_counter("http.server.request.count")
+                .with_unit("{request}")
+                .with_description("Number of HTTP server requests.")
+                .build(),
+        )
+    }
+
+    /// Records into instruments the app created itself.
+    pub fn with_instruments(duration: Histogram<f64>, requests: Counter<u64>) -> OtelLogger {
+        OtelLogger {
+            duration: duration,
+            requests: requests,
+        }
+    }
+}
+
+fn attributes(packet: &LogPacket) -> Vec<KeyValue> {
+    let mut attributes = vec![KeyValue::new("http.request.method", packet.method.to_string())];
+    if let Some(status) = packet.status {
+        attributes.push(KeyValue::new("http.response.status_code", i64::from(status.to_u16())));
+    }
+    if let Some(route) = packet.route() {
+        attributes.push(KeyValue::new("http.route", route.to_owned()));
+    }
+    attributes
+}
+
+impl LogWriter for OtelLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let attributes = attributes(packet);
+        let seconds = packet.timing.as_secs() as f64 + packet.timing.subsec_nanos() as f64 / 1e9;
+        for _ in 0..packet.count {
+            self.duration.record(seconds, &attributes);
+        }
+        self.requests.add(u64::from(packet.count), &attributes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate opentelemetry_sdk;
+
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use iron::method::Method;
+    use opentelemetry::metrics::MeterProvider;
+
+    use self::opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use self::opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+    type Attributes = BTreeMap<String, String>;
+
+    fn attributes<'a, I>(attributes: I) -> Attributes
+        where I: Iterator<Item = &'a KeyValue>
+    {
+        attributes.map(|kv| (kv.key.to_string(), kv.value.to_string())).collect()
+    }
+
+    fn expected(pairs: &[(&str, &str)]) -> Attributes {
+        pairs.iter().map(|&(key, value)| (key.to_owned(), value.to_owned())).collect()
+    }
+
+    #[test]
+    fn requests_are_recorded_with_semantic_attributes() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let mut logger = OtelLogger::new(&provider.meter("playground-middleware"));
+
+        let mut routed = LogPacket::stub();
+        routed.route = Some("/users/:id".into());
+        routed.count = 2;
+        logger.log(&routed).unwrap();
+        let mut unrouted = LogPacket::stub();
+        unrouted.method = Method::Post;
+        unrouted.status = None;
+        logger.log(&unrouted).unwrap();
+        provider.force_flush().unwrap();
+
+        let mut durations = BTreeMap::new();
+        let mut requests = BTreeMap::new();
+        let metrics = exporter.get_finished_metrics().unwrap();
+        for metric in metrics.iter().flat_map(|resource| resource.scope_metrics()).flat_map(|scope| scope.metrics()) {
+            match (metric.name(), metric.data()) {
+                ("http.server.request.duration", &AggregatedMetrics::F64(MetricData::Histogram(ref histogram))) => {
+                    assert_eq!(metric.unit(), "s");
+                    for point in histogram.data_points() {
+                        durations.insert(attributes(point.attributes()), (point.count(), point.sum()));
+                    }
+                }
+                ("http.server.request.count", &AggregatedMetrics::U64(MetricData::Sum(ref sum))) => {
+                    for point in sum.data_points() {
+                        requests.insert(attributes(point.attributes()), point.value());
+                    }
+                }
+                (name, _) => panic!("Unexpected metric {}", name),
+            }
+        }
+
+        let routed = expected(&[("http.request.method", "GET"), ("http.response.status_code", "200"),
+                                ("http.route", "/users/:id")]);
+        let unrouted = expected(&[("http.request.method", "POST")]);
+        assert_eq!(durations.len(), 2);
+        assert_eq!(durations[&routed], (2, 0.003));
+        assert_eq!(durations[&unrouted], (1, 0.0015));
+        assert_eq!(requests, vec![(routed, 2), (unrouted, 1)].into_iter().collect());
+    }
+}
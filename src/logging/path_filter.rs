@@ -0,0 +1,70 @@
+use regex::{self, RegexSet};
+
+/// Chooses which requests are logged by matching their path, without
+/// the query, against a set of regular expressions.
+///
+/// Patterns are not anchored, so `^/health$` only matches the health
+/// check itself while `^/static/` matches everything below it.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    patterns: RegexSet,
+    include: bool,
+}
+
+impl PathFilter {
+    /// Logs every request except those whose path matches one of the
+    /// patterns.
+    pub fn exclude<I, S>(patterns: I) -> Result<PathFilter, regex::Error>
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>,
+    {
+        Ok(PathFilter {
+            patterns: try!(RegexSet::new(patterns)),
+            include: false,
+        })
+    }
+
+    /// Only logs the requests whose path matches one of the patterns.
+    pub fn include<I, S>(patterns: I) -> Result<PathFilter, regex::Error>
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str>,
+    {
+        Ok(PathFilter {
+            patterns: try!(RegexSet::new(patterns)),
+            include: true,
+        })
+    }
+
+    /// Whether a request for `path` should be logged.
+    pub fn allows(&self, path: &str) -> bool {
+        self.patterns.is_match(path) == self.include
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn excluded_paths_are_not_allowed() {
+        let filter = PathFilter::exclude(["^/health$", "^/metrics"]).unwrap();
+
+        assert!(!filter.allows("/health"));
+        assert!(!filter.allows("/metrics/latency"));
+        assert!(filter.allows("/api/x"));
+        assert!(filter.allows("/api/health"));
+    }
+
+    #[test]
+    fn only_included_paths_are_allowed() {
+        let filter = PathFilter::include(["^/api/"]).unwrap();
+
+        assert!(filter.allows("/api/x"));
+        assert!(!filter.allows("/health"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(PathFilter::exclude(["("]).is_err());
+    }
+}
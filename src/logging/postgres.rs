@@ -0,0 +1,171 @@
+use std::mem;
+
+use postgres::types::ToSql;
+use postgres::{Client, Error, NoTls};
+
+use super::{LogPacket, LogWriter};
+
+const CREATE_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS requests (
+        url TEXT NOT NULL,
+        method TEXT NOT NULL,
+        ip TEXT NOT NULL,
+        status INTEGER,
+        start DOUBLE PRECISION NOT NULL,
+        timing_ns BIGINT NOT NULL
+    )";
+
+const INSERT: &str = "INSERT INTO requests (url, method, ip, status, start, timing_ns) VALUES ";
+const COLUMNS: usize = 6;
+// PostgreSQL allows at most 65535 parameters per statement
+const MAX_ROWS_PER_INSERT: usize = 65535 / COLUMNS;
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+struct Row {
+    url: String,
+    method: String,
+    ip: String,
+    status: Option<i32>,
+    start: f64,
+    timing_ns: i64,
+}
+
+/// Records statistics as rows of a `requests` table in PostgreSQL,
+/// creating the table if needed.
+///
+/// Rows are held in memory and inserted with a single multi-row
+/// `INSERT` when the logger is flushed, which the logging thread does
+/// after each batch; see `StatisticLogger::with_batching`. A batch is
+/// also inserted once 1000 rows are waiting, and when the logger is
+/// dropped. If an insert fails, its rows are discarded.
+pub struct PostgresLogger {
+    client: Client,
+    batch_size: usize,
+    pending: Vec<Row>,
+}
+
+impl PostgresLogger {
+    /// Connects without TLS using a connection string like
+    /// `host=localhost user=postgres`.
+    pub fn new(params: &str) -> Result<PostgresLogger, Error> {
+        PostgresLogger::from_client(try!(Client::connect(params, NoTls)))
+    }
+
+    pub fn from_client(mut client: Client) -> Result<PostgresLogger, Error> {
+        try!(client.batch_execute(CREATE_TABLE));
+        Ok(PostgresLogger {
+            client: client,
+            batch_size: DEFAULT_BATCH_SIZE,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Inserts as soon as `size` rows are waiting, even before the
+    /// next flush.
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    fn insert(&mut self) -> Result<(), Error> {
+        let rows = mem::take(&mut self.pending);
+        for chunk in rows.chunks(MAX_ROWS_PER_INSERT) {
+            try!(self.insert_rows(chunk));
+        }
+        Ok(())
+    }
+
+    fn insert_rows(&mut self, rows: &[Row]) -> Result<(), Error> {
+        let mut statement = String::from(INSERT);
+        let mut params: Vec<&(ToSql + Sync)> = Vec::with_capacity(rows.len() * COLUMNS);
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                statement.push_str(", ");
+            }
+            let first = i * COLUMNS;
+            statement.push_str(&format!("(${}, ${}, ${}, ${}, ${}, ${})",
+                                        first + 1, first + 2, first + 3, first + 4, first + 5, first + 6));
+            params.push(&row.url);
+            params.push(&row.method);
+            params.push(&row.ip);
+            params.push(&row.status);
+            params.push(&row.start);
+            params.push(&row.timing_ns);
+        }
+
+        try!(self.client.execute(&statement[..], &params));
+        Ok(())
+    }
+}
+
+impl LogWriter for PostgresLogger {
+    type Error = Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let start = super::since_epoch(packet.start);
+        self.pending.push(Row {
+            url: packet.url.to_string(),
+            method: packet.method.to_string(),
+            ip: super::format_ip(packet),
+            status: packet.status.map(|s| s.to_u16() as i32),
+            start: start.as_secs() as f64 + start.subsec_nanos() as f64 / 1e9,
+            timing_ns: packet.timing.as_secs() as i64 * 1_000_000_000 + packet.timing.subsec_nanos() as i64,
+        });
+
+        if self.pending.len() >= self.batch_size {
+            try!(self.insert());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.insert()
+    }
+}
+
+impl Drop for PostgresLogger {
+    fn drop(&mut self) {
+        if let Err(e) = self.insert() {
+            error!("Unable to insert request log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+
+    /// Runs against the database in `POSTGRES_TEST_URL`, such as
+    /// `host=localhost user=postgres dbname=playground_test`, and is
+    /// skipped when that is not set.
+    #[test]
+    fn packets_are_inserted_in_batches() {
+        let params = match env::var("POSTGRES_TEST_URL") {
+            Ok(params) => params,
+            Err(_) => return,
+        };
+        let mut client = Client::connect(&params, NoTls).unwrap();
+        client.batch_execute("DROP TABLE IF EXISTS requests").unwrap();
+
+        let mut logger = PostgresLogger::new(&params).unwrap().batch_size(2);
+        for _ in 0..3 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        let count: i64 = client.query_one("SELECT count(*) FROM requests", &[]).unwrap().get(0);
+        assert_eq!(count, 2);
+
+        logger.flush().unwrap();
+        let rows = client.query("SELECT url, method, ip, status, start, timing_ns FROM requests", &[]).unwrap();
+        assert_eq!(rows.len(), 3);
+        let row = &rows[2];
+        assert_eq!(row.get::<_, String>(0), "http://127.0.0.1/");
+        assert_eq!(row.get::<_, String>(1), "GET");
+        assert_eq!(row.get::<_, String>(2), "127.0.0.1:3000");
+        assert_eq!(row.get::<_, Option<i32>>(3), Some(200));
+        assert_eq!(row.get::<_, f64>(4), 1_500_000_000.0);
+        assert_eq!(row.get::<_, i64>(5), 1_500_000);
+    }
+}
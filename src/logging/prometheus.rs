@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use iron::prelude::*;
+use iron::{status, Handler};
+use mime::Mime;
+
+use super::{lock, status_class, LogPacket, LogWriter};
+
+/// The default Prometheus client histogram buckets, in seconds.
+const DEFAULT_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Counts requests and records their latency in a histogram, both
+/// labeled by status class (`2xx`, `4xx`, ...). Clones share the same
+/// metrics; use `handler` to expose them to Prometheus.
+#[derive(Debug, Clone)]
+pub struct PrometheusRecorder {
+    buckets: Arc<Vec<f64>>,
+    series: Arc<Mutex<BTreeMap<&'static str, Series>>>,
+}
+
+#[derive(Debug)]
+struct Series {
+    // Not cumulative; the exposition format sums them when rendering
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl PrometheusRecorder {
+    pub fn new() -> PrometheusRecorder {
+        PrometheusRecorder::with_buckets(DEFAULT_BUCKETS.to_vec())
+    }
+
+    /// Uses the given upper bounds, in seconds, for the latency
+    /// histogram buckets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any bound is NaN.
+    pub fn with_buckets(mut buckets: Vec<f64>) -> PrometheusRecorder {
+        buckets.sort_by(|a, b| a.partial_cmp(b).expect("Histogram buckets must be numbers"));
+
+        PrometheusRecorder {
+            buckets: Arc::new(buckets),
+            series: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// A handler serving the metrics in the Prometheus text
+    /// exposition format, usually mounted at `/metrics`.
+    pub fn handler(&self) -> PrometheusHandler {
+        PrometheusHandler(self.clone())
+    }
+
+    /// The current metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let series = lock(&self.series);
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Total HTTP requests handled.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for (class, s) in series.iter() {
+            let _ = writeln!(out, "http_requests_total{{status_class=\"{}\"}} {}", class, s.count);
+        }
+
+        out.push_str("# HELP http_request_duration_seconds Time spent handling HTTP requests.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (class, s) in series.iter() {
+            let mut cumulative = 0;
+            for (bound, count) in self.buckets.iter().zip(&s.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "http_request_duration_seconds_bucket{{status_class=\"{}\",le=\"{}\"}} {}",
+                                 class, bound, cumulative);
+            }
+            let _ = writeln!(out, "http_request_duration_seconds_bucket{{status_class=\"{}\",le=\"+Inf\"}} {}",
+                             class, s.count);
+            let _ = writeln!(out, "http_request_duration_seconds_sum{{status_class=\"{}\"}} {}", class, s.sum);
+            let _ = writeln!(out, "http_request_duration_seconds_count{{status_class=\"{}\"}} {}", class, s.count);
+        }
+
+        out
+    }
+}
+
+impl Default for PrometheusRecorder {
+    fn default() -> PrometheusRecorder {
+        PrometheusRecorder::new()
+    }
+}
+
+impl LogWriter for PrometheusRecorder {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let timing = packet.timing;
+        let secs = timing.as_secs() as f64 + timing.subsec_nanos() as f64 / 1e9;
+
+        let mut series = lock(&self.series);
+        let bucket_count = self.buckets.len();
+        let s = series.entry(status_class(packet.status)).or_insert_with(|| Series {
+            buckets: vec![0; bucket_count],
+            sum: 0.0,
+            count: 0,
+        });
+
+        if let Some(index) = self.buckets.iter().position(|&bound| secs <= bound) {
+            s.buckets[index] += 1;
+        }
+        s.sum += secs;
+        s.count += 1;
+
+        Ok(())
+    }
+}
+
+/// Serves the metrics of a `PrometheusRecorder`.
+pub struct PrometheusHandler(PrometheusRecorder);
+
+impl Handler for PrometheusHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        let content_type: Mime = "text/plain; version=0.0.4".parse()
+            .expect("Unable to create Prometheus content type");
+        Ok(Response::with((status::Ok, content_type, self.0.render())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::time::Duration;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    #[test]
+    fn metrics_are_exposed_by_status_class() {
+        let mut recorder = PrometheusRecorder::with_buckets(vec![0.01, 0.1]);
+
+        let mut packet = LogPacket::stub();
+        packet.timing = Duration::from_millis(5);
+        recorder.log(&packet).unwrap();
+        packet.timing = Duration::from_millis(50);
+        recorder.log(&packet).unwrap();
+        packet.status = Some(status::InternalServerError);
+        recorder.log(&packet).unwrap();
+
+        let res = request::get("http://127.0.0.1/metrics", Headers::new(), &recorder.handler()).unwrap();
+        let body = response::extract_body_to_string(res);
+
+        assert!(body.contains("# TYPE http_requests_total counter\n"));
+        assert!(body.contains("http_requests_total{status_class=\"2xx\"} 2\n"));
+        assert!(body.contains("http_requests_total{status_class=\"5xx\"} 1\n"));
+        assert!(body.contains("# TYPE http_request_duration_seconds histogram\n"));
+        assert!(body.contains("http_request_duration_seconds_bucket{status_class=\"2xx\",le=\"0.01\"} 1\n"));
+        assert!(body.contains("http_request_duration_seconds_bucket{status_class=\"2xx\",le=\"0.1\"} 2\n"));
+        assert!(body.contains("http_request_duration_seconds_bucket{status_class=\"2xx\",le=\"+Inf\"} 2\n"));
+        assert!(body.contains("http_request_duration_seconds_count{status_class=\"5xx\"} 1\n"));
+    }
+}
@@ -0,0 +1,75 @@
+use iron;
+use url::form_urlencoded;
+
+/// How the query string of each request is recorded, both in the URL
+/// and on its own. Query strings can hold secrets like access tokens,
+/// and make URLs harder to aggregate.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum QueryLogging {
+    /// Log queries as they are.
+    #[default]
+    Keep,
+    /// Drop the query, logging only the path.
+    Strip,
+    /// Replace the values of the named parameters with `***`, keeping
+    /// other parameters as they were sent.
+    Redact(Vec<String>),
+}
+
+impl QueryLogging {
+    pub fn apply(&self, url: &iron::Url) -> iron::Url {
+        let mut url = url.clone();
+        match *self {
+            QueryLogging::Keep => {}
+            QueryLogging::Strip => url.as_mut().set_query(None),
+            QueryLogging::Redact(ref names) => {
+                let redacted = url.query().map(|query| redact(query, names));
+                url.as_mut().set_query(redacted.as_ref().map(|q| &q[..]));
+            }
+        }
+        url
+    }
+}
+
+/// Replaces the values of the named parameters with `***`.
+pub fn redact(query: &str, names: &[String]) -> String {
+    query.split('&')
+        .map(|pair| {
+            let name = form_urlencoded::parse(pair.as_bytes()).next().map(|(name, _)| name);
+            match name {
+                Some(ref name) if names.iter().any(|n| n == name) => {
+                    let raw_name = pair.split('=').next().unwrap_or(pair);
+                    format!("{}=***", raw_name)
+                }
+                _ => pair.into(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn applied(logging: QueryLogging, url: &str) -> String {
+        logging.apply(&iron::Url::parse(url).unwrap()).to_string()
+    }
+
+    #[test]
+    fn named_parameters_are_redacted() {
+        let logging = QueryLogging::Redact(vec!["token".into(), "api key".into()]);
+
+        assert_eq!(applied(logging.clone(), "http://127.0.0.1/a?token=abc&page=2"),
+                   "http://127.0.0.1/a?token=***&page=2");
+        assert_eq!(applied(logging.clone(), "http://127.0.0.1/a?q=a%20b&api+key=x&token"),
+                   "http://127.0.0.1/a?q=a%20b&api+key=***&token=***");
+        assert_eq!(applied(logging, "http://127.0.0.1/a"), "http://127.0.0.1/a");
+    }
+
+    #[test]
+    fn queries_can_be_stripped() {
+        assert_eq!(applied(QueryLogging::Strip, "http://127.0.0.1/a?token=abc"), "http://127.0.0.1/a");
+        assert_eq!(applied(QueryLogging::Keep, "http://127.0.0.1/a?token=abc"), "http://127.0.0.1/a?token=abc");
+    }
+}
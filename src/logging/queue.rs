@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use iron::prelude::*;
+use iron::Handler;
+
+use super::{json_response, LoggerHandle};
+
+/// How well the logging thread keeps up, to alarm on before packets
+/// are dropped.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct QueueStats {
+    /// The packets waiting to be written.
+    pub depth: usize,
+    /// The most packets that can wait.
+    pub capacity: usize,
+    /// The packets discarded so far; see `DroppedPackets`.
+    pub dropped: usize,
+}
+
+/// How long requests waited to queue their packets, for the channel's
+/// lock or, under `OverflowPolicy::Block`, for room in the queue, to
+/// tell when logging slows down serving. Only waits longer than the
+/// threshold are counted; see `StatisticLoggerBuilder::contention_threshold`.
+/// They are not recorded in the packets, as logging them would add to
+/// the wait.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Contention {
+    /// The packets that waited longer than the threshold.
+    pub slow_sends: u64,
+    /// How long those packets waited in total.
+    pub blocked: Duration,
+    /// The longest any of them waited.
+    pub max_blocked: Duration,
+}
+
+/// Where the requests add up their waits.
+#[derive(Debug, Default)]
+pub struct ContentionCounter {
+    slow_sends: AtomicU64,
+    blocked_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl ContentionCounter {
+    pub fn record(&self, waited: Duration, threshold: Duration) {
+        if waited <= threshold {
+            return;
+        }
+        let nanos = waited.as_nanos() as u64;
+        self.slow_sends.fetch_add(1, Ordering::Relaxed);
+        self.blocked_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Contention {
+        Contention {
+            slow_sends: self.slow_sends.load(Ordering::Relaxed),
+            blocked: Duration::from_nanos(self.blocked_nanos.load(Ordering::Relaxed)),
+            max_blocked: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Serves the `QueueStats` of a logger as a JSON object like
+/// `{"depth":3,"capacity":1024,"dropped":0}`.
+pub struct QueueStatsHandler(LoggerHandle);
+
+pub fn handler(handle: LoggerHandle) -> QueueStatsHandler {
+    QueueStatsHandler(handle)
+}
+
+impl Handler for QueueStatsHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&self.0.queue_stats())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::io;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::sync::Mutex;
+
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{LogPacket, LogWriter, OverflowPolicy, StatisticLogger};
+
+    /// Tells when it received its first packet, then waits to be
+    /// released before writing it.
+    struct Blocked {
+        started: Sender<()>,
+        release: Mutex<Receiver<()>>,
+    }
+
+    impl LogWriter for Blocked {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            let _ = self.started.send(());
+            let _ = self.release.lock().unwrap().recv();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn the_depth_of_a_backed_up_queue_is_reported() {
+        let (started, has_started) = mpsc::channel();
+        let (release, released) = mpsc::channel();
+        let writer = Blocked {
+            started: started,
+            release: Mutex::new(released),
+        };
+        let logger = StatisticLogger::builder().capacity(4).overflow_policy(OverflowPolicy::DropNewest).build(writer);
+        let handle = logger.handle();
+        let handler = logger.around(Box::new(|_req: &mut Request| Ok(Response::with(status::Ok))));
+
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        has_started.recv().unwrap();
+        for _ in 0..5 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+
+        assert_eq!(handle.queue_stats(), QueueStats { depth: 4, capacity: 4, dropped: 1 });
+        let res = request::get("http://127.0.0.1/queue", Headers::new(), &handle.queue_stats_handler()).unwrap();
+        assert_eq!(response::extract_body_to_string(res), r#"{"depth":4,"capacity":4,"dropped":1}"#);
+
+        drop(release);
+        drop(handler);
+        assert_eq!(handle.queue_stats().depth, 0);
+    }
+}
@@ -0,0 +1,162 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use super::{lock, since_epoch, Aggregator, Clock, LogPacket, LogWriter, SystemClock};
+
+/// The number of complete seconds averaged over.
+const WINDOW: u64 = 60;
+
+/// Tracks the request rate, counting requests per second of their
+/// start time over the last minute. Clones share the same counts.
+#[derive(Clone)]
+pub struct RequestRate {
+    // One slot per second, including the one in progress, each
+    // holding the second it counts and the requests in it
+    slots: Arc<Mutex<Vec<(u64, u64)>>>,
+    clock: Arc<Clock>,
+}
+
+impl RequestRate {
+    pub fn new() -> RequestRate {
+        RequestRate::with_clock(SystemClock)
+    }
+
+    /// Reads the current time from `clock` instead of the system's
+    /// clock.
+    pub fn with_clock<C>(clock: C) -> RequestRate
+        where C: Clock + 'static
+    {
+        RequestRate {
+            slots: Arc::new(Mutex::new(vec![(0, 0); WINDOW as usize + 1])),
+            clock: Arc::new(clock),
+        }
+    }
+
+    /// The requests started during the last complete second.
+    pub fn current(&self) -> f64 {
+        let now = since_epoch(self.clock.now_system()).as_secs();
+        self.count(now.saturating_sub(1)..now) as f64
+    }
+
+    /// The mean requests per second over the last minute, not counting
+    /// the second in progress.
+    pub fn average(&self) -> f64 {
+        let now = since_epoch(self.clock.now_system()).as_secs();
+        self.count(now.saturating_sub(WINDOW)..now) as f64 / WINDOW as f64
+    }
+
+    fn count(&self, seconds: ::std::ops::Range<u64>) -> u64 {
+        lock(&self.slots).iter()
+            .filter(|&&(second, _)| seconds.start <= second && second < seconds.end)
+            .map(|&(_, count)| count)
+            .sum()
+    }
+}
+
+impl Default for RequestRate {
+    fn default() -> RequestRate {
+        RequestRate::new()
+    }
+}
+
+impl Aggregator for RequestRate {
+    fn observe(&self, packet: &LogPacket) {
+        let second = since_epoch(packet.start).as_secs();
+        let mut slots = lock(&self.slots);
+        let slot = &mut slots[(second % (WINDOW + 1)) as usize];
+
+        if slot.0 < second {
+            *slot = (second, 0);
+        }
+        // Packets older than the slot's second are outside the window
+        if slot.0 == second {
+            slot.1 += 1;
+        }
+    }
+
+    /// The rates, like `{"current":12.0,"average":9.5}`.
+    fn snapshot(&self) -> Value {
+        json!({"current": self.current(), "average": self.average()})
+    }
+}
+
+impl LogWriter for RequestRate {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    struct FixedClock(SystemTime);
+
+    impl Clock for FixedClock {
+        fn now_system(&self) -> SystemTime {
+            self.0
+        }
+
+        fn now_instant(&self) -> Instant {
+            Instant::now()
+        }
+    }
+
+    fn at(second: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_500_000_000 + second)
+    }
+
+    fn rate_at(rate: &RequestRate, second: u64) -> RequestRate {
+        RequestRate {
+            slots: rate.slots.clone(),
+            clock: Arc::new(FixedClock(at(second))),
+        }
+    }
+
+    #[test]
+    fn rates_are_computed_from_complete_seconds() {
+        let mut rate = RequestRate::with_clock(FixedClock(at(0)));
+        let mut packet = LogPacket::stub();
+        for second in 0..30 {
+            for _ in 0..4 {
+                packet.start = at(second) + Duration::from_millis(250);
+                rate.log(&packet).unwrap();
+            }
+        }
+        // Requests in the second in progress are not counted yet
+        packet.start = at(30);
+        rate.log(&packet).unwrap();
+
+        let now = rate_at(&rate, 30);
+        assert_eq!(now.current(), 4.0);
+        assert_eq!(now.average(), 2.0);
+
+        let later = rate_at(&rate, 75);
+        assert_eq!(later.current(), 0.0);
+        assert_eq!(later.average(), (15 * 4 + 1) as f64 / 60.0);
+    }
+
+    #[test]
+    fn old_buckets_are_reused() {
+        let mut rate = RequestRate::with_clock(FixedClock(at(0)));
+        let mut packet = LogPacket::stub();
+        packet.start = at(5);
+        rate.log(&packet).unwrap();
+        packet.start = at(5 + WINDOW + 1);
+        rate.log(&packet).unwrap();
+        rate.log(&packet).unwrap();
+        // Too old for the window, so dropped rather than counted
+        packet.start = at(5);
+        rate.log(&packet).unwrap();
+
+        assert_eq!(rate_at(&rate, 7 + WINDOW).current(), 2.0);
+        assert_eq!(rate_at(&rate, 7 + WINDOW).average(), 2.0 / 60.0);
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Clock, DroppedPackets, LogPacket, LogWriter, Summary, SystemClock};
+
+/// What a `RateLimitLogger` does with a packet over the rate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RateLimitPolicy {
+    /// Discard it, counting it as dropped.
+    #[default]
+    Drop,
+    /// Wait until it fits in the rate. The logging thread slows down,
+    /// so requests wait too once the queue fills up.
+    Block,
+}
+
+/// Passes at most a fixed number of packets per second to `logger`,
+/// to protect a sink that cannot take bursts. Unlike sampling, which
+/// keeps a share of packets at random, the limit is enforced exactly:
+/// a token bucket holding up to `burst` packets, refilled at the
+/// rate, lets packets through while it is not empty.
+pub struct RateLimitLogger<L> {
+    logger: L,
+    per_second: f64,
+    burst: f64,
+    policy: RateLimitPolicy,
+    clock: Arc<Clock>,
+    tokens: f64,
+    refilled: Option<Instant>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl<L> RateLimitLogger<L> {
+    /// Lets `per_second` packets through each second, all at once if
+    /// they come in a burst.
+    pub fn new(logger: L, per_second: u32) -> RateLimitLogger<L> {
+        let per_second = f64::from(per_second.max(1));
+        RateLimitLogger {
+            logger: logger,
+            per_second: per_second,
+            burst: per_second,
+            policy: RateLimitPolicy::default(),
+            clock: Arc::new(SystemClock),
+            tokens: per_second,
+            refilled: None,
+            dropped: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// The most packets let through at once after a quiet spell.
+    /// Defaults to the rate per second.
+    pub fn burst(self, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        RateLimitLogger { burst: burst, tokens: burst, ..self }
+    }
+
+    pub fn policy(self, policy: RateLimitPolicy) -> Self {
+        RateLimitLogger { policy: policy, ..self }
+    }
+
+    /// Refills the bucket by the time read from `clock` instead of the
+    /// system's clock.
+    pub fn clock<C>(self, clock: C) -> Self
+        where C: Clock + 'static
+    {
+        RateLimitLogger { clock: Arc::new(clock), ..self }
+    }
+
+    /// Counts the packets discarded for going over the rate. Take it
+    /// before the logger is moved to the logging thread.
+    pub fn dropped(&self) -> DroppedPackets {
+        DroppedPackets(self.dropped.clone())
+    }
+
+    /// Takes a token for the next packet, returning whether it may be
+    /// written.
+    fn acquire(&mut self) -> bool {
+        let now = self.clock.now_instant();
+        let refilled = self.refilled.unwrap_or(now);
+        let elapsed = now.saturating_duration_since(refilled);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst);
+        self.refilled = Some(now.max(refilled));
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+
+        match self.policy {
+            RateLimitPolicy::Drop => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            RateLimitPolicy::Block => {
+                // The time slept refills exactly the token this packet
+                // takes, whatever the clock says afterwards
+                let wait = Duration::from_secs_f64((1.0 - self.tokens) / self.per_second);
+                thread::sleep(wait);
+                self.tokens = 0.0;
+                self.refilled = self.refilled.map(|refilled| refilled + wait);
+                true
+            }
+        }
+    }
+}
+
+impl<L: LogWriter> LogWriter for RateLimitLogger<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if self.acquire() {
+            self.logger.log(packet)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Mutex;
+    use std::time::SystemTime;
+
+    use super::super::MemoryLogger;
+
+    #[derive(Clone)]
+    struct FakeClock {
+        base: Instant,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl Clock for FakeClock {
+        fn now_system(&self) -> SystemTime {
+            ::std::time::UNIX_EPOCH + *self.elapsed.lock().unwrap()
+        }
+
+        fn now_instant(&self) -> Instant {
+            self.base + *self.elapsed.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn bursts_are_capped_at_the_rate_per_second() {
+        let clock = FakeClock { base: Instant::now(), elapsed: Arc::default() };
+        let memory = MemoryLogger::new();
+        let mut logger = RateLimitLogger::new(memory.clone(), 10).clock(clock.clone());
+        let dropped = logger.dropped();
+
+        for _ in 0..25 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert_eq!(memory.packets().len(), 10);
+        assert_eq!(dropped.count(), 15);
+
+        *clock.elapsed.lock().unwrap() += Duration::from_millis(500);
+        for _ in 0..25 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert_eq!(memory.packets().len(), 15);
+
+        // A quiet spell refills no more than the burst
+        *clock.elapsed.lock().unwrap() += Duration::from_secs(60);
+        for _ in 0..25 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert_eq!(memory.packets().len(), 25);
+        assert_eq!(dropped.count(), 50);
+    }
+
+    #[test]
+    fn blocking_waits_for_room_instead_of_dropping() {
+        let memory = MemoryLogger::new();
+        let mut logger = RateLimitLogger::new(memory.clone(), 100).burst(1).policy(RateLimitPolicy::Block);
+        let dropped = logger.dropped();
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40), "{:?}", start.elapsed());
+        assert_eq!(memory.packets().len(), 5);
+        assert_eq!(dropped.count(), 0);
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use csv;
+use iron;
+use iron::method::Method;
+use iron::status::Status;
+use serde_json;
+
+use super::encoding;
+use super::{Column, Encoding};
+
+/// Reads back the CSV written by `FileLogger` and the other CSV
+/// writers, so tools do not have to track the column layout
+/// themselves. Columns are taken from the header row; those this
+/// version does not know are skipped.
+///
+/// ```
+/// use playground_middleware::{Column, LogReader};
+///
+/// let csv = "status,timing,path\n404,0.001500000,/missing\n";
+/// let record = LogReader::new(csv.as_bytes()).unwrap().next().unwrap().unwrap();
+///
+/// assert_eq!(record.status().map(|status| status.to_u16()), Some(404));
+/// assert_eq!(record.get(Column::Path), Some("/missing"));
+/// ```
+pub struct LogReader<R> {
+    records: csv::StringRecordsIntoIter<R>,
+    columns: Vec<Option<Column>>,
+    encoding: Encoding,
+}
+
+impl<R: io::Read> LogReader<R> {
+    /// Reads timings, times and statuses as the default `Encoding`
+    /// writes them.
+    pub fn new(reader: R) -> csv::Result<LogReader<R>> {
+        LogReader::with_encoding(reader, Encoding::default())
+    }
+
+    /// Reads timings, times and statuses as `encoding` writes them.
+    /// Its columns are ignored in favor of the header row.
+    pub fn with_encoding(reader: R, encoding: Encoding) -> csv::Result<LogReader<R>> {
+        let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader(reader);
+        let columns = try!(reader.headers()).iter().map(Column::from_name).collect();
+        Ok(LogReader {
+            records: reader.into_records(),
+            columns: columns,
+            encoding: encoding,
+        })
+    }
+
+    /// Reads rows without a header row, such as those of a
+    /// `StreamLogger`, taking the columns from `encoding` as well.
+    pub fn headerless(reader: R, encoding: Encoding) -> LogReader<R> {
+        let reader = csv::ReaderBuilder::new().has_headers(false).from_reader(reader);
+        LogReader {
+            records: reader.into_records(),
+            columns: encoding::columns(&encoding).iter().cloned().map(Some).collect(),
+            encoding: encoding,
+        }
+    }
+}
+
+impl<R: io::Read> Iterator for LogReader<R> {
+    type Item = csv::Result<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        let values = self.columns.iter()
+            .zip(record.iter())
+            .filter_map(|(column, value)| column.map(|column| (column, value.to_owned())))
+            .collect();
+        Some(Ok(LogRecord {
+            values: values,
+            encoding: self.encoding.clone(),
+        }))
+    }
+}
+
+/// A row read by a `LogReader`. Values the writer left empty, and
+/// columns the row does not have, are `None`; so are values that
+/// cannot be parsed.
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    values: Vec<(Column, String)>,
+    encoding: Encoding,
+}
+
+impl LogRecord {
+    /// The value of `column` as written.
+    pub fn get(&self, column: Column) -> Option<&str> {
+        self.values.iter()
+            .find(|&&(c, _)| c == column)
+            .map(|(_, value)| &value[..])
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The sequence number; a jump between rows means packets were lost.
+    pub fn seq(&self) -> Option<u64> {
+        self.get(Column::Seq).and_then(|seq| seq.parse().ok())
+    }
+
+    pub fn url(&self) -> Option<iron::Url> {
+        self.get(Column::Url).and_then(|url| iron::Url::parse(url).ok())
+    }
+
+    pub fn method(&self) -> Option<Method> {
+        self.get(Column::Method).and_then(|method| method.parse().ok())
+    }
+
+    pub fn ip(&self) -> Option<SocketAddr> {
+        self.get(Column::Ip).and_then(|ip| ip.parse().ok())
+    }
+
+    /// The status, unless the writer wrote its missing status.
+    pub fn status(&self) -> Option<Status> {
+        self.values.iter()
+            .find(|&&(c, _)| c == Column::Status)
+            .and_then(|(_, status)| encoding::parse_status(&self.encoding, status))
+    }
+
+    pub fn start(&self) -> Option<SystemTime> {
+        self.get(Column::Start).and_then(|start| encoding::parse_start(&self.encoding, start))
+    }
+
+    pub fn timing(&self) -> Option<Duration> {
+        self.duration(Column::Timing)
+    }
+
+    pub fn total(&self) -> Option<Duration> {
+        self.duration(Column::Total)
+    }
+
+    pub fn response_len(&self) -> Option<u64> {
+        self.get(Column::ResponseLen).and_then(|len| len.parse().ok())
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.get(Column::RequestId)
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.get(Column::UserAgent)
+    }
+
+    /// The captured request headers, empty when none were captured.
+    pub fn request_headers(&self) -> BTreeMap<String, String> {
+        self.map(Column::RequestHeaders)
+    }
+
+    pub fn response_headers(&self) -> BTreeMap<String, String> {
+        self.map(Column::ResponseHeaders)
+    }
+
+    /// The static tags, empty unless the writer selected `Column::Tags`.
+    pub fn tags(&self) -> BTreeMap<String, String> {
+        self.map(Column::Tags)
+    }
+
+    /// The handler's fields, empty unless the writer selected
+    /// `Column::Fields`.
+    pub fn fields(&self) -> BTreeMap<String, String> {
+        self.map(Column::Fields)
+    }
+
+    fn duration(&self, column: Column) -> Option<Duration> {
+        self.get(column).and_then(|duration| encoding::parse_duration(&self.encoding, duration))
+    }
+
+    /// Headers, tags and fields are written as JSON objects.
+    fn map(&self, column: Column) -> BTreeMap<String, String> {
+        self.get(column)
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::UNIX_EPOCH;
+
+    use iron::status;
+
+    use super::super::{csv_header, encode_csv, LogPacket, StatusFormat, TimestampFormat, TimingUnit};
+    use super::super::DEFAULT_DELIMITER;
+
+    fn packets() -> Vec<LogPacket> {
+        (0..5u32).map(|i| {
+            let mut packet = LogPacket::stub();
+            packet.seq = i as u64 * 2;
+            packet.method = if i % 2 == 0 { Method::Get } else { Method::Post };
+            packet.status = if i == 3 { None } else { Some(Status::from_u16(200 + i as u16)) };
+            packet.timing = Duration::new(i as u64, 123_456_789 * i);
+            packet.start = UNIX_EPOCH + Duration::from_secs(1_500_000_000 + 3600 * i as u64);
+            packet.request_id = format!("request-{}", i);
+            packet.response_len = if i == 4 { None } else { Some(i as u64 * 100) };
+            packet.request_headers.insert("Accept".into(), format!("text/{}", i));
+            packet
+        }).collect()
+    }
+
+    fn written(packets: &[LogPacket], encoding: &Encoding) -> Vec<u8> {
+        let mut csv = csv_header(DEFAULT_DELIMITER, encoding);
+        for packet in packets {
+            csv.extend(encode_csv(packet, encoding).unwrap());
+        }
+        csv
+    }
+
+    fn assert_read_back(packets: &[LogPacket], records: Vec<LogRecord>) {
+        assert_eq!(records.len(), packets.len());
+        for (packet, record) in packets.iter().zip(&records) {
+            assert_eq!(record.seq(), Some(packet.seq()));
+            assert_eq!(record.url().as_ref(), Some(packet.url()));
+            assert_eq!(record.method().as_ref(), Some(packet.method()));
+            assert_eq!(record.ip(), Some(packet.ip()));
+            assert_eq!(record.status(), packet.status());
+            assert_eq!(record.start(), Some(packet.start()));
+            assert_eq!(record.timing(), Some(packet.timing()));
+            assert_eq!(record.response_len(), packet.response_len());
+            assert_eq!(record.request_id(), Some(packet.request_id()));
+            assert_eq!(record.user_agent(), packet.user_agent());
+            assert_eq!(&record.request_headers(), packet.request_headers());
+        }
+    }
+
+    #[test]
+    fn written_packets_are_read_back() {
+        let packets = packets();
+        let csv = written(&packets, &Encoding::default());
+
+        let records = LogReader::new(&csv[..]).unwrap().collect::<csv::Result<Vec<_>>>().unwrap();
+        assert_read_back(&packets, records);
+    }
+
+    #[test]
+    fn packets_are_read_back_in_other_encodings() {
+        let packets = packets();
+        let encodings = vec![
+            Encoding::new().timing(TimingUnit::Millis).timestamps(TimestampFormat::Rfc3339),
+            Encoding::new().timing(TimingUnit::Nanos).timestamps(TimestampFormat::Apache(-300)),
+            Encoding::new().status_format(StatusFormat::Name).missing_status("-"),
+        ];
+
+        for encoding in encodings {
+            let csv = written(&packets, &encoding);
+            let records = LogReader::with_encoding(&csv[..], encoding.clone()).unwrap()
+                .collect::<csv::Result<Vec<_>>>()
+                .unwrap();
+            assert_read_back(&packets, records);
+        }
+    }
+
+    #[test]
+    fn opt_in_columns_are_only_read_when_selected() {
+        let mut packet = LogPacket::stub();
+        packet.tags.insert("service".into(), "api".into());
+        packet.fields.insert("user_id".into(), "42".into());
+
+        let csv = written(&[packet.clone()], &Encoding::default());
+        let record = LogReader::new(&csv[..]).unwrap().next().unwrap().unwrap();
+        assert!(record.tags().is_empty());
+        assert!(record.fields().is_empty());
+
+        let columns = Column::DEFAULT.iter().chain(&[Column::Tags, Column::Fields]).cloned().collect::<Vec<_>>();
+        let csv = written(&[packet], &Encoding::default().columns(&columns));
+        let record = LogReader::new(&csv[..]).unwrap().next().unwrap().unwrap();
+        assert_eq!(record.tags()["service"], "api");
+        assert_eq!(record.fields()["user_id"], "42");
+    }
+
+    #[test]
+    fn headerless_rows_are_read_by_the_encoding() {
+        let encoding = Encoding::new().columns(&[Column::Status, Column::Path, Column::Timing]);
+        let csv = encode_csv(&LogPacket::stub(), &encoding).unwrap();
+
+        let record = LogReader::headerless(&csv[..], encoding).next().unwrap().unwrap();
+        assert_eq!(record.status(), Some(status::Ok));
+        assert_eq!(record.get(Column::Path), Some("/"));
+        assert_eq!(record.timing(), Some(Duration::new(0, 1_500_000)));
+        assert_eq!(record.url(), None);
+    }
+
+    #[test]
+    fn unknown_columns_are_skipped() {
+        let csv = "status,shard,request_id\n500,7,abc\n";
+        let record = LogReader::new(csv.as_bytes()).unwrap().next().unwrap().unwrap();
+        assert_eq!(record.status(), Some(status::InternalServerError));
+        assert_eq!(record.request_id(), Some("abc"));
+    }
+}
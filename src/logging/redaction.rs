@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use iron;
+
+use super::query;
+
+/// Values replaced with `***` before packets are queued, so secrets
+/// like access tokens and credentials never reach a writer. Only the
+/// values are hidden; the names stay visible so that their presence
+/// can still be seen.
+///
+/// ```
+/// use playground_middleware::{RedactionPolicy, StatisticLogger, NullLogger};
+///
+/// let logger = StatisticLogger::builder()
+///     .log_request_headers(&["Authorization", "Accept"])
+///     .redaction(RedactionPolicy::new()
+///         .query_params(&["token", "api_key"])
+///         .headers(&["Authorization", "Cookie"]))
+///     .build(NullLogger);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RedactionPolicy {
+    query_params: Vec<String>,
+    headers: Vec<String>,
+}
+
+impl RedactionPolicy {
+    /// Redacts nothing, until names are added.
+    pub fn new() -> RedactionPolicy {
+        RedactionPolicy::default()
+    }
+
+    /// Redacts the named query parameters, which are matched exactly.
+    pub fn query_params(mut self, names: &[&str]) -> Self {
+        self.query_params.extend(names.iter().map(|&name| name.into()));
+        self
+    }
+
+    /// Redacts the named request and response headers, which are
+    /// matched ignoring case. Headers are only recorded when selected
+    /// with `log_request_headers` or `log_response_headers`.
+    pub fn headers(mut self, names: &[&str]) -> Self {
+        self.headers.extend(names.iter().map(|&name| name.into()));
+        self
+    }
+
+    pub fn apply_url(&self, url: &iron::Url) -> iron::Url {
+        let mut url = url.clone();
+        if !self.query_params.is_empty() {
+            let redacted = url.query().map(|q| query::redact(q, &self.query_params));
+            url.as_mut().set_query(redacted.as_ref().map(|q| &q[..]));
+        }
+        url
+    }
+
+    pub fn apply_headers(&self, mut headers: BTreeMap<String, String>) -> BTreeMap<String, String> {
+        for (name, value) in &mut headers {
+            if self.headers.iter().any(|redacted| redacted.eq_ignore_ascii_case(name)) {
+                *value = "***".into();
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_named_values_are_redacted() {
+        let policy = RedactionPolicy::new()
+            .query_params(&["token", "api_key"])
+            .headers(&["Authorization", "cookie"]);
+
+        let url = iron::Url::parse("http://127.0.0.1/a?token=abc&page=2&api_key=x&TOKEN=y").unwrap();
+        assert_eq!(policy.apply_url(&url).to_string(), "http://127.0.0.1/a?token=***&page=2&api_key=***&TOKEN=y");
+
+        let mut headers = BTreeMap::new();
+        headers.insert("authorization".to_owned(), "Bearer abc".to_owned());
+        headers.insert("Cookie".to_owned(), "session=1".to_owned());
+        headers.insert("Accept".to_owned(), "text/html".to_owned());
+        let headers = policy.apply_headers(headers);
+        assert_eq!(headers["authorization"], "***");
+        assert_eq!(headers["Cookie"], "***");
+        assert_eq!(headers["Accept"], "text/html");
+
+        let nothing = RedactionPolicy::new();
+        assert_eq!(nothing.apply_url(&url), url);
+    }
+}
@@ -0,0 +1,28 @@
+use std::str;
+
+use iron::prelude::*;
+use uuid::Uuid;
+
+const HEADER: &str = "X-Request-Id";
+
+/// The correlation ID sent by an upstream service, or a new random
+/// one when the request did not carry one.
+pub fn find_or_generate(req: &Request) -> String {
+    req.headers.get_raw(HEADER)
+        .and_then(|lines| lines.first())
+        .and_then(|id| str::from_utf8(id).ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Echoes the correlation ID back to the client, on error responses
+/// too.
+pub fn set(response_result: &mut IronResult<Response>, id: &str) {
+    let response = match *response_result {
+        Ok(ref mut response) => response,
+        Err(ref mut failure) => &mut failure.response,
+    };
+    response.headers.set_raw(HEADER, vec![id.as_bytes().to_vec()]);
+}
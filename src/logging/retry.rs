@@ -0,0 +1,135 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{LogPacket, LogWriter, Summary};
+
+/// Retries a failed write with exponential backoff before giving up.
+///
+/// The first retry waits for the base delay, and each one after that
+/// waits twice as long as the last. No retry is started that would
+/// take the total time spent on one packet past the time limit. The
+/// waiting happens on the logging thread, so requests are only slowed
+/// if the queue fills up.
+pub struct RetryLogger<L> {
+    logger: L,
+    max_attempts: u32,
+    base_delay: Duration,
+    time_limit: Duration,
+}
+
+impl<L> RetryLogger<L> {
+    /// Makes up to 3 attempts, starting with a 10ms delay and spending
+    /// at most one second per packet.
+    pub fn new(logger: L) -> RetryLogger<L> {
+        RetryLogger {
+            logger: logger,
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            time_limit: Duration::from_secs(1),
+        }
+    }
+
+    /// The total number of attempts, including the first one.
+    pub fn max_attempts(self, attempts: u32) -> Self {
+        RetryLogger { max_attempts: attempts.max(1), ..self }
+    }
+
+    pub fn base_delay(self, delay: Duration) -> Self {
+        RetryLogger { base_delay: delay, ..self }
+    }
+
+    pub fn time_limit(self, limit: Duration) -> Self {
+        RetryLogger { time_limit: limit, ..self }
+    }
+
+    fn retry<F, E>(&mut self, mut f: F) -> Result<(), E>
+        where F: FnMut(&mut L) -> Result<(), E>
+    {
+        let start = Instant::now();
+        let mut delay = self.base_delay;
+        let mut attempt = 1;
+
+        loop {
+            match f(&mut self.logger) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    if attempt >= self.max_attempts || start.elapsed() + delay > self.time_limit {
+                        return Err(e);
+                    }
+                }
+            }
+
+            thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+impl<L: LogWriter> LogWriter for RetryLogger<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.retry(|logger| logger.log(packet))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.retry(|logger| logger.flush())
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.retry(|logger| logger.finalize(summary))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io;
+
+    struct FailsTwice {
+        attempts: usize,
+    }
+
+    impl LogWriter for FailsTwice {
+        type Error = io::Error;
+
+        fn log(&mut self, _packet: &LogPacket) -> Result<(), Self::Error> {
+            self.attempts += 1;
+            if self.attempts <= 2 {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "Connection lost"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn writes_are_retried_until_they_succeed() {
+        let mut logger = RetryLogger::new(FailsTwice { attempts: 0 }).base_delay(Duration::from_millis(1));
+
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(logger.logger.attempts, 3);
+    }
+
+    #[test]
+    fn writes_give_up_after_the_last_attempt() {
+        let mut logger = RetryLogger::new(FailsTwice { attempts: 0 })
+            .max_attempts(2)
+            .base_delay(Duration::from_millis(1));
+
+        assert!(logger.log(&LogPacket::stub()).is_err());
+        assert_eq!(logger.logger.attempts, 2);
+    }
+
+    #[test]
+    fn writes_give_up_at_the_time_limit() {
+        let mut logger = RetryLogger::new(FailsTwice { attempts: 0 })
+            .base_delay(Duration::from_millis(50))
+            .time_limit(Duration::from_millis(10));
+
+        assert!(logger.log(&LogPacket::stub()).is_err());
+        assert_eq!(logger.logger.attempts, 1);
+    }
+}
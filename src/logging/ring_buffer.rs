@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use iron::prelude::*;
+use iron::Handler;
+
+use super::{json, json_response, lock, LogPacket, LogWriter};
+
+/// Keeps the last `capacity` packets in memory, evicting the oldest,
+/// to look at recent requests while debugging without shipping them
+/// anywhere. Clones share the same buffer; use `handler` to serve it.
+#[derive(Debug, Clone)]
+pub struct RingBufferLogger {
+    capacity: usize,
+    packets: Arc<Mutex<VecDeque<LogPacket>>>,
+}
+
+impl RingBufferLogger {
+    pub fn new(capacity: usize) -> RingBufferLogger {
+        RingBufferLogger {
+            capacity: capacity,
+            packets: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// A copy of the buffered packets, oldest first.
+    pub fn packets(&self) -> Vec<LogPacket> {
+        lock(&self.packets).iter().cloned().collect()
+    }
+
+    /// A handler serving the buffered packets, oldest first, as a JSON
+    /// array of the objects written by `JsonLogger`.
+    pub fn handler(&self) -> RingBufferHandler {
+        RingBufferHandler(self.clone())
+    }
+}
+
+impl LogWriter for RingBufferLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut packets = lock(&self.packets);
+        if packets.len() == self.capacity {
+            packets.pop_front();
+        }
+        packets.push_back(packet.clone());
+        Ok(())
+    }
+}
+
+/// Serves the packets of a `RingBufferLogger` as JSON.
+pub struct RingBufferHandler(RingBufferLogger);
+
+impl Handler for RingBufferHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&json::PacketArray(&self.0.packets()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron;
+    use serde_json::{self, Value};
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    #[test]
+    fn only_the_most_recent_packets_are_served() {
+        let mut ring = RingBufferLogger::new(3);
+        let mut packet = LogPacket::stub();
+        for n in 0..5 {
+            packet.set_url(iron::Url::parse(&format!("http://127.0.0.1/{}", n)).unwrap());
+            ring.log(&packet).unwrap();
+        }
+
+        let res = request::get("http://127.0.0.1/recent", Headers::new(), &ring.handler()).unwrap();
+        let json: Value = serde_json::from_str(&response::extract_body_to_string(res)).unwrap();
+        let paths: Vec<_> = json.as_array().unwrap().iter().map(|packet| packet["path"].clone()).collect();
+        assert_eq!(paths, vec!["/2", "/3", "/4"]);
+    }
+}
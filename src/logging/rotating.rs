@@ -0,0 +1,482 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use csv;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use super::{Encoding, LogPacket, LogWriter, SyncPolicy};
+
+/// When a `RotatingFileLogger` moves its file aside, and which of the
+/// rotated files it keeps.
+///
+/// ```
+/// use std::time::Duration;
+/// use playground_middleware::FileRotationPolicy;
+///
+/// // Daily files of at most 100MB, compressed and kept for a week
+/// let policy = FileRotationPolicy::new()
+///     .max_bytes(100 * 1024 * 1024)
+///     .interval(Duration::from_secs(24 * 60 * 60))
+///     .max_age(Duration::from_secs(7 * 24 * 60 * 60))
+///     .compress();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileRotationPolicy {
+    max_bytes: Option<u64>,
+    interval: Option<Duration>,
+    max_files: Option<usize>,
+    max_age: Option<Duration>,
+    compress: bool,
+}
+
+impl FileRotationPolicy {
+    /// Never rotates, until a trigger is added.
+    pub fn new() -> FileRotationPolicy {
+        FileRotationPolicy::default()
+    }
+
+    /// Rotates before a row would take the file past `max_bytes`.
+    pub fn max_bytes(self, max_bytes: u64) -> Self {
+        FileRotationPolicy { max_bytes: Some(max_bytes), ..self }
+    }
+
+    /// Rotates once a request starts `interval` or more after the
+    /// first one in the file.
+    pub fn interval(self, interval: Duration) -> Self {
+        FileRotationPolicy { interval: Some(interval), ..self }
+    }
+
+    /// Deletes rotated files beyond the most recent `count`. All
+    /// rotated files are kept by default.
+    pub fn max_files(self, count: usize) -> Self {
+        FileRotationPolicy { max_files: Some(count), ..self }
+    }
+
+    /// Deletes rotated files last modified longer than `age` ago,
+    /// checked whenever the file is rotated.
+    pub fn max_age(self, age: Duration) -> Self {
+        FileRotationPolicy { max_age: Some(age), ..self }
+    }
+
+    /// Compresses rotated files with gzip, adding `.gz` to their names.
+    /// Each file is compressed on a thread of its own while logging
+    /// carries on; the next rotation and dropping the logger wait for
+    /// it to finish.
+    pub fn compress(self) -> Self {
+        FileRotationPolicy { compress: true, ..self }
+    }
+}
+
+/// Records statistics to a CSV file, moving it aside according to a
+/// `FileRotationPolicy`. The newest rotated file is `<path>.1`, the
+/// one before it `<path>.2`, and so on. Each file starts with a
+/// header row.
+pub struct RotatingFileLogger {
+    path: PathBuf,
+    policy: FileRotationPolicy,
+    file: File,
+    size: u64,
+    first_start: Option<SystemTime>,
+    has_rows: bool,
+    encoding: Encoding,
+    sync: SyncPolicy,
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl RotatingFileLogger {
+    /// Rotates the file once it reaches `max_bytes`.
+    pub fn new<P>(path: P, max_bytes: u64) -> csv::Result<RotatingFileLogger>
+        where P: AsRef<Path>
+    {
+        RotatingFileLogger::with_policy(path, FileRotationPolicy::new().max_bytes(max_bytes))
+    }
+
+    pub fn with_policy<P>(path: P, policy: FileRotationPolicy) -> csv::Result<RotatingFileLogger>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref().to_path_buf();
+        let (file, _) = try!(super::open_csv(&path));
+        let size = try!(file.metadata()).len();
+
+        Ok(RotatingFileLogger {
+            path: path,
+            policy: policy,
+            file: file,
+            size: size,
+            first_start: None,
+            has_rows: has_rows(size, &Encoding::default()),
+            encoding: Encoding::default(),
+            sync: SyncPolicy::default(),
+            compressing: None,
+        })
+    }
+
+    /// Deletes rotated files beyond the most recent `count`; see
+    /// `FileRotationPolicy::max_files`.
+    pub fn keep(mut self, count: usize) -> Self {
+        self.policy = self.policy.clone().max_files(count);
+        self
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.has_rows = has_rows(self.size, &encoding);
+        self.encoding = encoding;
+        self
+    }
+
+    /// Syncs the file to disk as `policy` says; see `SyncPolicy`. A
+    /// file is also synced before being rotated, unless the policy is
+    /// `Never`.
+    pub fn sync(mut self, policy: SyncPolicy) -> Self {
+        self.sync = policy;
+        self
+    }
+
+    /// The rotated file at `index`, which is compressed if it has a
+    /// `.gz` suffix.
+    fn rotated_path(&self, index: usize, compressed: bool) -> PathBuf {
+        let mut path: OsString = self.path.clone().into();
+        path.push(format!(".{}", index));
+        if compressed {
+            path.push(".gz");
+        }
+        path.into()
+    }
+
+    /// The rotated file at `index`, if there is one.
+    fn existing(&self, index: usize) -> Option<(PathBuf, bool)> {
+        [false, true].iter()
+            .map(|&compressed| (self.rotated_path(index, compressed), compressed))
+            .find(|(path, _)| path.exists())
+    }
+
+    fn should_rotate(&self, packet: &LogPacket, len: u64) -> bool {
+        // A single oversized row still gets a file to itself
+        if !self.has_rows {
+            return false;
+        }
+        let full = self.policy.max_bytes.is_some_and(|max| self.size + len > max);
+        let expired = match (self.policy.interval, self.first_start) {
+            (Some(interval), Some(first)) => packet.start.duration_since(first).is_ok_and(|age| age >= interval),
+            _ => false,
+        };
+        full || expired
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // Rotated files are renamed below, so the last one must be
+        // compressed first
+        self.wait_for_compression();
+        if self.sync != SyncPolicy::Never {
+            try!(self.file.sync_data());
+        }
+
+        let mut last = 1;
+        while self.existing(last).is_some() {
+            last += 1;
+        }
+
+        let max_files = self.policy.max_files;
+        for index in (1..last).rev() {
+            let (from, compressed) = match self.existing(index) {
+                Some(rotated) => rotated,
+                // Removed since the scan, such as by an operator
+                None => continue,
+            };
+            match max_files {
+                Some(keep) if index >= keep => try!(fs::remove_file(&from)),
+                _ => try!(fs::rename(&from, self.rotated_path(index + 1, compressed))),
+            }
+        }
+
+        let rotated = self.rotated_path(1, false);
+        if max_files == Some(0) {
+            try!(fs::remove_file(&self.path));
+        } else {
+            try!(fs::rename(&self.path, &rotated));
+        }
+
+        if let Some(max_age) = self.policy.max_age {
+            try!(self.remove_older_than(max_age));
+        }
+
+        if self.policy.compress && rotated.exists() {
+            let compressed = self.rotated_path(1, true);
+            self.compressing = Some(try!(thread::Builder::new().name("rotated-log-gzip".into()).spawn(move || {
+                if let Err(e) = compress(&rotated, &compressed) {
+                    error!("Unable to compress {}: {}", rotated.display(), e);
+                }
+            })));
+        }
+
+        self.file = try!(super::open_csv(&self.path)).0;
+        self.size = try!(self.file.metadata()).len();
+        self.first_start = None;
+        self.has_rows = false;
+        Ok(())
+    }
+
+    fn wait_for_compression(&mut self) {
+        if let Some(compressing) = self.compressing.take() {
+            if compressing.join().is_err() {
+                error!("Unable to compress rotated request log");
+            }
+        }
+    }
+
+    fn remove_older_than(&self, max_age: Duration) -> io::Result<()> {
+        let now = SystemTime::now();
+        let mut index = 1;
+        while let Some((path, _)) = self.existing(index) {
+            let modified = try!(try!(path.metadata()).modified());
+            if now.duration_since(modified).is_ok_and(|age| age >= max_age) {
+                try!(fs::remove_file(&path));
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RotatingFileLogger {
+    fn drop(&mut self) {
+        self.wait_for_compression();
+    }
+}
+
+/// Replaces `from` with a gzip-compressed copy at `to`.
+fn compress(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(try!(File::create(to)), Compression::default());
+    try!(io::copy(&mut try!(File::open(from)), &mut encoder));
+    try!(encoder.finish());
+    fs::remove_file(from)
+}
+
+/// Whether a file of `size` bytes holds more than the header written
+/// with `encoding`.
+fn has_rows(size: u64, encoding: &Encoding) -> bool {
+    size > super::csv_header(super::DEFAULT_DELIMITER, encoding).len() as u64
+}
+
+impl LogWriter for RotatingFileLogger {
+    type Error = csv::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let row = try!(super::encode_csv(packet, &self.encoding));
+        let len = row.len() as u64;
+
+        if self.should_rotate(packet, len) {
+            try!(self.rotate());
+        }
+
+        if self.size == 0 {
+            let header = super::csv_header(super::DEFAULT_DELIMITER, &self.encoding);
+            try!(self.file.write_all(&header));
+            self.size += header.len() as u64;
+        }
+
+        try!(self.file.write_all(&row));
+        try!(self.file.flush());
+        if self.sync == SyncPolicy::EveryWrite {
+            try!(self.file.sync_data());
+        }
+        self.size += len;
+        self.first_start = self.first_start.or(Some(packet.start));
+        self.has_rows = true;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.sync == SyncPolicy::EveryBatch {
+            try!(self.file.sync_data());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use self::tempdir::TempDir;
+
+    use super::super::Column;
+
+    fn lines(path: &Path) -> usize {
+        let mut contents = String::new();
+        File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+        contents.lines().count()
+    }
+
+    fn file_len() -> (u64, u64) {
+        let header_len = super::super::csv_header(super::super::DEFAULT_DELIMITER, &Encoding::default()).len() as u64;
+        let row_len = super::super::encode_csv(&LogPacket::stub(), &Encoding::default()).unwrap().len() as u64;
+        (header_len, row_len)
+    }
+
+    #[test]
+    fn files_are_rotated_when_full() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let (header_len, row_len) = file_len();
+
+        let mut logger = RotatingFileLogger::new(&path, header_len + row_len * 2).unwrap().keep(2);
+        for _ in 0..7 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+
+        // Each file has a header followed by its rows
+        assert_eq!(lines(&path), 2);
+        assert_eq!(lines(&dir.path().join("access.csv.1")), 3);
+        assert_eq!(lines(&dir.path().join("access.csv.2")), 3);
+        assert!(!dir.path().join("access.csv.3").exists());
+    }
+
+    #[test]
+    fn rotation_skips_rotated_files_that_were_deleted() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let (header_len, row_len) = file_len();
+
+        let mut logger = RotatingFileLogger::new(&path, header_len + row_len - 1).unwrap();
+        for _ in 0..4 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        fs::remove_file(dir.path().join("access.csv.2")).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+
+        for name in &["access.csv", "access.csv.1", "access.csv.2", "access.csv.3"] {
+            assert_eq!(lines(&dir.path().join(name)), 2, "{}", name);
+        }
+        assert!(!dir.path().join("access.csv.4").exists());
+    }
+
+    #[test]
+    fn reopened_files_with_only_a_header_are_not_rotated() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let encoding = Encoding::default().columns(Column::ALL);
+        let header_len = super::super::csv_header(super::super::DEFAULT_DELIMITER, &encoding).len() as u64;
+        let row_len = super::super::encode_csv(&LogPacket::stub(), &encoding).unwrap().len() as u64;
+        // Left by an earlier run that logged nothing
+        fs::write(&path, super::super::csv_header(super::super::DEFAULT_DELIMITER, &encoding)).unwrap();
+
+        let mut logger = RotatingFileLogger::new(&path, header_len + row_len - 1).unwrap().encoding(encoding);
+        logger.log(&LogPacket::stub()).unwrap();
+
+        assert_eq!(lines(&path), 2);
+        assert!(!dir.path().join("access.csv.1").exists());
+    }
+
+    #[test]
+    fn synced_files_are_rotated() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let (header_len, row_len) = file_len();
+
+        let mut logger = RotatingFileLogger::new(&path, header_len + row_len * 2).unwrap().sync(SyncPolicy::EveryWrite);
+        for _ in 0..3 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        logger.flush().unwrap();
+
+        assert_eq!(lines(&path), 2);
+        assert_eq!(lines(&dir.path().join("access.csv.1")), 3);
+    }
+
+    #[test]
+    fn files_are_rotated_after_the_interval() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let policy = FileRotationPolicy::new().interval(Duration::from_secs(60));
+
+        let mut logger = RotatingFileLogger::with_policy(&path, policy).unwrap();
+        let mut packet = LogPacket::stub();
+        for &offset in &[0, 30, 59, 60, 90, 125] {
+            packet.start = LogPacket::stub().start + Duration::from_secs(offset);
+            logger.log(&packet).unwrap();
+        }
+
+        assert_eq!(lines(&dir.path().join("access.csv.2")), 4);
+        assert_eq!(lines(&dir.path().join("access.csv.1")), 3);
+        assert_eq!(lines(&path), 2);
+    }
+
+    #[test]
+    fn rotated_files_can_be_compressed() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let (header_len, row_len) = file_len();
+        let policy = FileRotationPolicy::new().max_bytes(header_len + row_len).max_files(2).compress();
+
+        let mut logger = RotatingFileLogger::with_policy(&path, policy).unwrap();
+        for _ in 0..4 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        drop(logger);
+
+        let mut contents = String::new();
+        GzDecoder::new(File::open(dir.path().join("access.csv.1.gz")).unwrap())
+            .read_to_string(&mut contents).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert!(dir.path().join("access.csv.2.gz").exists());
+        assert!(!dir.path().join("access.csv.1").exists());
+        assert!(!dir.path().join("access.csv.3.gz").exists());
+    }
+
+    #[test]
+    fn logging_continues_while_rotated_files_are_compressed() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let (header_len, row_len) = file_len();
+        let policy = FileRotationPolicy::new().max_bytes(header_len + row_len * 1000).compress();
+
+        let mut logger = RotatingFileLogger::with_policy(&path, policy).unwrap();
+        for _ in 0..1000 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        logger.log(&LogPacket::stub()).unwrap();
+        // Written to the new file, before compression need have finished
+        for _ in 0..9 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+        assert_eq!(lines(&path), 11);
+
+        // Waits for the compression to finish
+        drop(logger);
+
+        let compressed = dir.path().join("access.csv.1.gz");
+
+        let mut contents = String::new();
+        GzDecoder::new(File::open(&compressed).unwrap()).read_to_string(&mut contents).unwrap();
+        assert_eq!(contents.lines().count(), 1001);
+        assert!(!dir.path().join("access.csv.1").exists());
+    }
+
+    #[test]
+    fn old_rotated_files_are_deleted() {
+        let dir = TempDir::new("rotating").unwrap();
+        let path = dir.path().join("access.csv");
+        let (header_len, row_len) = file_len();
+        let policy = FileRotationPolicy::new().max_bytes(header_len + row_len).max_age(Duration::from_secs(0));
+
+        let mut logger = RotatingFileLogger::with_policy(&path, policy).unwrap();
+        for _ in 0..3 {
+            logger.log(&LogPacket::stub()).unwrap();
+        }
+
+        assert_eq!(lines(&path), 2);
+        assert!(!dir.path().join("access.csv.1").exists());
+    }
+}
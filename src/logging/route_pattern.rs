@@ -0,0 +1,79 @@
+use iron::prelude::*;
+use iron::typemap::Key;
+
+/// The route pattern a router matched the request with, like
+/// `/users/:id`, which groups requests far better than their paths.
+/// Routers do not record it themselves, so set it where the route is
+/// registered, for example by wrapping each route's handler:
+///
+/// ```
+/// extern crate iron;
+/// extern crate playground_middleware;
+///
+/// use iron::prelude::*;
+/// use iron::status;
+/// use playground_middleware::RoutePattern;
+///
+/// fn show_user(req: &mut Request) -> IronResult<Response> {
+///     RoutePattern::set(req, "/users/:id");
+///     Ok(Response::with(status::Ok))
+/// }
+/// # fn main() {}
+/// ```
+pub struct RoutePattern;
+
+impl Key for RoutePattern {
+    type Value = String;
+}
+
+impl RoutePattern {
+    pub fn set(req: &mut Request, pattern: &str) {
+        req.extensions.insert::<RoutePattern>(pattern.into());
+    }
+}
+
+/// The pattern set for the request, if any.
+pub fn get(req: &Request) -> Option<String> {
+    req.extensions.get::<RoutePattern>().cloned()
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::{Column, Encoding, MemoryLogger, StatisticLogger};
+
+    #[test]
+    fn matched_patterns_are_logged_instead_of_paths() {
+        let memory = MemoryLogger::new();
+        // Stands in for a router that records the route it matched
+        let handler = StatisticLogger::new(memory.clone()).around(Box::new(|req: &mut Request| {
+            if req.url.path()[0] == "users" {
+                RoutePattern::set(req, "/users/:id");
+            }
+            Ok(Response::with(status::Ok))
+        }));
+
+        request::get("http://127.0.0.1/users/42", Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/about", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets[0].route(), Some("/users/:id"));
+        assert_eq!(packets[1].route(), None);
+
+        let encoding = Encoding::default().columns(&[Column::Path, Column::Route]);
+        let rows: Vec<_> = packets.iter()
+            .map(|packet| String::from_utf8(super::super::encode_csv(packet, &encoding).unwrap()).unwrap())
+            .collect();
+        assert_eq!(rows, vec!["/users/42,/users/:id\n", "/about,/about\n"]);
+    }
+}
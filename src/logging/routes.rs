@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+
+use super::{lock, Aggregator, LogPacket, LogWriter};
+
+/// Timing statistics for the requests to one route.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RouteStats {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl RouteStats {
+    fn new(timing: Duration) -> RouteStats {
+        RouteStats {
+            count: 1,
+            total: timing,
+            min: timing,
+            max: timing,
+        }
+    }
+
+    fn record(&mut self, timing: Duration) {
+        self.count += 1;
+        self.total += timing;
+        self.min = self.min.min(timing);
+        self.max = self.max.max(timing);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+}
+
+/// Aggregates request timings per route so slow endpoints stand out.
+/// Clones share the same statistics.
+///
+/// Paths are grouped by a template: by default, numeric and UUID
+/// segments are replaced with `:id`, so `/user/1` and `/user/2` are
+/// both counted as `/user/:id`. Requests with a `RoutePattern` are
+/// grouped by that pattern instead.
+#[derive(Clone)]
+pub struct RouteAggregator {
+    normalize: Arc<Fn(&str) -> String + Send + Sync>,
+    routes: Arc<Mutex<BTreeMap<String, RouteStats>>>,
+}
+
+impl RouteAggregator {
+    pub fn new() -> RouteAggregator {
+        RouteAggregator::with_normalizer(normalize_path)
+    }
+
+    /// Groups requests by the template returned for each path.
+    pub fn with_normalizer<F>(normalize: F) -> RouteAggregator
+        where F: Fn(&str) -> String + Send + Sync + 'static
+    {
+        RouteAggregator {
+            normalize: Arc::new(normalize),
+            routes: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// A copy of the statistics for each route seen so far.
+    pub fn snapshot(&self) -> BTreeMap<String, RouteStats> {
+        lock(&self.routes).clone()
+    }
+
+    /// Forgets every route, returning their statistics so far.
+    pub fn reset(&self) -> BTreeMap<String, RouteStats> {
+        mem::take(&mut *lock(&self.routes))
+    }
+}
+
+impl Default for RouteAggregator {
+    fn default() -> RouteAggregator {
+        RouteAggregator::new()
+    }
+}
+
+impl Aggregator for RouteAggregator {
+    fn observe(&self, packet: &LogPacket) {
+        let route = packet.route().map_or_else(|| (self.normalize)(packet.path()), String::from);
+        let mut routes = lock(&self.routes);
+        if let Some(stats) = routes.get_mut(&route) {
+            stats.record(packet.timing);
+            return;
+        }
+        routes.insert(route, RouteStats::new(packet.timing));
+    }
+
+    /// Each route's statistics, with timings in seconds, like
+    /// `{"/user/:id":{"count":2,"mean":0.0015,"min":0.001,"max":0.002}}`.
+    fn snapshot(&self) -> Value {
+        let routes: Map<String, Value> = lock(&self.routes).iter()
+            .map(|(route, stats)| {
+                (route.clone(), json!({
+                    "count": stats.count(),
+                    "mean": stats.mean().as_secs_f64(),
+                    "min": stats.min().as_secs_f64(),
+                    "max": stats.max().as_secs_f64(),
+                }))
+            })
+            .collect();
+        Value::Object(routes)
+    }
+}
+
+impl LogWriter for RouteAggregator {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+/// Replaces numeric and UUID path segments with `:id`.
+fn normalize_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| if is_id(segment) { ":id" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn is_id(segment: &str) -> bool {
+    let is_number = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    let is_uuid = segment.len() == 36 && segment.bytes().enumerate().all(|(i, b)| match i {
+        8 | 13 | 18 | 23 => b == b'-',
+        _ => b.is_ascii_hexdigit(),
+    });
+    is_number || is_uuid
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron::Url;
+
+    fn packet(path: &str, millis: u64) -> LogPacket {
+        let mut packet = LogPacket::stub();
+        packet.url = Url::parse(&format!("http://127.0.0.1{}", path)).unwrap();
+        packet.timing = Duration::from_millis(millis);
+        packet
+    }
+
+    #[test]
+    fn requests_are_grouped_by_route() {
+        let mut aggregator = RouteAggregator::new();
+        aggregator.log(&packet("/user/1", 10)).unwrap();
+        aggregator.log(&packet("/user/2", 30)).unwrap();
+        aggregator.log(&packet("/user/2/posts", 5)).unwrap();
+
+        let routes = aggregator.snapshot();
+        assert_eq!(routes.len(), 2);
+
+        let user = routes["/user/:id"];
+        assert_eq!(user.count(), 2);
+        assert_eq!(user.total(), Duration::from_millis(40));
+        assert_eq!(user.min(), Duration::from_millis(10));
+        assert_eq!(user.max(), Duration::from_millis(30));
+        assert_eq!(user.mean(), Duration::from_millis(20));
+        assert_eq!(routes["/user/:id/posts"].count(), 1);
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize_path("/"), "/");
+        assert_eq!(normalize_path("/user/42/v2"), "/user/:id/v2");
+        assert_eq!(normalize_path("/order/0b8cc2e6-2ba7-4b5c-9e3e-4f26e3a1a2de"), "/order/:id");
+    }
+
+    #[test]
+    fn normalization_can_be_customized() {
+        let mut aggregator = RouteAggregator::with_normalizer(|path| {
+            path.split('/').take(2).collect::<Vec<_>>().join("/")
+        });
+        aggregator.log(&packet("/static/app.js", 1)).unwrap();
+        aggregator.log(&packet("/static/app.css", 1)).unwrap();
+
+        assert_eq!(aggregator.snapshot()["/static"].count(), 2);
+    }
+}
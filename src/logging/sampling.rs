@@ -0,0 +1,252 @@
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use iron::status::Status;
+
+use super::{LogPacket, LogWriter, Summary};
+
+/// Keeps a random fraction of requests, so busy servers log a
+/// representative sample instead of every request.
+///
+/// Rates are between 0, logging nothing, and 1, logging everything.
+/// Paths can have their own rate, and failed requests can be given a
+/// separate rate so that errors are never missed. Clones share the
+/// same random sequence.
+#[derive(Debug, Clone)]
+pub struct Sampling {
+    rate: f64,
+    paths: Vec<(String, f64)>,
+    errors: Option<f64>,
+    state: Arc<AtomicU64>,
+}
+
+impl Sampling {
+    /// Keeps each request with probability `rate`.
+    pub fn new(rate: f64) -> Sampling {
+        Sampling {
+            rate: rate,
+            paths: Vec::new(),
+            errors: None,
+            state: Arc::new(AtomicU64::new(time_seed())),
+        }
+    }
+
+    /// Keeps requests whose path starts with `prefix` with probability
+    /// `rate` instead. The longest matching prefix wins.
+    pub fn path(mut self, prefix: &str, rate: f64) -> Self {
+        self.paths.push((prefix.into(), rate));
+        self.paths.sort_by_key(|path| Reverse(path.0.len()));
+        self
+    }
+
+    /// Keeps requests that failed with a server error, or without a
+    /// status, with probability `rate`, whatever their path.
+    pub fn errors(self, rate: f64) -> Self {
+        Sampling { errors: Some(rate), ..self }
+    }
+
+    /// Starts the random sequence from `seed`, for reproducible tests.
+    pub fn seed(self, seed: u64) -> Self {
+        Sampling { state: Arc::new(AtomicU64::new(seed)), ..self }
+    }
+
+    /// The probability that a request is kept.
+    pub fn rate(&self, path: &str, status: Option<Status>) -> f64 {
+        let failed = status.is_none_or(|s| s.is_server_error());
+        match self.errors {
+            Some(rate) if failed => rate,
+            _ => self.paths.iter()
+                .find(|&(prefix, _)| path.starts_with(&prefix[..]))
+                .map(|&(_, rate)| rate)
+                .unwrap_or(self.rate),
+        }
+    }
+
+    /// Decides whether a request is logged.
+    pub fn keep(&self, path: &str, status: Option<Status>) -> bool {
+        keep_at(self, self.rate(path, status))
+    }
+}
+
+/// Decides whether a request is logged, once its `rate` is known.
+pub fn keep_at(sampling: &Sampling, rate: f64) -> bool {
+    sample(&sampling.state, rate)
+}
+
+/// Keeps a random fraction of ordinary requests, but every one that
+/// failed with a status of 400 or above, or without a status, and
+/// every one slower than a threshold. Unlike `Sampling`, the decision
+/// is made on the logging thread, once the outcome of the request is
+/// known; see `StatisticLoggerBuilder::tail_sampling`.
+#[derive(Debug, Clone)]
+pub struct TailSampling {
+    rate: f64,
+    slower_than: Option<Duration>,
+    state: Arc<AtomicU64>,
+}
+
+impl TailSampling {
+    /// Keeps ordinary requests with probability `rate`.
+    pub fn new(rate: f64) -> TailSampling {
+        TailSampling {
+            rate: rate,
+            slower_than: None,
+            state: Arc::new(AtomicU64::new(time_seed())),
+        }
+    }
+
+    /// Keeps every request that took longer than `threshold`.
+    pub fn slower_than(self, threshold: Duration) -> Self {
+        TailSampling { slower_than: Some(threshold), ..self }
+    }
+
+    /// Starts the random sequence from `seed`, for reproducible tests.
+    pub fn seed(self, seed: u64) -> Self {
+        TailSampling { state: Arc::new(AtomicU64::new(seed)), ..self }
+    }
+
+    /// The probability that a packet is written: 1 for failed and
+    /// slow requests.
+    pub fn rate(&self, packet: &LogPacket) -> f64 {
+        let failed = packet.status.is_none_or(|s| s.to_u16() >= 400);
+        let slow = self.slower_than.is_some_and(|threshold| packet.timing > threshold);
+        if failed || slow { 1.0 } else { self.rate }
+    }
+
+    /// Decides whether a packet is written.
+    pub fn keep(&self, packet: &LogPacket) -> bool {
+        sample(&self.state, self.rate(packet))
+    }
+}
+
+/// Only passes the packets kept by a `TailSampling` on to the writer,
+/// with their `sample_rate` lowered by the rate they were kept at.
+pub struct TailSampled<L> {
+    logger: L,
+    sampling: TailSampling,
+}
+
+impl<L> TailSampled<L> {
+    pub fn new(logger: L, sampling: TailSampling) -> TailSampled<L> {
+        TailSampled {
+            logger: logger,
+            sampling: sampling,
+        }
+    }
+}
+
+impl<L: LogWriter> LogWriter for TailSampled<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let rate = self.sampling.rate(packet);
+        if !sample(&self.sampling.state, rate) {
+            return Ok(());
+        }
+        if rate >= 1.0 {
+            return self.logger.log(packet);
+        }
+
+        let mut packet = packet.clone();
+        packet.sample_rate *= rate;
+        self.logger.log(&packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+
+    fn discards_everything(&self) -> bool {
+        self.logger.discards_everything()
+    }
+}
+
+fn time_seed() -> u64 {
+    ::std::time::SystemTime::now()
+        .duration_since(::std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() ^ ((d.subsec_nanos() as u64) << 32))
+        .unwrap_or(0)
+}
+
+/// Returns true with probability `rate`.
+fn sample(state: &AtomicU64, rate: f64) -> bool {
+    if rate >= 1.0 {
+        return true;
+    }
+    // The top 53 bits give a uniform float in [0, 1)
+    let sample = (next(state) >> 11) as f64 / (1u64 << 53) as f64;
+    sample < rate
+}
+
+/// SplitMix64, which only needs an atomic add to be shared between
+/// threads.
+fn next(state: &AtomicU64) -> u64 {
+    let mut z = state.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron::status;
+
+    fn kept(sampling: &Sampling, path: &str, status: Option<Status>) -> usize {
+        (0..10_000).filter(|_| sampling.keep(path, status)).count()
+    }
+
+    #[test]
+    fn about_the_given_fraction_is_kept() {
+        let sampling = Sampling::new(0.1).seed(42);
+
+        let kept = kept(&sampling, "/", Some(status::Ok));
+        assert!(kept > 900 && kept < 1100, "{}", kept);
+    }
+
+    #[test]
+    fn paths_can_override_the_rate() {
+        let sampling = Sampling::new(0.1).path("/api", 0.0).path("/api/checkout", 1.0).seed(42);
+
+        assert_eq!(kept(&sampling, "/api/search", Some(status::Ok)), 0);
+        assert_eq!(kept(&sampling, "/api/checkout/pay", Some(status::Ok)), 10_000);
+    }
+
+    #[test]
+    fn errors_can_always_be_kept() {
+        let sampling = Sampling::new(0.0).errors(1.0).seed(42);
+
+        assert_eq!(kept(&sampling, "/", Some(status::NotFound)), 0);
+        assert_eq!(kept(&sampling, "/", Some(status::InternalServerError)), 10_000);
+        assert_eq!(kept(&sampling, "/", None), 10_000);
+    }
+
+    #[test]
+    fn tail_sampling_keeps_slow_and_failed_requests() {
+        let sampling = TailSampling::new(0.0).slower_than(Duration::from_millis(100)).seed(42);
+        let mut packet = LogPacket::stub();
+
+        assert!(!sampling.keep(&packet));
+        packet.timing = Duration::from_millis(250);
+        assert!(sampling.keep(&packet));
+
+        packet.timing = Duration::from_millis(1);
+        for &status in &[status::NotFound, status::InternalServerError] {
+            packet.status = Some(status);
+            assert!(sampling.keep(&packet));
+        }
+
+        let sampling = TailSampling::new(0.5).seed(42);
+        let packet = LogPacket::stub();
+        let kept = (0..10_000).filter(|_| sampling.keep(&packet)).count();
+        assert!(kept > 4_500 && kept < 5_500, "{}", kept);
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{LogPacket, LogWriter, Summary};
+
+pub type SlowRequestHandler = Box<FnMut(&LogPacket) + Send>;
+
+/// Calls a handler for every packet that took longer than a threshold,
+/// before logging it as usual. Wraps the writer on the logging thread
+/// so the handler never runs while a request is being served. The
+/// writers of a pool share the handler, calling it one at a time.
+pub struct SlowRequests<L> {
+    logger: L,
+    threshold: Duration,
+    on_slow: Arc<Mutex<SlowRequestHandler>>,
+}
+
+impl<L> SlowRequests<L> {
+    pub fn new(logger: L, threshold: Duration, on_slow: Arc<Mutex<SlowRequestHandler>>) -> SlowRequests<L> {
+        SlowRequests {
+            logger: logger,
+            threshold: threshold,
+            on_slow: on_slow,
+        }
+    }
+}
+
+/// Warns through the `log` crate, for `slow_threshold`.
+pub fn warn(threshold: Duration) -> SlowRequestHandler {
+    Box::new(move |packet: &LogPacket| {
+        warn!("Slow request: {} {} took {:?}, over {:?}", packet.method, packet.url, packet.timing, threshold);
+    })
+}
+
+impl<L: LogWriter> LogWriter for SlowRequests<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if packet.timing > self.threshold {
+            let mut on_slow = self.on_slow.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            (*on_slow)(packet);
+        }
+        self.logger.log(packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+
+    fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+        self.logger.finalize(summary)
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::typemap::Key;
+use iron::{AfterMiddleware, BeforeMiddleware};
+
+use super::{Recorder, Started};
+
+/// Stamps the start of each request, for the `AfterLogger` from the
+/// same `StatisticLogger::split`.
+pub struct BeforeLogger(Arc<Recorder>);
+
+/// Logs each request stamped by its `BeforeLogger`, whether the chain
+/// succeeded or failed. Requests that never passed the `BeforeLogger`
+/// are not logged.
+pub struct AfterLogger(Arc<Recorder>);
+
+struct StartedKey;
+
+impl Key for StartedKey {
+    type Value = Started;
+}
+
+pub fn split(recorder: Recorder) -> (BeforeLogger, AfterLogger) {
+    let recorder = Arc::new(recorder);
+    (BeforeLogger(recorder.clone()), AfterLogger(recorder))
+}
+
+impl BeforeMiddleware for BeforeLogger {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let started = self.0.start(req);
+        req.extensions.insert::<StartedKey>(started);
+        Ok(())
+    }
+}
+
+impl AfterLogger {
+    fn finish(&self, req: &mut Request, mut response_result: IronResult<Response>) -> IronResult<Response> {
+        if let Some(started) = req.extensions.remove::<StartedKey>() {
+            self.0.finish(req, &mut response_result, started);
+        }
+        response_result
+    }
+}
+
+impl AfterMiddleware for AfterLogger {
+    fn after(&self, req: &mut Request, res: Response) -> IronResult<Response> {
+        self.finish(req, Ok(res))
+    }
+
+    fn catch(&self, req: &mut Request, err: IronError) -> IronResult<Response> {
+        self.finish(req, Err(err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::status;
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::{MemoryLogger, StatisticLogger};
+
+    #[test]
+    fn a_chain_is_logged_before_and_after() {
+        let memory = MemoryLogger::new();
+        let (before, after) = StatisticLogger::new(memory.clone()).split();
+
+        let mut chain = Chain::new(|req: &mut Request| -> IronResult<Response> {
+            if req.url.path() == ["missing"] {
+                Err(IronError::new(::std::fmt::Error, status::NotFound))
+            } else {
+                Ok(Response::with((status::Ok, "hello")))
+            }
+        });
+        chain.link_before(before);
+        chain.link_after(after);
+
+        let res = request::get("http://127.0.0.1/a", Headers::new(), &chain).expect("Request failed");
+        assert!(res.headers.get_raw("X-Request-Id").is_some());
+        let _ = request::get("http://127.0.0.1/missing", Headers::new(), &chain);
+        drop(chain);
+
+        let packets = memory.packets();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].path(), "/a");
+        assert_eq!(packets[0].status(), Some(status::Ok));
+        assert_eq!(packets[0].response_len(), Some(5));
+        assert_eq!(packets[1].status(), Some(status::NotFound));
+        assert!(packets[1].error().is_some());
+    }
+}
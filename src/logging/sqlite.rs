@@ -0,0 +1,117 @@
+use std::path::Path;
+
+use rusqlite::{Connection, Error, ToSql};
+
+use super::{LogPacket, LogWriter};
+
+const CREATE_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS requests (
+        url TEXT NOT NULL,
+        method TEXT NOT NULL,
+        ip TEXT NOT NULL,
+        status INTEGER,
+        start REAL NOT NULL,
+        timing_ns INTEGER NOT NULL
+    )";
+
+const INSERT: &str = "
+    INSERT INTO requests (url, method, ip, status, start, timing_ns)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
+
+/// Records statistics as rows of a `requests` table in a SQLite
+/// database, creating the table if needed.
+///
+/// Rows are inserted inside a transaction that is committed when the
+/// logger is flushed, so a burst of requests costs a single commit.
+pub struct SqliteLogger {
+    conn: Connection,
+}
+
+impl SqliteLogger {
+    pub fn new<P>(path: P) -> Result<SqliteLogger, Error>
+        where P: AsRef<Path>
+    {
+        SqliteLogger::from_connection(try!(Connection::open(path)))
+    }
+
+    pub fn from_connection(conn: Connection) -> Result<SqliteLogger, Error> {
+        try!(conn.execute_batch(CREATE_TABLE));
+        Ok(SqliteLogger { conn: conn })
+    }
+}
+
+impl LogWriter for SqliteLogger {
+    type Error = Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if self.conn.is_autocommit() {
+            try!(self.conn.execute_batch("BEGIN"));
+        }
+
+        let start = super::since_epoch(packet.start);
+        let start = start.as_secs() as f64 + start.subsec_nanos() as f64 / 1e9;
+        let timing_ns = packet.timing.as_secs() as i64 * 1_000_000_000 + packet.timing.subsec_nanos() as i64;
+
+        let mut insert = try!(self.conn.prepare_cached(INSERT));
+        let params: &[&ToSql] = &[
+            &packet.url.as_ref().as_str(),
+            &packet.method.as_ref(),
+            &super::format_ip(packet),
+            &packet.status.map(|s| s.to_u16()),
+            &start,
+            &timing_ns,
+        ];
+        try!(insert.execute(params));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if !self.conn.is_autocommit() {
+            try!(self.conn.execute_batch("COMMIT"));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SqliteLogger {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("Unable to commit request log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempdir;
+
+    use super::*;
+
+    use self::tempdir::TempDir;
+
+    #[test]
+    fn packets_are_inserted_as_rows() {
+        let dir = TempDir::new("sqlite").unwrap();
+        let path = dir.path().join("requests.db");
+
+        let mut logger = SqliteLogger::new(&path).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.flush().unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let mut select = conn.prepare("SELECT url, method, ip, status, start, timing_ns FROM requests").unwrap();
+        let rows: Vec<(String, String, String, Option<u16>, f64, i64)> = select
+            .query_map([], |row| {
+                Ok((try!(row.get(0)), try!(row.get(1)), try!(row.get(2)),
+                    try!(row.get(3)), try!(row.get(4)), try!(row.get(5))))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], ("http://127.0.0.1/".into(), "GET".into(), "127.0.0.1:3000".into(),
+                             Some(200), 1_500_000_000.0, 1_500_000));
+    }
+}
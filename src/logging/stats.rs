@@ -0,0 +1,230 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use iron::prelude::*;
+use iron::Handler;
+use serde_json::Value;
+
+use super::{json_response, status_counts, Aggregator, LatencyRecorder, LogPacket, LogWriter, StatusCounter};
+
+/// Aggregates the request count, the counts per status class and the
+/// mean and 95th percentile latency. Clones share the same
+/// statistics; use `handler` to serve them, typically at `/stats`.
+#[derive(Debug, Clone)]
+pub struct StatsRecorder {
+    counts: StatusCounter,
+    latency: LatencyRecorder,
+    total_nanos: Arc<AtomicU64>,
+    // Held for reading while logging, so a reset sees every packet
+    // either completely or not at all
+    resetting: Arc<RwLock<()>>,
+}
+
+impl StatsRecorder {
+    pub fn new() -> StatsRecorder {
+        StatsRecorder {
+            counts: StatusCounter::new(),
+            latency: LatencyRecorder::with_percentiles(vec![95.0]),
+            total_nanos: Arc::new(AtomicU64::new(0)),
+            resetting: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// The number of requests recorded so far.
+    pub fn requests(&self) -> u64 {
+        self.latency.count()
+    }
+
+    /// The mean latency, or zero before any request is recorded.
+    pub fn mean_latency(&self) -> Duration {
+        let requests = self.requests();
+        if requests == 0 {
+            return Duration::from_secs(0);
+        }
+        Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed) / requests)
+    }
+
+    /// A handler serving the statistics as a JSON object like
+    /// `{"requests":3,"status":{"2xx":2,...},"mean_latency":0.0015,"p95_latency":0.0025}`,
+    /// with latencies in seconds.
+    pub fn handler(&self) -> StatsHandler {
+        StatsHandler {
+            recorder: self.clone(),
+            reset: false,
+        }
+    }
+
+    /// Starts the statistics over, returning a recorder that holds
+    /// those before, for rates since the last reset.
+    pub fn reset(&self) -> StatsRecorder {
+        let _resetting = self.resetting.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        StatsRecorder {
+            counts: status_counts::detach(&self.counts),
+            latency: self.latency.reset(),
+            total_nanos: Arc::new(AtomicU64::new(self.total_nanos.swap(0, Ordering::Relaxed))),
+            resetting: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// A handler that resets the statistics and serves those before,
+    /// in the format of `handler`, typically at `/stats/reset`.
+    pub fn reset_handler(&self) -> StatsHandler {
+        StatsHandler {
+            recorder: self.clone(),
+            reset: true,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let p95 = self.latency.percentiles()[0].1;
+        json!({
+            "requests": self.requests(),
+            "status": self.counts.snapshot(),
+            "mean_latency": as_secs(self.mean_latency()),
+            "p95_latency": as_secs(p95),
+        })
+    }
+}
+
+impl Default for StatsRecorder {
+    fn default() -> StatsRecorder {
+        StatsRecorder::new()
+    }
+}
+
+fn as_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+impl Aggregator for StatsRecorder {
+    fn observe(&self, packet: &LogPacket) {
+        let nanos = packet.timing.as_secs() * 1_000_000_000 + packet.timing.subsec_nanos() as u64;
+        let _logging = self.resetting.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.counts.observe(packet);
+        self.latency.observe(packet);
+    }
+
+    /// The statistics served by `handler`.
+    fn snapshot(&self) -> Value {
+        self.to_json()
+    }
+}
+
+impl LogWriter for StatsRecorder {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+/// Serves the statistics of a `StatsRecorder` as JSON.
+pub struct StatsHandler {
+    recorder: StatsRecorder,
+    reset: bool,
+}
+
+impl Handler for StatsHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        let stats = if self.reset { self.recorder.reset().to_json() } else { self.recorder.to_json() };
+        json_response(&stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::thread;
+
+    use iron::headers::ContentType;
+    use iron::status;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    #[test]
+    fn statistics_are_served_as_json() {
+        let mut recorder = StatsRecorder::new();
+        let mut packet = LogPacket::stub();
+        for &(status, millis) in &[(status::Ok, 1), (status::Ok, 2), (status::NotFound, 3), (status::BadGateway, 6)] {
+            packet.status = Some(status);
+            packet.timing = Duration::from_millis(millis);
+            recorder.log(&packet).unwrap();
+        }
+
+        let res = request::get("http://127.0.0.1/stats", Headers::new(), &recorder.handler()).unwrap();
+        assert_eq!(res.headers.get::<ContentType>().unwrap().to_string(), "application/json");
+        let json: Value = serde_json::from_str(&response::extract_body_to_string(res)).unwrap();
+
+        assert_eq!(json["requests"], 4);
+        assert_eq!(json["status"]["2xx"], 2);
+        assert_eq!(json["status"]["4xx"], 1);
+        assert_eq!(json["status"]["5xx"], 1);
+        assert_eq!(json["mean_latency"], 0.003);
+        // 6ms falls in the bucket covering 5.984ms to 6.015ms
+        assert_eq!(json["p95_latency"], 0.006_015);
+    }
+
+    #[test]
+    fn resetting_serves_the_statistics_since_the_last_reset() {
+        let mut recorder = StatsRecorder::new();
+        let get = |handler: &StatsHandler| {
+            let res = request::get("http://127.0.0.1/stats/reset", Headers::new(), handler).unwrap();
+            serde_json::from_str::<Value>(&response::extract_body_to_string(res)).unwrap()
+        };
+
+        recorder.log(&LogPacket::stub()).unwrap();
+        recorder.log(&LogPacket::stub()).unwrap();
+        let json = get(&recorder.reset_handler());
+        assert_eq!(json["requests"], 2);
+        assert_eq!(json["status"]["2xx"], 2);
+        assert_eq!(json["mean_latency"], 0.0015);
+
+        let mut packet = LogPacket::stub();
+        packet.status = Some(status::NotFound);
+        packet.timing = Duration::from_millis(3);
+        recorder.log(&packet).unwrap();
+        let json = get(&recorder.handler());
+        assert_eq!(json["requests"], 1);
+        assert_eq!(json["status"]["2xx"], 0);
+        assert_eq!(json["status"]["4xx"], 1);
+        assert_eq!(json["mean_latency"], 0.003);
+    }
+
+    #[test]
+    fn packets_logged_during_resets_are_counted_once() {
+        let recorder = StatsRecorder::new();
+        let loggers: Vec<_> = (0..4).map(|_| {
+            let mut recorder = recorder.clone();
+            thread::spawn(move || for _ in 0..1000 {
+                recorder.log(&LogPacket::stub()).unwrap();
+            })
+        }).collect();
+
+        let mut requests = 0;
+        let mut total = Duration::from_secs(0);
+        for _ in 0..100 {
+            let before = recorder.reset();
+            assert_eq!(before.counts.snapshot().success, before.requests());
+            requests += before.requests();
+            total += before.mean_latency() * before.requests() as u32;
+        }
+        for logger in loggers {
+            logger.join().unwrap();
+        }
+        let rest = recorder.reset();
+        requests += rest.requests();
+        total += rest.mean_latency() * rest.requests() as u32;
+
+        assert_eq!(requests, 4000);
+        assert_eq!(total, Duration::from_micros(1500) * 4000);
+    }
+}
@@ -0,0 +1,76 @@
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use super::{LogPacket, LogWriter};
+
+/// Sends a statsd timer for the request duration and a counter per
+/// response status to a statsd server over UDP.
+///
+/// With the default `http` prefix, a successful request is reported
+/// as:
+///
+/// ```text
+/// http.response_time:1.500|ms
+/// http.status.200:1|c
+/// ```
+pub struct StatsdLogger {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdLogger {
+    pub fn new<A>(addr: A) -> io::Result<StatsdLogger>
+        where A: ToSocketAddrs
+    {
+        Ok(StatsdLogger {
+            socket: try!(super::connect_udp(addr)),
+            prefix: "http".into(),
+        })
+    }
+
+    /// Sets the text prepended to every metric name.
+    pub fn prefix(self, prefix: &str) -> Self {
+        StatsdLogger { prefix: prefix.into(), ..self }
+    }
+
+    fn format(&self, packet: &LogPacket) -> String {
+        let timing = packet.timing;
+        let millis = timing.as_secs() as f64 * 1e3 + timing.subsec_nanos() as f64 / 1e6;
+        let status = packet.status
+            .map(|s| s.to_u16().to_string())
+            .unwrap_or_else(|| "unknown".into());
+
+        format!("{prefix}.response_time:{:.3}|ms\n{prefix}.status.{}:1|c",
+                millis, status, prefix = self.prefix)
+    }
+}
+
+impl LogWriter for StatsdLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let metrics = self.format(packet);
+        try!(self.socket.send(metrics.as_bytes()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::str;
+
+    #[test]
+    fn metrics_are_sent_as_statsd_datagrams() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut logger = StatsdLogger::new(server.local_addr().unwrap()).unwrap().prefix("web");
+
+        logger.log(&LogPacket::stub()).unwrap();
+
+        let mut buf = [0; 512];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(str::from_utf8(&buf[..len]).unwrap(),
+                   "web.response_time:1.500|ms\nweb.status.200:1|c");
+    }
+}
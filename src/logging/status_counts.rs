@@ -0,0 +1,258 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::status::Status;
+use iron::Handler;
+use serde_json::{self, Value};
+
+use super::{json_response, status_class, Aggregator, LogPacket, LogWriter};
+
+/// Counts requests per status class. Clones share the same counters;
+/// use `handler` to expose them as JSON.
+///
+/// Under sampling, the counts only cover the logged requests, so an
+/// estimate of every request is kept as well, with each packet
+/// counted as its `estimated_count`.
+#[derive(Debug, Clone, Default)]
+pub struct StatusCounter(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    logged: Classes,
+    // In thousandths of a request
+    estimated: Classes,
+}
+
+#[derive(Debug, Default)]
+struct Classes {
+    success: AtomicU64,
+    redirection: AtomicU64,
+    client_error: AtomicU64,
+    server_error: AtomicU64,
+    unknown: AtomicU64,
+}
+
+impl Classes {
+    fn new(counts: StatusCounts) -> Classes {
+        Classes {
+            success: AtomicU64::new(counts.success),
+            redirection: AtomicU64::new(counts.redirection),
+            client_error: AtomicU64::new(counts.client_error),
+            server_error: AtomicU64::new(counts.server_error),
+            unknown: AtomicU64::new(counts.unknown),
+        }
+    }
+
+    fn class(&self, status: Option<Status>) -> &AtomicU64 {
+        match status_class(status) {
+            "2xx" => &self.success,
+            "3xx" => &self.redirection,
+            "4xx" => &self.client_error,
+            "5xx" => &self.server_error,
+            _ => &self.unknown,
+        }
+    }
+
+    fn load(&self) -> StatusCounts {
+        StatusCounts {
+            success: self.success.load(Ordering::Relaxed),
+            redirection: self.redirection.load(Ordering::Relaxed),
+            client_error: self.client_error.load(Ordering::Relaxed),
+            server_error: self.server_error.load(Ordering::Relaxed),
+            unknown: self.unknown.load(Ordering::Relaxed),
+        }
+    }
+
+    fn swap(&self) -> StatusCounts {
+        StatusCounts {
+            success: self.success.swap(0, Ordering::Relaxed),
+            redirection: self.redirection.swap(0, Ordering::Relaxed),
+            client_error: self.client_error.swap(0, Ordering::Relaxed),
+            server_error: self.server_error.swap(0, Ordering::Relaxed),
+            unknown: self.unknown.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+/// The number of requests seen in each status class. Responses
+/// without a status, and informational ones, are counted as unknown.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StatusCounts {
+    #[serde(rename = "2xx")]
+    pub success: u64,
+    #[serde(rename = "3xx")]
+    pub redirection: u64,
+    #[serde(rename = "4xx")]
+    pub client_error: u64,
+    #[serde(rename = "5xx")]
+    pub server_error: u64,
+    pub unknown: u64,
+}
+
+impl StatusCounter {
+    pub fn new() -> StatusCounter {
+        StatusCounter::default()
+    }
+
+    /// The counts of logged requests so far. Each counter is read
+    /// separately, so a snapshot taken while requests are logged may
+    /// be off by one between classes.
+    pub fn snapshot(&self) -> StatusCounts {
+        self.0.logged.load()
+    }
+
+    /// The estimated counts of every request so far, sampled or not,
+    /// rounded to whole requests. Without sampling they match the
+    /// `snapshot`.
+    pub fn estimated(&self) -> StatusCounts {
+        whole_requests(self.0.estimated.load())
+    }
+
+    /// Zeroes the counts and estimates, returning the counts before.
+    /// Each counter is swapped atomically, so a request logged
+    /// meanwhile is counted either before or after the reset, never
+    /// both.
+    pub fn reset(&self) -> StatusCounts {
+        self.0.estimated.swap();
+        self.0.logged.swap()
+    }
+
+    /// A handler serving the counts as a JSON object like
+    /// `{"2xx":10,"3xx":0,"4xx":2,"5xx":1,"unknown":0}`.
+    pub fn handler(&self) -> StatusCountHandler {
+        StatusCountHandler(self.clone())
+    }
+}
+
+impl Aggregator for StatusCounter {
+    fn observe(&self, packet: &LogPacket) {
+        let estimated = (packet.estimated_count() * 1000.0).round() as u64;
+        self.0.logged.class(packet.status).fetch_add(packet.count as u64, Ordering::Relaxed);
+        self.0.estimated.class(packet.status).fetch_add(estimated, Ordering::Relaxed);
+    }
+
+    /// The counts served by `handler`.
+    fn snapshot(&self) -> Value {
+        serde_json::to_value(StatusCounter::snapshot(self)).expect("Unable to serialize status counts")
+    }
+}
+
+impl LogWriter for StatusCounter {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+fn whole_requests(counts: StatusCounts) -> StatusCounts {
+    let round = |thousandths: u64| (thousandths + 500) / 1000;
+    StatusCounts {
+        success: round(counts.success),
+        redirection: round(counts.redirection),
+        client_error: round(counts.client_error),
+        server_error: round(counts.server_error),
+        unknown: round(counts.unknown),
+    }
+}
+
+/// Resets `counter`, returning a counter that holds the counts and
+/// estimates from before.
+pub fn detach(counter: &StatusCounter) -> StatusCounter {
+    StatusCounter(Arc::new(Counters {
+        estimated: Classes::new(counter.0.estimated.swap()),
+        logged: Classes::new(counter.0.logged.swap()),
+    }))
+}
+
+/// Serves the counts of a `StatusCounter` as JSON.
+pub struct StatusCountHandler(StatusCounter);
+
+impl Handler for StatusCountHandler {
+    fn handle(&self, _req: &mut Request) -> IronResult<Response> {
+        json_response(&self.0.snapshot())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::{status, AroundMiddleware};
+    use serde_json::Value;
+
+    use self::iron_test::{request, response};
+    use self::hyper::header::Headers;
+
+    use super::super::{Sampling, StatisticLoggerBuilder};
+
+    #[test]
+    fn requests_are_counted_by_status_class() {
+        let mut counter = StatusCounter::new();
+        let mut packet = LogPacket::stub();
+        for &status in &[Some(status::Ok), Some(status::Created), Some(status::Found), Some(status::NotFound),
+                         Some(status::Unauthorized), Some(status::NotFound), Some(status::BadGateway), None] {
+            packet.status = status;
+            counter.log(&packet).unwrap();
+        }
+
+        assert_eq!(counter.snapshot(), StatusCounts {
+            success: 2,
+            redirection: 1,
+            client_error: 3,
+            server_error: 1,
+            unknown: 1,
+        });
+    }
+
+    #[test]
+    fn sampled_requests_are_scaled_back_up() {
+        let counter = StatusCounter::new();
+        let logger = StatisticLoggerBuilder::new()
+            .sampling(Sampling::new(0.1).seed(7))
+            .build(counter.clone());
+        let handler = logger.around(Box::new(|_: &mut Request| Ok(Response::with(status::Ok))));
+        for _ in 0..2000 {
+            request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        }
+        drop(handler);
+
+        let logged = counter.snapshot().success;
+        assert!(logged > 150 && logged < 250, "{}", logged);
+        assert_eq!(counter.estimated().success, logged * 10);
+        assert_eq!(counter.estimated().client_error, 0);
+    }
+
+    #[test]
+    fn counts_are_served_as_json() {
+        let mut counter = StatusCounter::new();
+        counter.log(&LogPacket::stub()).unwrap();
+
+        let res = request::get("http://127.0.0.1/status", Headers::new(), &counter.handler()).unwrap();
+        let json: Value = serde_json::from_str(&response::extract_body_to_string(res)).unwrap();
+
+        assert_eq!(json["2xx"], 1);
+        assert_eq!(json["5xx"], 0);
+        assert_eq!(json["unknown"], 0);
+    }
+
+    #[test]
+    fn counts_start_over_after_a_reset() {
+        let mut counter = StatusCounter::new();
+        let mut packet = LogPacket::stub();
+        counter.log(&packet).unwrap();
+        counter.log(&packet).unwrap();
+
+        assert_eq!(counter.reset().success, 2);
+        packet.status = Some(status::NotFound);
+        counter.log(&packet).unwrap();
+
+        assert_eq!(counter.snapshot(), StatusCounts { client_error: 1, ..StatusCounts::default() });
+    }
+}
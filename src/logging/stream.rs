@@ -0,0 +1,121 @@
+use std::io::{self, BufWriter, Write};
+
+use csv;
+
+use super::{CsvFormatter, CsvQuoting, Encoding, Formatter, LogPacket, LogWriter};
+
+/// Records statistics to any stream, such as stdout for platforms that
+/// collect a process' output. Packets are written as CSV rows, without
+/// a header row, unless another `Formatter` is given.
+pub struct StreamLogger<W: Write, F = CsvFormatter> {
+    stream: BufWriter<W>,
+    formatter: F,
+}
+
+impl<W: Write> StreamLogger<W> {
+    pub fn new(stream: W) -> StreamLogger<W> {
+        StreamLogger::with_formatter(stream, CsvFormatter::new())
+    }
+
+    /// Separates fields with `delimiter` instead of a comma.
+    pub fn with_delimiter(stream: W, delimiter: u8) -> StreamLogger<W> {
+        StreamLogger::with_formatter(stream, CsvFormatter::new().delimiter(delimiter))
+    }
+
+    /// Quotes fields as `quoting` says.
+    pub fn with_quoting(stream: W, quoting: CsvQuoting) -> StreamLogger<W> {
+        StreamLogger::with_formatter(stream, CsvFormatter::new().quoting(quoting))
+    }
+
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        StreamLogger { formatter: self.formatter.encoding(encoding), ..self }
+    }
+}
+
+impl<W: Write, F: Formatter> StreamLogger<W, F> {
+    /// Renders packets with `formatter`, such as a `JsonFormatter`.
+    pub fn with_formatter(stream: W, formatter: F) -> StreamLogger<W, F> {
+        StreamLogger {
+            stream: BufWriter::new(stream),
+            formatter: formatter,
+        }
+    }
+}
+
+impl StreamLogger<Box<Write + Send>> {
+    /// Writes to a stream known only as an `io::Write`, such as a
+    /// channel adapter or a pipe to another process, without naming
+    /// its type. Give a boxed stream to `with_formatter` to write
+    /// another format.
+    pub fn boxed<S>(stream: S) -> StreamLogger<Box<Write + Send>>
+        where S: Write + Send + 'static
+    {
+        StreamLogger::new(Box::new(stream))
+    }
+}
+
+impl StreamLogger<io::Stdout> {
+    pub fn stdout() -> StreamLogger<io::Stdout> {
+        StreamLogger::new(io::stdout())
+    }
+}
+
+impl StreamLogger<io::Stderr> {
+    pub fn stderr() -> StreamLogger<io::Stderr> {
+        StreamLogger::new(io::stderr())
+    }
+}
+
+impl<W: Write, F: Formatter> LogWriter for StreamLogger<W, F> {
+    type Error = csv::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        try!(self.formatter.format(packet, &mut self.stream));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        try!(self.stream.flush());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use super::super::{Column, JsonFormatter};
+
+    /// A `Vec<u8>` that is still readable once boxed.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn boxed_streams_get_formatted_packets() {
+        let csv = Shared::default();
+        let mut logger = StreamLogger::boxed(csv.clone())
+            .encoding(Encoding::new().columns(&[Column::Method, Column::Path, Column::Status]));
+        logger.log(&LogPacket::stub()).unwrap();
+        logger.flush().unwrap();
+        assert_eq!(*csv.0.lock().unwrap(), b"GET,/,200\n");
+
+        let json = Shared::default();
+        let stream: Box<Write + Send> = Box::new(json.clone());
+        let mut logger = StreamLogger::with_formatter(stream, JsonFormatter::new());
+        logger.log(&LogPacket::stub()).unwrap();
+        drop(logger);
+        assert_eq!(*json.0.lock().unwrap(), super::super::json::encode(&LogPacket::stub()).unwrap());
+    }
+}
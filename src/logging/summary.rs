@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use super::{LogPacket, LogWriter};
+
+/// Totals of everything a `Summarized` writer logged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub requests: u64,
+    /// The bytes of responses whose size was known up front.
+    pub response_bytes: u64,
+    /// The time from creating the writer until logging stopped.
+    pub uptime: Duration,
+}
+
+/// Gives a writer a `Summary` of the packets it logged once logging
+/// stops, when the logger drains at shutdown, as a final record. How
+/// the summary is written is up to the writer's `finalize`; writers
+/// without one ignore it. Each writer of a pool is summarized
+/// separately.
+pub struct Summarized<L: LogWriter> {
+    logger: L,
+    started: Instant,
+    requests: u64,
+    response_bytes: u64,
+}
+
+impl<L: LogWriter> Summarized<L> {
+    pub fn new(logger: L) -> Summarized<L> {
+        Summarized {
+            logger: logger,
+            started: Instant::now(),
+            requests: 0,
+            response_bytes: 0,
+        }
+    }
+}
+
+impl<L: LogWriter> LogWriter for Summarized<L> {
+    type Error = L::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.requests += packet.count as u64;
+        self.response_bytes += packet.response_len.unwrap_or(0);
+        self.logger.log(packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.logger.flush()
+    }
+}
+
+impl<L: LogWriter> Drop for Summarized<L> {
+    fn drop(&mut self) {
+        let summary = Summary {
+            requests: self.requests,
+            response_bytes: self.response_bytes,
+            uptime: self.started.elapsed(),
+        };
+        if let Err(e) = self.logger.finalize(&summary).and_then(|()| self.logger.flush()) {
+            error!("Unable to write request log summary: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use iron::prelude::*;
+    use iron::{status, AroundMiddleware};
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::StatisticLogger;
+
+    struct Lines(Arc<Mutex<Vec<String>>>);
+
+    impl LogWriter for Lines {
+        type Error = io::Error;
+
+        fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push(packet.path().to_owned());
+            Ok(())
+        }
+
+        fn finalize(&mut self, summary: &Summary) -> Result<(), Self::Error> {
+            self.0.lock().unwrap().push(format!("{} requests, {} bytes", summary.requests, summary.response_bytes));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn the_summary_is_written_after_the_last_packet() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let handler = StatisticLogger::new(Summarized::new(Lines(lines.clone())))
+            .around(Box::new(|_req: &mut Request| Ok(Response::with((status::Ok, "Hello, world!")))));
+
+        request::get("http://127.0.0.1/a", Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/b", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        assert_eq!(*lines.lock().unwrap(), vec!["/a", "/b", "2 requests, 26 bytes"]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::process;
+
+use iron::status::Status;
+
+use super::{network, LogPacket, LogWriter, NetworkOptions};
+
+/// Syslog facility codes from RFC 5424.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Facility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    AuthPriv = 10,
+    Ftp = 11,
+    Ntp = 12,
+    Audit = 13,
+    Alert = 14,
+    Clock = 15,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Syslog severity levels from RFC 5424.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Informational = 6,
+    Debug = 7,
+}
+
+/// Chooses the severity of a request from its status, for the
+/// leveled writers: syslog, `log` and `tracing`.
+pub type SeverityMapping = Box<Fn(Option<Status>) -> Severity + Send>;
+
+/// Server errors are warnings and everything else is informational.
+pub fn default_severity(status: Option<Status>) -> Severity {
+    match status {
+        Some(status) if status.is_server_error() => Severity::Warning,
+        _ => Severity::Informational,
+    }
+}
+
+/// Sends each request as an RFC 5424 syslog message over UDP.
+///
+/// The status, timing and method are carried as structured data, and
+/// the message itself is a short human readable summary:
+///
+/// ```text
+/// <14>1 2017-07-14T02:40:00.000000Z web-1 shop 4242 request [request@32473 method="GET" status="200" timing="0.001500000"] GET http://127.0.0.1/ 200
+/// ```
+///
+/// The hostname and app-name default to the syslog nil value `-`.
+/// Every message is informational unless `severity` or
+/// `severity_mapping` says otherwise.
+pub struct SyslogLogger {
+    socket: UdpSocket,
+    facility: Facility,
+    severity: SeverityMapping,
+    hostname: String,
+    app_name: String,
+}
+
+impl SyslogLogger {
+    pub fn new<A>(addr: A) -> io::Result<SyslogLogger>
+        where A: ToSocketAddrs
+    {
+        SyslogLogger::with_options(addr, NetworkOptions::default())
+    }
+
+    /// Like `new`, but sends with the write timeout of `options`.
+    pub fn with_options<A>(addr: A, options: NetworkOptions) -> io::Result<SyslogLogger>
+        where A: ToSocketAddrs
+    {
+        let socket = try!(super::connect_udp(addr));
+        try!(network::configure_udp(&socket, &options));
+
+        Ok(SyslogLogger {
+            socket: socket,
+            facility: Facility::User,
+            severity: Box::new(|_| Severity::Informational),
+            hostname: "-".into(),
+            app_name: "-".into(),
+        })
+    }
+
+    pub fn facility(self, facility: Facility) -> Self {
+        SyslogLogger { facility: facility, ..self }
+    }
+
+    /// Sends every message with `severity`.
+    pub fn severity(self, severity: Severity) -> Self {
+        SyslogLogger { severity: Box::new(move |_| severity), ..self }
+    }
+
+    /// Chooses the severity of each message from the status, such as
+    /// with `default_severity`.
+    pub fn severity_mapping<F>(self, mapping: F) -> Self
+        where F: Fn(Option<Status>) -> Severity + Send + 'static
+    {
+        SyslogLogger { severity: Box::new(mapping), ..self }
+    }
+
+    pub fn hostname(self, hostname: &str) -> Self {
+        SyslogLogger { hostname: header_field(hostname, 255), ..self }
+    }
+
+    pub fn app_name(self, app_name: &str) -> Self {
+        SyslogLogger { app_name: header_field(app_name, 48), ..self }
+    }
+
+    fn format(&self, packet: &LogPacket) -> String {
+        let priority = self.facility as u8 * 8 + (self.severity)(packet.status) as u8;
+        let status = packet.status
+            .map(|s| s.to_u16().to_string())
+            .unwrap_or_else(|| "-".into());
+
+        format!("<{}>1 {} {} {} {} request [request@32473 method=\"{}\" status=\"{}\" timing=\"{}\"] {} {} {}",
+                priority,
+                super::rfc3339(packet.start),
+                self.hostname,
+                self.app_name,
+                process::id(),
+                escape(packet.method.as_ref()),
+                status,
+                super::format_duration(&packet.timing),
+                packet.method,
+                packet.url,
+                status)
+    }
+}
+
+impl LogWriter for SyslogLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let message = self.format(packet);
+        try!(self.socket.send(message.as_bytes()));
+        Ok(())
+    }
+}
+
+/// Header fields are printable ASCII without spaces, and `-` when empty.
+fn header_field(value: &str, max_len: usize) -> String {
+    let value: String = value.chars().filter(|c| c.is_ascii_graphic()).take(max_len).collect();
+    if value.is_empty() { "-".into() } else { value }
+}
+
+/// Escapes a structured data parameter value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace(']', "\\]")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::str;
+
+    #[test]
+    fn messages_are_sent_as_rfc5424() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut logger = SyslogLogger::new(server.local_addr().unwrap()).unwrap()
+            .facility(Facility::Local0)
+            .severity(Severity::Notice)
+            .hostname("web-1")
+            .app_name("shop");
+
+        logger.log(&LogPacket::stub()).unwrap();
+
+        let mut buf = [0; 1024];
+        let len = server.recv(&mut buf).unwrap();
+        let message = str::from_utf8(&buf[..len]).unwrap();
+        let expected_start = "<133>1 2017-07-14T02:40:00.000000Z web-1 shop ";
+        assert!(message.starts_with(expected_start), "{}", message);
+        assert!(message.ends_with(
+            r#" request [request@32473 method="GET" status="200" timing="0.001500000"] GET http://127.0.0.1/ 200"#),
+            "{}", message);
+    }
+
+    #[test]
+    fn severities_can_be_mapped_from_the_status() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut logger = SyslogLogger::new(server.local_addr().unwrap()).unwrap()
+            .severity_mapping(|status| match status {
+                Some(::iron::status::NotFound) => Severity::Debug,
+                status => default_severity(status),
+            });
+
+        let mut packet = LogPacket::stub();
+        let mut priorities = Vec::new();
+        for &status in &[::iron::status::Ok, ::iron::status::NotFound, ::iron::status::BadGateway] {
+            packet.status = Some(status);
+            logger.log(&packet).unwrap();
+
+            let mut buf = [0; 1024];
+            let len = server.recv(&mut buf).unwrap();
+            priorities.push(str::from_utf8(&buf[..len]).unwrap().split('>').next().unwrap()[1..].to_owned());
+        }
+        // The user facility is 1, so priorities start at 8
+        assert_eq!(priorities, vec!["14", "15", "12"]);
+    }
+}
@@ -0,0 +1,162 @@
+use std::io;
+use std::mem;
+use std::net::ToSocketAddrs;
+
+use super::{json, network, Encoding, LogPacket, LogWriter, NetworkOptions};
+
+/// Records are sent once this many bytes are waiting.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// How each record is encoded on the wire.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TcpFormat {
+    /// One CSV row per packet, without a header.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Streams statistics to a log collector over TCP.
+///
+/// Records are buffered and sent when the buffer fills or when the
+/// logger is flushed. If the connection breaks, the logger reconnects
+/// and resends the buffered records once before giving up on them.
+/// How it connects is set with `NetworkOptions`.
+pub struct TcpLogger {
+    connection: network::Connection,
+    format: TcpFormat,
+    buffer: Vec<u8>,
+    encoding: Encoding,
+}
+
+impl TcpLogger {
+    pub fn new<A>(addr: A) -> io::Result<TcpLogger>
+        where A: ToSocketAddrs
+    {
+        TcpLogger::with_options(addr, NetworkOptions::default())
+    }
+
+    /// Like `new`, but connects as `options` says.
+    pub fn with_options<A>(addr: A, options: NetworkOptions) -> io::Result<TcpLogger>
+        where A: ToSocketAddrs
+    {
+        let addrs: Vec<_> = try!(addr.to_socket_addrs()).collect();
+
+        Ok(TcpLogger {
+            connection: try!(network::Connection::open(addrs, options)),
+            format: TcpFormat::Csv,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            encoding: Encoding::default(),
+        })
+    }
+
+    pub fn format(self, format: TcpFormat) -> Self {
+        TcpLogger { format: format, ..self }
+    }
+
+    /// Sets how CSV records are encoded.
+    pub fn encoding(self, encoding: Encoding) -> Self {
+        TcpLogger { encoding: encoding, ..self }
+    }
+
+    fn encode(&self, packet: &LogPacket) -> io::Result<Vec<u8>> {
+        match self.format {
+            TcpFormat::Csv => super::encode_csv(packet, &self.encoding).map_err(io::Error::other),
+            TcpFormat::Json => json::encode(packet).map_err(io::Error::from),
+        }
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let buffer = mem::take(&mut self.buffer);
+
+        let result = self.connection.write_all(&buffer).or_else(|_| {
+            self.connection.reset();
+            self.connection.write_all(&buffer)
+        });
+        if result.is_err() {
+            self.connection.reset();
+        }
+
+        self.buffer = buffer;
+        self.buffer.clear();
+        result
+    }
+}
+
+impl LogWriter for TcpLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let record = try!(self.encode(packet));
+        self.buffer.extend_from_slice(&record);
+
+        if self.buffer.len() >= BUFFER_SIZE {
+            try!(self.send());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::{BufRead, BufReader};
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::{Duration, Instant};
+
+    use socket2::{Domain, Socket, Type};
+
+    #[test]
+    fn records_are_sent_to_the_collector() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut csv = TcpLogger::new(addr).unwrap();
+        let mut json = TcpLogger::new(addr).unwrap().format(TcpFormat::Json);
+
+        csv.log(&LogPacket::stub()).unwrap();
+        csv.log(&LogPacket::stub()).unwrap();
+        csv.flush().unwrap();
+        json.log(&LogPacket::stub()).unwrap();
+        json.flush().unwrap();
+
+        let received = |listener: &TcpListener, n| {
+            let (conn, _) = listener.accept().unwrap();
+            BufReader::new(conn).lines().take(n).map(Result::unwrap).collect::<Vec<_>>()
+        };
+
+        let row = String::from_utf8(super::super::encode_csv(&LogPacket::stub(), &Encoding::default()).unwrap()).unwrap();
+        assert_eq!(received(&listener, 2), vec![row.trim_end(), row.trim_end()]);
+
+        let lines = received(&listener, 1);
+        assert!(lines[0].starts_with(r#"{"url":"http://127.0.0.1/""#), "{}", lines[0]);
+    }
+
+    #[test]
+    fn connecting_to_a_collector_that_never_accepts_times_out() {
+        // A listener whose queue is full leaves further connections
+        // unanswered, like a hung collector
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let listener = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+        listener.bind(&addr.into()).unwrap();
+        listener.listen(0).unwrap();
+        let addr = listener.local_addr().unwrap().as_socket().unwrap();
+        let options = NetworkOptions::new().connect_timeout(Some(Duration::from_millis(200)));
+        let queued: Vec<_> = (0..4).filter_map(|_| TcpLogger::with_options(addr, options).ok()).collect();
+
+        let started = Instant::now();
+        let error = TcpLogger::with_options(addr, options).err().expect("Connected to a full listener");
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(queued);
+    }
+}
@@ -0,0 +1,77 @@
+use iron::prelude::*;
+use iron::typemap::Key;
+
+/// The TLS parameters negotiated for the connection a request came
+/// in on, such as `TLSv1.3` and `TLS_AES_128_GCM_SHA256`. Iron does
+/// not expose them, so servers terminating TLS in-process should set
+/// them from their listener, for example in a `BeforeMiddleware`
+/// linked before the logger. Plaintext requests leave them unset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    pub version: String,
+    pub cipher: String,
+}
+
+impl Key for TlsInfo {
+    type Value = TlsInfo;
+}
+
+impl TlsInfo {
+    pub fn set(req: &mut Request, version: &str, cipher: &str) {
+        req.extensions.insert::<TlsInfo>(TlsInfo {
+            version: version.into(),
+            cipher: cipher.into(),
+        });
+    }
+}
+
+/// The TLS parameters set for the request, if any.
+pub fn get(req: &Request) -> Option<TlsInfo> {
+    req.extensions.get::<TlsInfo>().cloned()
+}
+
+#[cfg(test)]
+mod test {
+    extern crate iron_test;
+    extern crate hyper;
+
+    use super::*;
+
+    use iron::{status, AroundMiddleware, Handler};
+
+    use self::iron_test::request;
+    use self::hyper::header::Headers;
+
+    use super::super::{json, Column, Encoding, MemoryLogger, StatisticLogger};
+
+    #[test]
+    fn negotiated_parameters_are_logged() {
+        let memory = MemoryLogger::new();
+        let logged = StatisticLogger::new(memory.clone()).around(Box::new(|_: &mut Request| {
+            Ok(Response::with(status::Ok))
+        }));
+        // Stands in for a listener that terminated TLS on port 443
+        let handler = move |req: &mut Request| {
+            if req.url.port() == 443 {
+                TlsInfo::set(req, "TLSv1.3", "TLS_AES_128_GCM_SHA256");
+            }
+            logged.handle(req)
+        };
+
+        request::get("https://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        request::get("http://127.0.0.1/", Headers::new(), &handler).expect("Request failed");
+        drop(handler);
+
+        let packets = memory.packets();
+        assert_eq!(packets[0].tls_version(), Some("TLSv1.3"));
+        assert_eq!(packets[0].tls_cipher(), Some("TLS_AES_128_GCM_SHA256"));
+        assert_eq!(packets[1].tls_version(), None);
+        assert_eq!(packets[1].tls_cipher(), None);
+
+        let encoding = Encoding::default().columns(&[Column::TlsVersion, Column::TlsCipher]);
+        let row = super::super::encode_csv(&packets[0], &encoding).unwrap();
+        assert_eq!(String::from_utf8(row).unwrap(), "TLSv1.3,TLS_AES_128_GCM_SHA256\n");
+        let json = String::from_utf8(json::encode(&packets[1]).unwrap()).unwrap();
+        assert!(json.contains(r#""tls_version":null,"tls_cipher":null"#), "{}", json);
+    }
+}
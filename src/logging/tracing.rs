@@ -0,0 +1,159 @@
+use std::io;
+
+use iron::status::Status;
+use tracing::{event, Level};
+
+use super::{syslog, LogPacket, LogWriter, Severity};
+
+/// Emits each request as a `tracing` event with structured fields:
+/// `method`, `path`, `status`, `duration_us`, `ip` and `request_id`.
+///
+/// Server errors are emitted at the `WARN` level and everything else
+/// at `INFO`, unless `severity_mapping` says otherwise, all with the
+/// `access` target. Events are emitted from the logging thread, so
+/// they are not part of the request's span.
+pub struct TracingLogger {
+    severity: syslog::SeverityMapping,
+}
+
+impl TracingLogger {
+    pub fn new() -> TracingLogger {
+        TracingLogger { severity: Box::new(syslog::default_severity) }
+    }
+
+    /// Chooses the level of each event from the status. Emergencies
+    /// through errors are emitted as `ERROR`, and notices as `INFO`.
+    pub fn severity_mapping<F>(self, mapping: F) -> Self
+        where F: Fn(Option<Status>) -> Severity + Send + 'static
+    {
+        TracingLogger { severity: Box::new(mapping) }
+    }
+}
+
+impl Default for TracingLogger {
+    fn default() -> TracingLogger {
+        TracingLogger::new()
+    }
+}
+
+impl LogWriter for TracingLogger {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        let status = packet.status.map(|s| s.to_u16()).unwrap_or(0);
+        let duration_us = packet.timing.as_secs() * 1_000_000 + packet.timing.subsec_micros() as u64;
+        let method = packet.method.as_ref();
+        let path = packet.path();
+        let ip = super::format_ip(packet);
+        let request_id = &packet.request_id[..];
+
+        // Each level needs its own event, as the level is fixed at the
+        // call site
+        macro_rules! emit {
+            ($level:expr) => {
+                event!(target: "access", $level, method, path, status, duration_us, ip = &ip[..], request_id)
+            }
+        }
+        match (self.severity)(packet.status) {
+            Severity::Emergency | Severity::Alert | Severity::Critical | Severity::Error => emit!(Level::ERROR),
+            Severity::Warning => emit!(Level::WARN),
+            Severity::Notice | Severity::Informational => emit!(Level::INFO),
+            Severity::Debug => emit!(Level::DEBUG),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::subscriber::{self, Subscriber};
+    use tracing::{Event, Metadata};
+
+    type CapturedEvent = (Level, BTreeMap<String, String>);
+
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<CapturedEvent>>>);
+
+    struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+    impl<'a> Visit for Fields<'a> {
+        fn record_debug(&mut self, field: &Field, value: &fmt::Debug) {
+            self.0.insert(field.name().into(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().into(), value.into());
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, _span: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut fields = BTreeMap::new();
+            event.record(&mut Fields(&mut fields));
+            self.0.lock().unwrap().push((*event.metadata().level(), fields));
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    fn packets_are_emitted_as_events() {
+        let capture = Capture::default();
+
+        subscriber::with_default(capture.clone(), || {
+            TracingLogger::new().log(&LogPacket::stub()).unwrap();
+        });
+
+        let events = capture.0.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let (level, ref fields) = events[0];
+        assert_eq!(level, Level::INFO);
+        assert_eq!(fields["method"], "GET");
+        assert_eq!(fields["path"], "/");
+        assert_eq!(fields["status"], "200");
+        assert_eq!(fields["duration_us"], "1500");
+        assert_eq!(fields["ip"], "127.0.0.1:3000");
+        assert_eq!(fields["request_id"], "0b8cc2e6-2ba7-4b5c-9e3e-4f26e3a1a2de");
+    }
+
+    #[test]
+    fn levels_can_be_mapped_from_the_status() {
+        let capture = Capture::default();
+        let mut packet = LogPacket::stub();
+        packet.status = Some(::iron::status::NotFound);
+
+        subscriber::with_default(capture.clone(), || {
+            TracingLogger::new()
+                .severity_mapping(|status| match status {
+                    Some(::iron::status::NotFound) => Severity::Debug,
+                    status => syslog::default_severity(status),
+                })
+                .log(&packet)
+                .unwrap();
+        });
+
+        assert_eq!(capture.0.lock().unwrap()[0].0, Level::DEBUG);
+    }
+}
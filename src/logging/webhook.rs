@@ -0,0 +1,297 @@
+use std::io;
+use std::time::{Duration, Instant};
+use std::{error, fmt, mem, thread};
+
+use hyper::client::Client;
+use hyper::header::ContentType;
+use hyper::status::StatusCode;
+use hyper;
+use serde_json;
+use url::{self, Url};
+
+use super::{json, LogPacket, LogWriter};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_TIMEOUT: u64 = 10;
+
+/// POSTs statistics to an HTTP ingest endpoint as a JSON array of
+/// packets, in the same format as `JsonLogger`.
+///
+/// Packets are held in memory until 100 are waiting, or until the
+/// logger is flushed at least 5 seconds after the oldest of them
+/// arrived, and are sent when the logger is dropped. Requests that
+/// fail with a server error or without a response are retried up to 3
+/// times in all; if every attempt fails, the batch is dropped and the
+/// error returned.
+pub struct WebhookLogger {
+    client: Client,
+    url: Url,
+    batch_size: usize,
+    interval: Duration,
+    max_attempts: u32,
+    retry_delay: Duration,
+    pending: Vec<LogPacket>,
+    oldest: Instant,
+}
+
+impl WebhookLogger {
+    pub fn new(url: &str) -> Result<WebhookLogger, url::ParseError> {
+        let mut client = Client::new();
+        client.set_read_timeout(Some(Duration::from_secs(DEFAULT_TIMEOUT)));
+        client.set_write_timeout(Some(Duration::from_secs(DEFAULT_TIMEOUT)));
+
+        Ok(WebhookLogger {
+            client: client,
+            url: try!(Url::parse(url)),
+            batch_size: DEFAULT_BATCH_SIZE,
+            interval: Duration::from_secs(5),
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(100),
+            pending: Vec::new(),
+            oldest: Instant::now(),
+        })
+    }
+
+    /// Sends as soon as `size` packets are waiting. This also bounds
+    /// the number of packets held in memory.
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Sends when the logger is flushed once the oldest waiting packet
+    /// is this old.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The total number of attempts per batch, including the first
+    /// one.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// How long to wait before the first retry. Each retry after that
+    /// waits twice as long as the last.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    fn send(&mut self) -> Result<(), WebhookError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let packets = mem::take(&mut self.pending);
+        let body = try!(json::encode_array(&packets));
+
+        let mut delay = self.retry_delay;
+        let mut attempt = 1;
+        loop {
+            match self.post(&body) {
+                Err(ref e) if e.is_retryable() && attempt < self.max_attempts => {}
+                result => return result,
+            }
+
+            thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), WebhookError> {
+        let mut response = try!(self.client.post(self.url.clone())
+            .header(ContentType::json())
+            .body(body)
+            .send());
+
+        // Read the body so the connection can be reused
+        let _ = io::copy(&mut response, &mut io::sink());
+        if response.status.is_success() {
+            Ok(())
+        } else {
+            Err(WebhookError::Status(response.status))
+        }
+    }
+}
+
+impl LogWriter for WebhookLogger {
+    type Error = WebhookError;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        if self.pending.is_empty() {
+            self.oldest = Instant::now();
+        }
+        self.pending.push(packet.clone());
+
+        if self.pending.len() >= self.batch_size {
+            try!(self.send());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.oldest.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+impl Drop for WebhookLogger {
+    fn drop(&mut self) {
+        if let Err(e) = self.send() {
+            error!("Unable to send request log: {}", e);
+        }
+    }
+}
+
+/// Why a `WebhookLogger` could not deliver a batch.
+#[derive(Debug)]
+pub enum WebhookError {
+    /// The packets could not be encoded.
+    Encode(serde_json::Error),
+    /// The endpoint could not be reached.
+    Request(hyper::Error),
+    /// The endpoint responded with a status other than success.
+    Status(StatusCode),
+}
+
+impl WebhookError {
+    fn is_retryable(&self) -> bool {
+        match *self {
+            WebhookError::Encode(_) => false,
+            WebhookError::Request(_) => true,
+            WebhookError::Status(status) => status.is_server_error(),
+        }
+    }
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WebhookError::Encode(ref e) => write!(f, "Unable to encode requests: {}", e),
+            WebhookError::Request(ref e) => write!(f, "Unable to reach the webhook: {}", e),
+            WebhookError::Status(status) => write!(f, "The webhook responded with {}", status),
+        }
+    }
+}
+
+impl error::Error for WebhookError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            WebhookError::Encode(ref e) => Some(e),
+            WebhookError::Request(ref e) => Some(e),
+            WebhookError::Status(_) => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for WebhookError {
+    fn from(e: serde_json::Error) -> WebhookError {
+        WebhookError::Encode(e)
+    }
+}
+
+impl From<hyper::Error> for WebhookError {
+    fn from(e: hyper::Error) -> WebhookError {
+        WebhookError::Request(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+
+    use serde_json::Value;
+
+    /// Answers one request per status with that status, sending each
+    /// request body back to the test.
+    fn serve(statuses: Vec<u16>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ingest", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for status in statuses {
+                let (conn, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(conn.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                tx.send(String::from_utf8(body).unwrap()).unwrap();
+
+                write!(&conn, "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status)
+                    .unwrap();
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[test]
+    fn batches_are_posted_as_json_arrays() {
+        let (url, bodies) = serve(vec![200, 200]);
+        let mut logger = WebhookLogger::new(&url).unwrap().batch_size(2);
+
+        let mut packet = LogPacket::stub();
+        logger.log(&packet).unwrap();
+        packet.method = ::iron::method::Method::Post;
+        logger.log(&packet).unwrap();
+        logger.log(&packet).unwrap();
+
+        let batch: Value = serde_json::from_str(&bodies.recv().unwrap()).unwrap();
+        let methods: Vec<_> = batch.as_array().unwrap().iter().map(|p| p["method"].clone()).collect();
+        assert_eq!(methods, vec!["GET", "POST"]);
+        assert_eq!(batch[0]["url"], "http://127.0.0.1/");
+
+        drop(logger);
+        let batch: Value = serde_json::from_str(&bodies.recv().unwrap()).unwrap();
+        assert_eq!(batch.as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn server_errors_are_retried() {
+        let (url, bodies) = serve(vec![503, 200]);
+        let mut logger = WebhookLogger::new(&url).unwrap().batch_size(1).retry_delay(Duration::from_millis(1));
+
+        logger.log(&LogPacket::stub()).unwrap();
+        assert_eq!(bodies.recv().unwrap(), bodies.recv().unwrap());
+    }
+
+    #[test]
+    fn batches_are_dropped_after_the_last_attempt() {
+        let (url, bodies) = serve(vec![500, 500, 404]);
+        let mut logger = WebhookLogger::new(&url).unwrap()
+            .batch_size(1)
+            .max_attempts(2)
+            .retry_delay(Duration::from_millis(1));
+
+        let e = logger.log(&LogPacket::stub()).unwrap_err();
+        assert_eq!(e.to_string(), "The webhook responded with 500 Internal Server Error");
+        assert_eq!(bodies.iter().take(2).count(), 2);
+
+        // Client errors are not retried, and nothing is left to send
+        let e = logger.log(&LogPacket::stub()).unwrap_err();
+        assert_eq!(e.to_string(), "The webhook responded with 404 Not Found");
+        drop(logger);
+        assert_eq!(bodies.recv().map(|body| body.len() > 2), Ok(true));
+        assert!(bodies.recv().is_err());
+    }
+}
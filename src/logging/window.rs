@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use iron::status::Status;
+
+use serde_json::Value;
+
+use super::{lock, Aggregator, LogPacket, LogWriter};
+
+type Entry = (Option<Status>, Duration);
+
+/// Keeps the status and timing of the most recent requests, for a
+/// rolling view of the error rate and latency. Clones share the same
+/// window.
+#[derive(Debug, Clone)]
+pub struct SlidingWindow {
+    size: usize,
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+}
+
+impl SlidingWindow {
+    /// Covers the last `size` requests.
+    pub fn new(size: usize) -> SlidingWindow {
+        SlidingWindow {
+            size: size,
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(size))),
+        }
+    }
+
+    /// The number of requests currently in the window.
+    pub fn len(&self) -> usize {
+        lock(&self.entries).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The fraction of requests in the window that failed with a
+    /// server error or without a status, from 0 to 1.
+    pub fn error_rate(&self) -> f64 {
+        let entries = lock(&self.entries);
+        if entries.is_empty() {
+            return 0.0;
+        }
+        let errors = entries.iter()
+            .filter(|&&(status, _)| status.is_none_or(|s| s.is_server_error()))
+            .count();
+        errors as f64 / entries.len() as f64
+    }
+
+    /// The mean timing of the requests in the window.
+    pub fn mean_latency(&self) -> Duration {
+        let entries = lock(&self.entries);
+        if entries.is_empty() {
+            return Duration::from_secs(0);
+        }
+        let total: Duration = entries.iter().map(|&(_, timing)| timing).sum();
+        Duration::from_nanos((total.as_nanos() / entries.len() as u128) as u64)
+    }
+}
+
+impl Aggregator for SlidingWindow {
+    fn observe(&self, packet: &LogPacket) {
+        let mut entries = lock(&self.entries);
+        if self.size == 0 {
+            return;
+        }
+        if entries.len() == self.size {
+            entries.pop_front();
+        }
+        entries.push_back((packet.status, packet.timing));
+    }
+
+    /// The window, with the latency in seconds, like
+    /// `{"requests":100,"error_rate":0.02,"mean_latency":0.0015}`.
+    fn snapshot(&self) -> Value {
+        json!({
+            "requests": self.len(),
+            "error_rate": self.error_rate(),
+            "mean_latency": self.mean_latency().as_secs_f64(),
+        })
+    }
+}
+
+impl LogWriter for SlidingWindow {
+    type Error = io::Error;
+
+    fn log(&mut self, packet: &LogPacket) -> Result<(), Self::Error> {
+        self.observe(packet);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use iron::status;
+
+    fn log(window: &mut SlidingWindow, status: Status, millis: u64) {
+        let mut packet = LogPacket::stub();
+        packet.status = Some(status);
+        packet.timing = Duration::from_millis(millis);
+        window.log(&packet).unwrap();
+    }
+
+    #[test]
+    fn only_the_most_recent_requests_are_counted() {
+        let mut window = SlidingWindow::new(4);
+        for _ in 0..4 {
+            log(&mut window, status::InternalServerError, 100);
+        }
+        assert_eq!(window.error_rate(), 1.0);
+
+        log(&mut window, status::Ok, 10);
+        log(&mut window, status::Ok, 10);
+        log(&mut window, status::NotFound, 20);
+
+        assert_eq!(window.len(), 4);
+        assert_eq!(window.error_rate(), 0.25);
+        assert_eq!(window.mean_latency(), Duration::from_millis(35));
+    }
+
+    #[test]
+    fn an_empty_window_has_no_errors() {
+        let window = SlidingWindow::new(10);
+
+        assert!(window.is_empty());
+        assert_eq!(window.error_rate(), 0.0);
+        assert_eq!(window.mean_latency(), Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use iron::headers::Headers;
+use iron::method::Method;
+use iron::prelude::*;
+use iron::{AroundMiddleware, Handler};
+use iron_test::request;
+
+use logging::{LogPacket, LoggerHandle, MemoryLogger, StatisticLogger, StatisticLoggerBuilder};
+
+/// How long `packets` waits for the logging thread.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A handler wrapped in a `StatisticLogger` that keeps every packet in
+/// memory, for testing what gets logged without running a server.
+/// Requests are made with `iron_test`, straight through the handler.
+///
+/// ```
+/// # extern crate iron;
+/// # extern crate playground_middleware;
+/// use iron::prelude::*;
+/// use iron::status;
+/// use playground_middleware::testing::LoggedHandler;
+///
+/// # fn main() {
+/// let handler = LoggedHandler::new(|_: &mut Request| Ok(Response::with(status::Created)));
+/// handler.get("http://127.0.0.1/widgets").unwrap();
+///
+/// let packets = handler.packets();
+/// assert_eq!(packets[0].status(), Some(status::Created));
+/// # }
+/// ```
+pub struct LoggedHandler {
+    handler: Box<Handler>,
+    memory: MemoryLogger,
+    handle: LoggerHandle,
+}
+
+impl LoggedHandler {
+    pub fn new<H: Handler>(handler: H) -> LoggedHandler {
+        LoggedHandler::with_builder(StatisticLogger::builder(), handler)
+    }
+
+    /// Logs with the options set on `builder`, such as a `clock` or
+    /// the headers to capture.
+    pub fn with_builder<H: Handler>(builder: StatisticLoggerBuilder, handler: H) -> LoggedHandler {
+        let memory = MemoryLogger::new();
+        let logger = builder.build(memory.clone());
+        let handle = logger.handle();
+        LoggedHandler {
+            handler: logger.around(Box::new(handler)),
+            memory: memory,
+            handle: handle,
+        }
+    }
+
+    pub fn get(&self, url: &str) -> IronResult<Response> {
+        self.request(Method::Get, url, Headers::new(), "")
+    }
+
+    pub fn request(&self, method: Method, url: &str, headers: Headers, body: &str) -> IronResult<Response> {
+        request::request(method, url, body, headers, &self.handler)
+    }
+
+    /// Waits for the logging thread to write every request made so
+    /// far, then returns their packets in order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the packets are not written within five seconds.
+    pub fn packets(self) -> Vec<LogPacket> {
+        assert!(self.handle.drain(DRAIN_TIMEOUT), "Timed out waiting for the logging thread");
+        self.memory.packets()
+    }
+}
+
+/// Makes one request through `handler`, wrapped in a default
+/// `StatisticLogger`, and returns its packet.
+pub fn log_request<H: Handler>(method: Method, url: &str, handler: H) -> LogPacket {
+    let handler = LoggedHandler::new(handler);
+    // Failed requests are logged too
+    let _ = handler.request(method, url, Headers::new(), "");
+    handler.packets().remove(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::{self, Read};
+    use std::thread;
+
+    use iron::status;
+
+    #[test]
+    fn the_status_is_captured() {
+        let packet = log_request(Method::Get, "http://127.0.0.1/missing", |_: &mut Request| {
+            Ok(Response::with(status::NotFound))
+        });
+        assert_eq!(packet.status(), Some(status::NotFound));
+        assert_eq!(packet.url().path(), vec!["missing"]);
+    }
+
+    #[test]
+    fn the_method_and_body_are_captured() {
+        let handler = LoggedHandler::new(|req: &mut Request| {
+            let mut body = String::new();
+            try!(req.body.read_to_string(&mut body).map_err(|e| IronError::new(e, status::BadRequest)));
+            Ok(Response::with((status::Ok, body)))
+        });
+        handler.request(Method::Post, "http://127.0.0.1/", Headers::new(), "hello").unwrap();
+        handler.request(Method::Delete, "http://127.0.0.1/", Headers::new(), "").unwrap();
+
+        let packets = handler.packets();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(*packets[0].method(), Method::Post);
+        assert_eq!(packets[0].response_len(), Some(5));
+        assert_eq!(*packets[1].method(), Method::Delete);
+    }
+
+    #[test]
+    fn the_handler_is_timed() {
+        let packet = log_request(Method::Get, "http://127.0.0.1/", |_: &mut Request| {
+            thread::sleep(Duration::from_millis(20));
+            Ok(Response::with(status::Ok))
+        });
+        assert!(packet.timing() >= Duration::from_millis(20), "{:?}", packet.timing());
+    }
+
+    #[test]
+    fn failed_requests_are_returned_too() {
+        let packet = log_request(Method::Get, "http://127.0.0.1/", |_: &mut Request| {
+            Err(IronError::new(io::Error::other("database is down"), status::ServiceUnavailable))
+        });
+        assert_eq!(packet.status(), Some(status::ServiceUnavailable));
+        assert_eq!(packet.error(), Some("database is down"));
+    }
+}